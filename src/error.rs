@@ -18,3 +18,188 @@ pub enum NexusError {
     #[error("expected \"#NEXUS\" at the start of the input")]
     InvalidHeader,
 }
+
+/// A type for errors when rooting trees
+#[derive(Error, Debug)]
+pub enum RootingError {
+    /// Taxa not present in the tree
+    #[error("taxa {taxa} not found in tree")]
+    MissingTaxa {
+        /// Taxa that could not be found
+        taxa: String,
+    },
+    /// Outgroup is empty or does not form a clade on either side of an edge
+    #[error("outgroup is not monophyletic")]
+    NonMonophyletic,
+    /// Tree does not have enough leaves to be rooted
+    #[error("tree must have at least {min} leaves")]
+    TooFewLeaves {
+        /// Minimum number of leaves required
+        min: usize,
+    },
+    /// Node is not an internal node of the tree
+    #[error("could not root tree at node {node}")]
+    InvalidNode {
+        /// Node that was requested as the root
+        node: String,
+    },
+    /// Tree could not be rerooted
+    #[error("could not reroot tree at ({parent}, {child})")]
+    InvalidEdge {
+        /// Parent of the edge
+        parent: String,
+        /// Child of the edge
+        child: String,
+    },
+    /// Trees are not on the same set of leaf taxa
+    #[error("trees do not have the same leaf taxa")]
+    TaxaMismatch,
+}
+
+/// A type for errors when editing the topology of trees
+#[derive(Error, Debug)]
+pub enum EditError {
+    /// Move does not describe an interchange around an edge of the tree
+    #[error("move does not describe an interchange around an edge of the tree")]
+    InvalidNNIMove,
+    /// No interchange exists around the edge ending in a node
+    #[error("no interchange around the edge ending in {node}")]
+    NoNNIMove {
+        /// Node at the lower end of the edge
+        node: String,
+    },
+    /// Node is the root or not present in the tree, so there is no edge above it
+    #[error("node {node} has no parent")]
+    MissingParent {
+        /// Node that was expected to have a parent
+        node: String,
+    },
+    /// Edge that is not an edge of the tree or cannot be used by the operation
+    #[error("({parent}, {child}) is not a valid edge for this operation")]
+    InvalidEdge {
+        /// Parent of the edge
+        parent: String,
+        /// Child of the edge
+        child: String,
+    },
+    /// Node that must have exactly two children does not
+    #[error("node {node} is not binary")]
+    NotBinary {
+        /// Node that is not binary
+        node: String,
+    },
+}
+
+/// A type for errors when computing distances between trees
+#[derive(Error, Debug)]
+pub enum DistanceError {
+    /// Edge without a weight where one is required
+    #[error("edge ({parent}, {child}) has no weight")]
+    MissingEdgeWeight {
+        /// Parent of the edge
+        parent: String,
+        /// Child of the edge
+        child: String,
+    },
+    /// Trees are not on the same set of leaf taxa
+    #[error("trees do not have the same leaf taxa")]
+    TaxaMismatch,
+    /// Tree is not binary where a binary tree is required
+    #[error("tree is not binary")]
+    NotBinary,
+    /// Trees do not have any leaf taxa in common
+    #[error("trees do not share any leaf taxa")]
+    NoSharedTaxa,
+}
+
+/// A type for errors when summarising a collection of trees
+#[derive(Error, Debug)]
+pub enum ConsensusError {
+    /// No trees to summarise
+    #[error("no trees to summarise")]
+    NoTrees,
+    /// Trees are not on the same set of leaf taxa
+    #[error("trees do not have the same leaf taxa")]
+    TaxaMismatch,
+    /// Majority threshold outside the range where clades are compatible
+    #[error("majority threshold {threshold} is not between 0.5 and 1")]
+    InvalidThreshold {
+        /// Threshold that was provided
+        threshold: f64,
+    },
+    /// Zeta values required by the summary are not set for every node
+    #[error("zeta values not set")]
+    ZetaNotSet,
+}
+
+/// A type for errors when annotating trees with branch supports
+#[derive(Error, Debug)]
+pub enum SupportError {
+    /// Node not present in the tree
+    #[error("node {node} not found in tree")]
+    MissingNode {
+        /// Node that could not be found
+        node: String,
+    },
+    /// Support that cannot be written in the requested form
+    #[error("support {support} cannot be written to node {node}")]
+    InvalidSupport {
+        /// Node that was annotated
+        node: String,
+        /// Support that could not be written
+        support: f64,
+    },
+}
+
+/// A type for errors when building trees from distances
+#[derive(Error, Debug)]
+pub enum BuildError {
+    /// Distance matrix does not have one row and column per taxa
+    #[error("distance matrix must have one row and column per taxa")]
+    NotSquare,
+    /// Distance matrix is not symmetric
+    #[error("distance matrix is not symmetric at ({row}, {col})")]
+    Asymmetric {
+        /// Row of the asymmetric entry
+        row: usize,
+        /// Column of the asymmetric entry
+        col: usize,
+    },
+    /// Distance matrix does not have enough taxa to build a tree
+    #[error("distance matrix must have at least {min} taxa")]
+    TooFewTaxa {
+        /// Minimum number of taxa required
+        min: usize,
+    },
+    /// Leaves of a tree do not match the taxa of the distance matrix
+    #[error("tree leaves do not match the taxa of the distance matrix")]
+    TaxaMismatch,
+    /// Tree could not be rooted
+    #[error(transparent)]
+    Rooting(#[from] RootingError),
+}
+
+/// A type for errors when simulating trees
+#[derive(Error, Debug)]
+pub enum SimulationError {
+    /// Parameter of a simulation outside its valid range
+    #[error("invalid {name}")]
+    InvalidParameter {
+        /// Name of the parameter
+        name: String,
+    },
+    /// Edge without a weight where one is required
+    #[error("edge ({parent}, {child}) has no weight")]
+    MissingEdgeWeight {
+        /// Parent of the edge
+        parent: String,
+        /// Child of the edge
+        child: String,
+    },
+    /// Taxa not present in the tree
+    #[error("taxa {taxa} not found in tree")]
+    MissingTaxa {
+        /// Taxa that could not be found
+        taxa: String,
+    },
+}
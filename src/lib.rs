@@ -13,11 +13,16 @@
 //! **We also provide a struct so you don't have to implement one...**  
 //!
 //! # Using `phylo`
-//! Most of the functionality is implemented in [`crate::tree::simple_rtree`]. The
+//! Most of the functionality is implemented in [`crate::tree::simple_rtree`], with unrooted trees described in [`crate::tree::simple_utree`]. The
 //! [`crate::tree::ops`] module is used to dealt with phylolgenetic analysis that require tree mutations such as SPR, NNI, etc.
-//! [`crate::tree::simulation`] module is used to simulate random trees
+//! [`crate::tree::rooting`] module is used to root trees by midpoint, outgroup or minimal ancestor deviation
+//! [`crate::tree::simulation`] module is used to simulate random trees under the Yule, uniform, birth-death, coalescent and multispecies coalescent models
+//! [`crate::tree::build`] module is used to build trees from pairwise distances between taxa by neighbor-joining, BIONJ, average linkage and balanced minimum evolution
+//! [`crate::tree::consensus`] module is used to summarise collections of trees by consensus, median and maximum clade credibility trees
+//! [`crate::tree::support`] module is used to compute and annotate branch supports from replicate trees
 //! [`crate::tree::io`] module is used to read trees from various encodings
 //! [`crate::tree::distances`] module is used to compute various types of distance between nodes in a tree and between trees
+//! [`crate::tree::agreement`] module is used to compute maximum agreement subtrees and forests of two trees, and SPR distances
 //! [`crate::iter`] is a helper module to provide tree traversals and iterations.
 //!
 //! ## Building trees
@@ -192,16 +197,33 @@ pub mod prelude {
     #[doc(no_inline)]
     pub use crate::node::{simple_rnode::*, Node, PhyloNode};
     #[doc(no_inline)]
+    pub use crate::node::{simple_unode::*, UnrootedNode, PhyloUnrootedNode};
+    #[doc(no_inline)]
+    pub use crate::tree::agreement::*;
+    #[doc(no_inline)]
+    pub use crate::tree::build::*;
+    #[doc(no_inline)]
+    pub use crate::tree::consensus::*;
+    #[doc(no_inline)]
     pub use crate::tree::distances::*;
     #[doc(no_inline)]
     pub use crate::tree::io::*;
     #[doc(no_inline)]
     pub use crate::tree::ops::*;
     #[doc(no_inline)]
+    pub use crate::tree::rooting::*;
+    #[doc(no_inline)]
     pub use crate::tree::simple_rtree::*;
     #[doc(no_inline)]
+    pub use crate::tree::simple_utree::*;
+    #[doc(no_inline)]
     pub use crate::tree::simulation::*;
+    #[doc(no_inline)]
+    pub use crate::tree::support::*;
 
     #[cfg(feature = "simple_rooted_tree")]
     pub use crate::tree::{SimpleRootedTree, PhyloTree};
+
+    #[cfg(feature = "simple_unrooted_tree")]
+    pub use crate::tree::{SimpleUnrootedTree, PhyloUnrootedTree};
 }
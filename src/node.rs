@@ -2,10 +2,13 @@
 
 /// Module with traits of rooted tree nodes
 pub mod simple_rnode;
+/// Module with traits of unrooted tree nodes
+pub mod simple_unode;
 
 use crate::node::simple_rnode::{
     RootedMetaNode, RootedTreeNode, RootedWeightedNode, RootedZetaNode, EdgeWeight, NodeWeight, NodeTaxa
 };
+use crate::node::simple_unode::{UnrootedMetaNode, UnrootedTreeNode, UnrootedWeightedNode};
 use std::fmt::{Debug, Display};
 
 /// Default NodeID type 
@@ -17,6 +20,9 @@ pub type PhyloNode = Node<String,f32,f32>;
 /// Default NodeID type 
 pub type DemoNode = Node<u32,f32,f32>;
 
+/// Default unrooted node type
+pub type PhyloUnrootedNode = UnrootedNode<String,f32>;
+
 /// A node structure in an arena-memory managed tree, linking to connected neighbours via NodeID
 #[derive(Clone)]
 pub struct Node<T,W,Z> 
@@ -193,3 +199,144 @@ where
         )
     }
 }
+
+/// A node structure in an arena-memory managed unrooted tree, linking to adjacent nodes via NodeID
+#[derive(Clone)]
+pub struct UnrootedNode<T,W>
+where
+    T: NodeTaxa,
+    W: EdgeWeight,
+{
+    /// A unique identifier for a node
+    id: NodeID,
+    /// Adjacent nodes along with the weight of the connecting edge
+    neighbours: Vec<(NodeID, Option<W>)>,
+    /// Taxa annotation of node
+    taxa: Option<T>,
+}
+
+impl<T,W> UnrootedTreeNode for UnrootedNode<T,W>
+where
+    T: NodeTaxa,
+    W: EdgeWeight,
+{
+    type NodeID = NodeID;
+
+    fn new(id: Self::NodeID) -> Self {
+        UnrootedNode {
+            id,
+            neighbours: vec![],
+            taxa: None,
+        }
+    }
+
+    fn get_id(&self) -> Self::NodeID {
+        self.id
+    }
+
+    fn set_id(&mut self, id: Self::NodeID) {
+        self.id = id
+    }
+
+    fn get_neighbours(&self) -> impl ExactSizeIterator<Item = Self::NodeID> + DoubleEndedIterator {
+        self.neighbours
+            .iter()
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    fn add_neighbour(&mut self, neighbour: Self::NodeID) {
+        if !self.has_neighbour(&neighbour) {
+            self.neighbours.push((neighbour, None));
+        }
+    }
+
+    fn remove_neighbour(&mut self, neighbour: &Self::NodeID) {
+        self.neighbours.retain(|(id, _)| id != neighbour);
+    }
+
+    fn has_neighbour(&self, neighbour: &Self::NodeID) -> bool {
+        self.neighbours.iter().any(|(id, _)| id == neighbour)
+    }
+
+    fn degree(&self) -> usize {
+        self.neighbours.len()
+    }
+}
+
+impl<T,W> UnrootedMetaNode for UnrootedNode<T,W>
+where
+    T: NodeTaxa,
+    W: EdgeWeight,
+{
+    type Meta = T;
+
+    fn get_taxa<'a>(&'a self) -> Option<&'a Self::Meta> {
+        self.taxa.as_ref()
+    }
+
+    fn set_taxa(&mut self, taxa: Option<Self::Meta>) {
+        self.taxa = taxa;
+    }
+}
+
+impl<T,W> UnrootedWeightedNode for UnrootedNode<T,W>
+where
+    T: NodeTaxa,
+    W: EdgeWeight,
+{
+    type Weight = W;
+
+    fn get_neighbour_weight(&self, neighbour: &Self::NodeID) -> Option<Self::Weight> {
+        self.neighbours
+            .iter()
+            .find(|(id, _)| id == neighbour)
+            .and_then(|(_, w)| *w)
+    }
+
+    fn set_neighbour_weight(&mut self, neighbour: &Self::NodeID, w: Option<Self::Weight>) {
+        if let Some(edge) = self.neighbours.iter_mut().find(|(id, _)| id == neighbour) {
+            edge.1 = w;
+        }
+    }
+}
+
+impl<T,W> Debug for UnrootedNode<T,W>
+where
+    T: NodeTaxa,
+    W: EdgeWeight,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}:{:?}",
+            self.get_id(),
+            self.node_type(),
+            match self.get_taxa() {
+                None => "No Taxa".to_string(),
+                Some(t) => t.to_string(),
+            },
+            self.neighbours,
+        )
+    }
+}
+
+impl<T,W> Display for UnrootedNode<T,W>
+where
+    T: NodeTaxa,
+    W: EdgeWeight,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}",
+            self.get_id(),
+            self.node_type(),
+            match self.get_taxa() {
+                None => "None".to_string(),
+                Some(t) => t.to_string(),
+            },
+        )
+    }
+}
@@ -1,51 +1,117 @@
-use std::fmt::{Debug, Display};
-use std::hash::Hash;
-use std::ops::{Add, AddAssign, Sub, SubAssign};
-
-pub trait UnrootedTreeNode{
-    type Weight: Display + Debug + Clone + Add<Output=Self::Weight> + AddAssign + Sub<Output=Self::Weight> + SubAssign;
-    type Taxa: Display + Debug + Eq + PartialEq + Clone + Ord;
-    type NodeID: Display + Debug + Hash + Copy + Clone + Ord + Add<Output = Self::NodeID> + AddAssign + Sub<Output = Self::NodeID> + SubAssign;
-    
-    fn new(id: Self::NodeID, is_leaf: bool)->Self;
-    fn is_leaf(&self)->bool;
-    fn flip(&mut self);
-    fn get_taxa(&self)->Option<Self::Taxa>;
-    fn get_taxa_mut(&mut self)->Option<Self::Taxa>;
-    fn set_taxa(&mut self, taxa: Option<Self::Taxa>);
-    fn get_id(&self)->Self::NodeID;
-    fn get_id_mut(&mut self)->Self::NodeID;
+use itertools::Itertools;
+use std::{
+    fmt::{Debug, Display},
+    hash::Hash,
+};
+
+use crate::node::simple_rnode::{EdgeWeight, NodeTaxa};
+
+/// A trait describing the behaviour of a Node in an unrooted tree
+pub trait UnrootedTreeNode
+where
+    Self: Clone,
+{
+    /// Associate type for node identifier. Should be unique within a tree
+    type NodeID: Display + Debug + Hash + Ord + PartialEq + Eq + Copy;
+
+    /// Creates a new node with provided id
+    fn new(id: Self::NodeID) -> Self;
+
+    /// Returns id of node
+    fn get_id(&self) -> Self::NodeID;
+
+    /// Changes id of node
     fn set_id(&mut self, id: Self::NodeID);
-    fn get_neighbours(&self)->&Vec<(Self::NodeID, Weight)>;
-    fn get_neighbours_mut(&mut self)->&Vec<(Self::NodeID, Weight)>;
-    fn add_neighbour(&mut self, child:(Self::NodeID, Weight));
-    fn remove_neighbour(&mut self, child:Self::NodeID);
-    fn node_type(&self)->String{
+
+    /// Returns Iterator containing neighbour node ids
+    fn get_neighbours(&self) -> impl ExactSizeIterator<Item = Self::NodeID> + DoubleEndedIterator;
+
+    /// Add NodeID to node neighbours
+    fn add_neighbour(&mut self, neighbour: Self::NodeID);
+
+    /// Remove NodeID from node neighbours
+    fn remove_neighbour(&mut self, neighbour: &Self::NodeID);
+
+    /// Returns true if node is adjacent to neighbour
+    fn has_neighbour(&self, neighbour: &Self::NodeID) -> bool {
+        self.get_neighbours().contains(neighbour)
+    }
+
+    /// Returns degree of node.
+    fn degree(&self) -> usize {
+        self.get_neighbours().len()
+    }
+
+    /// Checks if node is a leaf node
+    fn is_leaf(&self) -> bool {
+        self.degree() < 2
+    }
+
+    /// Returns Node type as String
+    fn node_type(&self) -> String {
         match self.is_leaf() {
             false => "Internal".to_string(),
             true => "Leaf".to_string(),
         }
     }
-    fn add_neighbours(&mut self, neighbours: Vec<(Self::NodeID, Weight)>){
-        for neighbour in neighbours.into_iter(){
+
+    /// Adds NodeIDs from Iterator as neighbours
+    fn add_neighbours(&mut self, neighbours: impl Iterator<Item = Self::NodeID>) {
+        for neighbour in neighbours {
             self.add_neighbour(neighbour);
         }
     }
-    fn remove_neighbours(&mut self, neighbours: Vec<Self::NodeID>){
-        for neighbour in neighbours.into_iter(){
-            self.remove_neighbour(neighbours);
+
+    /// Removes NodeIDs from Iterator from node neighbours
+    fn remove_neighbours(&mut self, neighbours: impl Iterator<Item = Self::NodeID>) {
+        for neighbour in neighbours {
+            self.remove_neighbour(&neighbour);
         }
     }
-    fn to_leaf(&mut self){
-        match self.is_leaf(){
-            true => {},
-            false => {self.flip()}
+
+    /// Removes all neighbours from node
+    fn remove_all_neighbours(&mut self) {
+        let neighbours = self.get_neighbours().collect_vec();
+        for neighbour in neighbours {
+            self.remove_neighbour(&neighbour);
         }
     }
-    fn to_internal(&mut self){
-        match self.is_leaf(){
-            true => {self.flip()},
-            false => {}
+}
+
+/// A trait describing the behaviour of a Node in an unrooted tree that carries node annotations
+pub trait UnrootedMetaNode: UnrootedTreeNode {
+    /// Meta annotation of node
+    type Meta: NodeTaxa;
+
+    /// Returns node annotation
+    fn get_taxa<'a>(&'a self) -> Option<&'a Self::Meta>;
+
+    /// Sets node annotation
+    fn set_taxa(&mut self, taxa: Option<Self::Meta>);
+}
+
+/// A trait describing the behaviour of a Node in an unrooted tree that has numeric edge annotations
+pub trait UnrootedWeightedNode: UnrootedTreeNode {
+    /// Weight of edges incident on node
+    type Weight: EdgeWeight;
+
+    /// Returns weight of edge between node and neighbour
+    fn get_neighbour_weight(&self, neighbour: &Self::NodeID) -> Option<Self::Weight>;
+
+    /// Sets weight of edge between node and neighbour. Does nothing if neighbour is not adjacent to node
+    fn set_neighbour_weight(&mut self, neighbour: &Self::NodeID, w: Option<Self::Weight>);
+
+    /// Adds a neighbour with a weighted edge
+    fn add_weighted_neighbour(&mut self, neighbour: Self::NodeID, w: Option<Self::Weight>) {
+        self.add_neighbour(neighbour);
+        self.set_neighbour_weight(&neighbour, w);
+    }
+
+    /// Sets weights of all edges incident on node as None
+    fn unweight(&mut self) {
+        let neighbours = self.get_neighbours().collect_vec();
+        for neighbour in neighbours {
+            self.set_neighbour_weight(&neighbour, None);
         }
     }
 }
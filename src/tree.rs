@@ -1,25 +1,39 @@
 #![allow(clippy::needless_lifetimes)]
+/// Module with traits and structs for agreement subtrees and forests
+pub mod agreement;
+/// Module with traits and structs for building trees from distances
+pub mod build;
+/// Module with traits and structs for consensus trees
+pub mod consensus;
 /// Module with traits and structs for distance computation
 pub mod distances;
 /// Module with traits and structs for tree encoding
 pub mod io;
 /// Module with traits and structs for tree operations
 pub mod ops;
+/// Module with traits and structs for rooting trees
+pub mod rooting;
 /// Module with traits and structs for general tree traits
 pub mod simple_rtree;
+/// Module with traits and structs for general unrooted tree traits
+pub mod simple_utree;
 /// Module with traits and structs for tree simulation
 pub mod simulation;
+/// Module with traits and structs for branch support
+pub mod support;
 
 #[cfg(feature = "simple_rooted_tree")]
 pub use simple_rooted_tree::*;
 
 #[cfg(feature = "simple_rooted_tree")]
 mod simple_rooted_tree {
-    use super::simulation::{Uniform, Yule};
+    use super::simulation::{
+        BirthDeath, Coalescent, MultispeciesCoalescent, RandomEdgeWeights, SimulatedTaxa, Uniform, Yule,
+    };
     use std::ops::Index;
 
     use itertools::Itertools;
-    use rand::prelude::IteratorRandom;
+    use rand::prelude::{IteratorRandom, Rng};
 
     use crate::iter::{BFSIterator, DFSPostOrderIterator};
     use crate::node::{Node, NodeID};
@@ -118,6 +132,17 @@ mod simple_rooted_tree {
         pub fn get_capacity(&self)->usize{
             self.nodes.len()
         }
+
+        /// Recomputes the constant-time LCA structures after a change in topology, if they were precomputed
+        fn refresh_precomputed_lca(&mut self) {
+            if self.precomputed_euler.is_some()
+                || self.precomputed_fai.is_some()
+                || self.precomputed_da.is_some()
+                || self.precomputed_rmq.is_some()
+            {
+                self.precompute_constant_time_lca();
+            }
+        }
     }
 
     impl<T,W,Z> RootedTree for SimpleRootedTree<T,W,Z> 
@@ -263,6 +288,12 @@ mod simple_rooted_tree {
         }
 
         fn set_node_taxa(&mut self, node_id: TreeNodeID<Self>, taxa: Option<TreeNodeMeta<Self>>) {
+            // the old taxa may already have been reassigned to another node
+            if let Some(old_taxa) = self.get_node_taxa(node_id).cloned() {
+                if self.taxa_node_id_map.get(&old_taxa) == Some(&node_id) {
+                    self.taxa_node_id_map.remove(&old_taxa);
+                }
+            }
             self.get_node_mut(node_id).unwrap().set_taxa(taxa.clone());
             if let Some(t) = taxa {
                 self.taxa_node_id_map.insert(t, node_id);
@@ -288,7 +319,7 @@ mod simple_rooted_tree {
         W: EdgeWeight,
         Z: NodeWeight,
     {
-        fn yule(num_taxa: usize) -> SimpleRootedTree<T,W,Z> {
+        fn yule_with_rng<R: Rng + ?Sized>(num_taxa: usize, rng: &mut R) -> SimpleRootedTree<T,W,Z> {
             let mut tree = SimpleRootedTree::new(0);
             if num_taxa < 3 {
                 return tree;
@@ -306,7 +337,7 @@ mod simple_rooted_tree {
             for i in 2..num_taxa {
                 let rand_leaf_id = current_leaf_ids
                     .iter()
-                    .choose(&mut rand::thread_rng())
+                    .choose(rng)
                     .unwrap();
                 let rand_leaf_parent_id = tree.get_node_parent_id(*rand_leaf_id).unwrap();
                 let split_node =  Node::new(tree.next_id());
@@ -322,13 +353,48 @@ mod simple_rooted_tree {
         }
     }
 
+    impl<T,W,Z> BirthDeath for SimpleRootedTree<T,W,Z> 
+    where 
+        T: NodeTaxa,
+        W: EdgeWeight,
+        Z: NodeWeight,
+    {}
+
+    impl<T,W,Z> Coalescent for SimpleRootedTree<T,W,Z> 
+    where 
+        T: NodeTaxa,
+        W: EdgeWeight,
+        Z: NodeWeight,
+    {}
+
+    impl<T,W,Z> MultispeciesCoalescent for SimpleRootedTree<T,W,Z> 
+    where 
+        T: NodeTaxa,
+        W: EdgeWeight,
+        Z: NodeWeight,
+    {}
+
+    impl<T,W,Z> SimulatedTaxa for SimpleRootedTree<T,W,Z> 
+    where 
+        T: NodeTaxa,
+        W: EdgeWeight,
+        Z: NodeWeight,
+    {}
+
+    impl<T,W,Z> RandomEdgeWeights for SimpleRootedTree<T,W,Z> 
+    where 
+        T: NodeTaxa,
+        W: EdgeWeight,
+        Z: NodeWeight,
+    {}
+
     impl<T,W,Z> Uniform for SimpleRootedTree<T,W,Z> 
     where 
         T: NodeTaxa,
         W: EdgeWeight,
         Z: NodeWeight,
     {
-        fn unif(num_taxa: usize) -> SimpleRootedTree<T,W,Z> {
+        fn unif_with_rng<R: Rng + ?Sized>(num_taxa: usize, rng: &mut R) -> SimpleRootedTree<T,W,Z> {
             let mut tree = SimpleRootedTree::new(0);
             if num_taxa < 3 {
                 return tree;
@@ -346,7 +412,7 @@ mod simple_rooted_tree {
             for i in 2..num_taxa {
                 let rand_leaf_id = *current_node_ids
                     .iter()
-                    .choose(&mut rand::thread_rng())
+                    .choose(rng)
                     .unwrap();
                 let rand_leaf_parent_id = tree.get_node_parent_id(rand_leaf_id).unwrap();
                 let split_node =  Node::new(tree.next_id());
@@ -426,6 +492,103 @@ mod simple_rooted_tree {
         Z: NodeWeight,
     {}
 
+    impl<T,W,Z> QuartetDistance for SimpleRootedTree<T,W,Z> 
+    where 
+        T: NodeTaxa,
+        W: EdgeWeight,
+        Z: NodeWeight,
+    {}
+
+    impl<T,W,Z> TripletDistance for SimpleRootedTree<T,W,Z> 
+    where 
+        T: NodeTaxa,
+        W: EdgeWeight,
+        Z: NodeWeight,
+    {}
+
+    impl<T,W,Z> PathDifference for SimpleRootedTree<T,W,Z> 
+    where 
+        T: NodeTaxa,
+        W: EdgeWeight,
+        Z: NodeWeight,
+    {}
+
+    impl<T,W,Z> MaximumAgreementSubtree for SimpleRootedTree<T,W,Z> 
+    where 
+        T: NodeTaxa,
+        W: EdgeWeight,
+        Z: NodeWeight,
+    {}
+
+    impl<T,W,Z> SprDistance for SimpleRootedTree<T,W,Z> 
+    where 
+        T: NodeTaxa,
+        W: EdgeWeight,
+        Z: NodeWeight,
+    {}
+
+    impl<T,W,Z> TransferSupport for SimpleRootedTree<T,W,Z> 
+    where 
+        T: NodeTaxa,
+        W: EdgeWeight,
+        Z: NodeWeight,
+    {}
+
+    impl<T,W,Z> SplitSupport for SimpleRootedTree<T,W,Z> 
+    where 
+        T: NodeTaxa,
+        W: EdgeWeight,
+        Z: NodeWeight,
+    {}
+
+    impl<T,W,Z> Consensus for SimpleRootedTree<T,W,Z> 
+    where 
+        T: NodeTaxa,
+        W: EdgeWeight,
+        Z: NodeWeight,
+    {}
+
+    impl<T,W,Z> MaximumCladeCredibility for SimpleRootedTree<T,W,Z> 
+    where 
+        T: NodeTaxa,
+        W: EdgeWeight,
+        Z: NodeWeight,
+    {}
+
+    impl<T,W,Z> AdamsConsensus for SimpleRootedTree<T,W,Z> 
+    where 
+        T: NodeTaxa,
+        W: EdgeWeight,
+        Z: NodeWeight,
+    {}
+
+    impl<T,W,Z> MedianTree for SimpleRootedTree<T,W,Z> 
+    where 
+        T: NodeTaxa,
+        W: EdgeWeight,
+        Z: NodeWeight,
+    {}
+
+    impl<T,W,Z> GeneralizedRobinsonFoulds for SimpleRootedTree<T,W,Z> 
+    where 
+        T: NodeTaxa,
+        W: EdgeWeight,
+        Z: NodeWeight,
+    {}
+
+    impl<T,W,Z> WeightedRobinsonFoulds for SimpleRootedTree<T,W,Z> 
+    where 
+        T: NodeTaxa,
+        W: EdgeWeight,
+        Z: NodeWeight,
+    {}
+    impl<T,W,Z> BranchScore for SimpleRootedTree<T,W,Z> 
+    where 
+        T: NodeTaxa,
+        W: EdgeWeight,
+        Z: NodeWeight,
+    {}
+
     impl<T,W,Z> DistanceMatrix for SimpleRootedTree<T,W,Z> 
     where 
         T: NodeTaxa,
@@ -983,36 +1146,185 @@ mod simple_rooted_tree {
     {
         fn graft(
             &mut self,
-            mut tree: Self,
+            tree: Self,
             edge: (TreeNodeID<Self>, TreeNodeID<Self>),
         ) -> Result<(), ()> {
+            let (parent_id, child_id) = edge;
+            if child_id >= self.nodes.len()
+                || self.nodes[child_id].is_none()
+                || self.get_node_parent_id(child_id) != Some(parent_id)
+            {
+                return Err(());
+            }
+            // nodes of the grafted tree keep their NodeIDs unless these are already in use
+            let mut next_id = self.nodes.len().max(tree.nodes.len());
+            let id_map: HashMap<NodeID, NodeID> = tree
+                .get_node_ids()
+                .map(|id| match id < self.nodes.len() && self.nodes[id].is_some() {
+                    true => {
+                        next_id += 1;
+                        (id, next_id - 1)
+                    }
+                    false => (id, id),
+                })
+                .collect();
+            for node in tree.get_nodes() {
+                let mut node = node.clone();
+                let children_ids = node.get_children().collect_vec();
+                node.remove_all_children();
+                node.add_children(children_ids.iter().map(|id| id_map[id]));
+                node.set_id(id_map[&node.get_id()]);
+                node.set_parent(node.get_parent().map(|id| id_map[&id]));
+                self.set_node(node);
+            }
             let new_node = self.next_node();
             let new_node_id = new_node.get_id();
-            for node in tree.get_nodes_mut() {
-                node.set_id(self.next_node().get_id());
-                self.set_node(node.clone());
-            }
             self.split_edge(edge, new_node);
-            self.set_child(new_node_id, tree.get_root_id());
+            self.set_child(new_node_id, id_map[&tree.get_root_id()]);
+            self.refresh_precomputed_lca();
             Ok(())
         }
         fn prune(&mut self, node_id: TreeNodeID<Self>) -> Result<Self, ()> {
-            let mut pruned_tree = SimpleRootedTree::new(node_id);
-            let p_id = self.get_node_parent_id(node_id).unwrap();
+            if node_id >= self.nodes.len() || self.nodes[node_id].is_none() {
+                return Err(());
+            }
+            let p_id = self.get_node_parent_id(node_id).ok_or(())?;
             self.get_node_mut(p_id).unwrap().remove_child(&node_id);
-            pruned_tree
-                .get_node_mut(pruned_tree.get_root_id())
-                .unwrap()
-                .add_children(self.get_node(node_id).unwrap().get_children());
-            let dfs = self.dfs(node_id).collect_vec();
-            for node in dfs {
-                // self.nodes.remove(node.get_id());
-                pruned_tree.set_node(node.clone());
+            let mut pruned_tree = SimpleRootedTree::new(node_id);
+            let subtree_ids = self.dfs(node_id).map(|node| node.get_id()).collect_vec();
+            for id in subtree_ids {
+                let node = self.nodes[id].take().unwrap();
+                if let Some(taxa) = node.get_taxa() {
+                    self.taxa_node_id_map.remove(taxa);
+                }
+                pruned_tree.set_node(node);
             }
+            pruned_tree.get_node_mut(node_id).unwrap().set_parent(None);
+            self.refresh_precomputed_lca();
             Ok(pruned_tree)
         }
     }
 
+    impl<T,W,Z> TBR for SimpleRootedTree<T,W,Z> 
+    where 
+        T: NodeTaxa,
+        W: EdgeWeight,
+        Z: NodeWeight,
+    {}
+
+    impl<T,W,Z> NNI<'_> for SimpleRootedTree<T,W,Z>
+    where
+        T: NodeTaxa,
+        W: EdgeWeight,
+        Z: NodeWeight,
+    {
+        fn apply_nni(&mut self, nni_move: NNIMove<TreeNodeID<Self>>) -> Result<(), EditError> {
+            let (parent_id, node_id) = nni_move.edge;
+            let is_valid = [parent_id, node_id, nni_move.sibling, nni_move.child]
+                .into_iter()
+                .all(|id| id < self.nodes.len() && self.nodes[id].is_some())
+                && node_id != nni_move.sibling
+                && self.get_node_parent_id(node_id) == Some(parent_id)
+                && self.get_node_parent_id(nni_move.sibling) == Some(parent_id)
+                && self.get_node_parent_id(nni_move.child) == Some(node_id);
+            if !is_valid {
+                return Err(EditError::InvalidNNIMove);
+            }
+            self.remove_child(parent_id, nni_move.sibling);
+            self.remove_child(node_id, nni_move.child);
+            self.set_child(parent_id, nni_move.child);
+            self.set_child(node_id, nni_move.sibling);
+            self.refresh_precomputed_lca();
+            Ok(())
+        }
+    }
+
+    impl<T,W,Z> Reroot<'_> for SimpleRootedTree<T,W,Z>
+    where
+        T: NodeTaxa,
+        W: EdgeWeight,
+        Z: NodeWeight,
+    {
+        fn reroot_at_node(&mut self, node_id: TreeNodeID<Self>) -> Result<(), RootingError> {
+            if node_id >= self.nodes.len() || self.nodes[node_id].is_none() || self.is_leaf(node_id) {
+                return Err(RootingError::InvalidNode {
+                    node: node_id.to_string(),
+                });
+            }
+            let old_root_id = self.get_root_id();
+            if node_id == old_root_id {
+                return Ok(());
+            }
+            // reverse edges along the path from the old root down to the new root.
+            // the weight of an edge is held by its child, so it moves to the new child.
+            let path_edges = self
+                .node_to_root_ids(node_id)
+                .tuple_windows()
+                .collect_vec();
+            for (child_id, parent_id) in path_edges.into_iter().rev() {
+                let weight = self.get_node(child_id).unwrap().get_weight();
+                self.remove_child(parent_id, child_id);
+                self.set_child(child_id, parent_id);
+                self.get_node_mut(parent_id).unwrap().set_weight(weight);
+            }
+            let new_root = self.get_node_mut(node_id).unwrap();
+            new_root.set_parent(None);
+            new_root.set_weight(None);
+            self.set_root(node_id);
+
+            // the old root is left as a unifurcation (or a dangling node) which is suppressed
+            let old_root = self.get_node(old_root_id).unwrap().clone();
+            if old_root.get_taxa().is_none() && old_root.num_children() < 2 {
+                let old_root_parent_id = old_root.get_parent().unwrap();
+                self.remove_node(old_root_id);
+                if let Some(child_id) = old_root.get_children().next() {
+                    let weight = match (self.get_node(child_id).unwrap().get_weight(), old_root.get_weight()) {
+                        (Some(w1), Some(w2)) => Some(w1 + w2),
+                        (w1, w2) => w1.or(w2),
+                    };
+                    self.set_child(old_root_parent_id, child_id);
+                    self.get_node_mut(child_id).unwrap().set_weight(weight);
+                }
+            }
+            self.refresh_precomputed_lca();
+            Ok(())
+        }
+
+        fn reroot_at_edge(&mut self, edge: (TreeNodeID<Self>, TreeNodeID<Self>)) -> Result<(), RootingError> {
+            let (parent_id, child_id) = edge;
+            if parent_id >= self.nodes.len()
+                || child_id >= self.nodes.len()
+                || self.nodes[parent_id].is_none()
+                || self.nodes[child_id].is_none()
+                || self.get_node_parent_id(child_id) != Some(parent_id)
+            {
+                return Err(RootingError::InvalidEdge {
+                    parent: parent_id.to_string(),
+                    child: child_id.to_string(),
+                });
+            }
+            // the new root splits the edge, with the edge weight divided equally on either side
+            let split_node = self.next_node();
+            let split_node_id = split_node.get_id();
+            let half_weight = self
+                .get_node(child_id)
+                .unwrap()
+                .get_weight()
+                .map(|w| w / (W::one() + W::one()));
+            self.split_edge(edge, split_node);
+            self.get_node_mut(split_node_id).unwrap().set_weight(half_weight);
+            self.get_node_mut(child_id).unwrap().set_weight(half_weight);
+            self.reroot_at_node(split_node_id)
+        }
+    }
+
+    impl<T,W,Z> Rooting for SimpleRootedTree<T,W,Z>
+    where
+        T: NodeTaxa,
+        W: EdgeWeight,
+        Z: NodeWeight,
+    {}
+
     impl<T,W,Z> Balance for SimpleRootedTree<T,W,Z> 
     where 
         T: NodeTaxa,
@@ -1069,4 +1381,322 @@ mod simple_rooted_tree {
         W: EdgeWeight,
         Z: NodeWeight,
     {}
+
+    impl<T,W,Z> NeighborJoining for SimpleRootedTree<T,W,Z> 
+    where 
+        T: NodeTaxa,
+        W: EdgeWeight,
+        Z: NodeWeight,
+    {}
+    impl<T,W,Z> AverageLinkage for SimpleRootedTree<T,W,Z> 
+    where 
+        T: NodeTaxa,
+        W: EdgeWeight,
+        Z: NodeWeight,
+    {}
+    impl<T,W,Z> BalancedMinimumEvolution for SimpleRootedTree<T,W,Z> 
+    where 
+        T: NodeTaxa,
+        W: EdgeWeight,
+        Z: NodeWeight,
+    {}
+}
+
+#[cfg(feature = "simple_unrooted_tree")]
+pub use simple_unrooted_tree::*;
+
+#[cfg(feature = "simple_unrooted_tree")]
+mod simple_unrooted_tree {
+    use std::collections::VecDeque;
+
+    use itertools::Itertools;
+
+    use crate::node::{Node, NodeID, UnrootedNode};
+    use crate::prelude::*;
+
+    #[cfg(feature = "non_crypto_hash")]
+    use fxhash::FxHashMap as HashMap;
+    #[cfg(not(feature = "non_crypto_hash"))]
+    use std::collections::HashMap;
+
+    use super::SimpleRootedTree;
+
+    /// Type alias for unrooted Phylogenetic tree.
+    pub type PhyloUnrootedTree = SimpleUnrootedTree<String,f32>;
+
+    /// Arena memory-managed unrooted tree struct
+    #[derive(Debug, Clone)]
+    pub struct SimpleUnrootedTree<T,W>
+    where
+        T: NodeTaxa,
+        W: EdgeWeight,
+    {
+        /// Nodes of the tree
+        pub nodes: Vec<Option<UnrootedNode<T,W>>>,
+        /// Index of nodes by taxa
+        pub taxa_node_id_map: HashMap<T, NodeID>,
+    }
+
+    impl<T,W> SimpleUnrootedTree<T,W>
+    where
+        T: NodeTaxa,
+        W: EdgeWeight,
+    {
+        /// Returns next available NodeID
+        pub fn next_id(&self) -> usize {
+            match &self.nodes.iter().position(|r| r.is_none()) {
+                Some(x) => *x,
+                None => self.nodes.len(),
+            }
+        }
+
+        /// Creates new node with next NodeID
+        pub fn next_node(&self) -> UnrootedNode<T,W> {
+            UnrootedNode::new(self.next_id())
+        }
+
+        /// Returns a rooted tree with the root at an internal node. NodeIDs are preserved.
+        pub fn root_at_node<Z: NodeWeight>(&self, node_id: NodeID) -> Result<SimpleRootedTree<T,W,Z>, RootingError> {
+            if !self.contains_node(node_id) || self.is_leaf(node_id) {
+                return Err(RootingError::InvalidNode {
+                    node: node_id.to_string(),
+                });
+            }
+            let mut nodes = vec![None; self.nodes.len()];
+            nodes[node_id] = Some(Node::new(node_id));
+            let mut tree = SimpleRootedTree::from_nodes(nodes, node_id);
+            self.grow_rooted(&mut tree, node_id, None);
+            Ok(tree)
+        }
+
+        /// Returns a rooted tree with a new root splitting an edge. Edge weight is split equally on both sides of the root. NodeIDs of existing nodes are preserved.
+        pub fn root_at_edge<Z: NodeWeight>(&self, edge: (NodeID, NodeID)) -> Result<SimpleRootedTree<T,W,Z>, RootingError> {
+            let (node_1, node_2) = edge;
+            if !self.contains_node(node_1)
+                || !self.get_node(node_1).unwrap().has_neighbour(&node_2)
+            {
+                return Err(RootingError::InvalidEdge {
+                    parent: node_1.to_string(),
+                    child: node_2.to_string(),
+                });
+            }
+            let root_id = self.next_id();
+            let mut nodes = vec![None; self.nodes.len().max(root_id + 1)];
+            nodes[root_id] = Some(Node::new(root_id));
+            let mut tree = SimpleRootedTree::from_nodes(nodes, root_id);
+            let half_weight = self
+                .get_edge_weight(node_1, node_2)
+                .map(|w| w / (W::one() + W::one()));
+            for (child_id, other_id) in [(node_1, node_2), (node_2, node_1)] {
+                tree.add_child(root_id, Node::new(child_id));
+                tree.set_edge_weight((root_id, child_id), half_weight);
+                tree.set_node_taxa(child_id, self.get_node_taxa(child_id).cloned());
+                self.grow_rooted(&mut tree, child_id, Some(other_id));
+            }
+            Ok(tree)
+        }
+
+        /// Copies the subtree hanging from a node, directed away from prev_id, into a rooted tree that already contains the node
+        fn grow_rooted<Z: NodeWeight>(&self, tree: &mut SimpleRootedTree<T,W,Z>, start_id: NodeID, prev_id: Option<NodeID>) {
+            tree.set_node_taxa(start_id, self.get_node_taxa(start_id).cloned());
+            let mut queue = VecDeque::from([(start_id, prev_id)]);
+            while let Some((node_id, parent_id)) = queue.pop_front() {
+                for neighbour_id in self.get_node_neighbour_ids(node_id) {
+                    if Some(neighbour_id) == parent_id {
+                        continue;
+                    }
+                    tree.add_child(node_id, Node::new(neighbour_id));
+                    tree.set_edge_weight((node_id, neighbour_id), self.get_edge_weight(node_id, neighbour_id));
+                    tree.set_node_taxa(neighbour_id, self.get_node_taxa(neighbour_id).cloned());
+                    queue.push_back((neighbour_id, Some(node_id)));
+                }
+            }
+        }
+    }
+
+    impl<T,W> UnrootedTree for SimpleUnrootedTree<T,W>
+    where
+        T: NodeTaxa,
+        W: EdgeWeight,
+    {
+        type Node = UnrootedNode<T,W>;
+
+        fn new() -> Self {
+            SimpleUnrootedTree {
+                nodes: vec![],
+                taxa_node_id_map: [].into_iter().collect::<HashMap<_, _>>(),
+            }
+        }
+
+        fn with_capacity(capacity: usize) -> Self {
+            SimpleUnrootedTree {
+                nodes: vec![None; capacity],
+                taxa_node_id_map: [].into_iter().collect::<HashMap<_, _>>(),
+            }
+        }
+
+        fn from_nodes(nodes: Vec<Option<Self::Node>>) -> Self {
+            let taxa_node_id_map = nodes
+                .iter()
+                .flatten()
+                .filter_map(|node| Some((node.get_taxa()?.clone(), node.get_id())))
+                .collect::<HashMap<_, _>>();
+            SimpleUnrootedTree {
+                nodes,
+                taxa_node_id_map,
+            }
+        }
+
+        fn get_node<'a>(&'a self, node_id: UTreeNodeID<Self>) -> Option<&'a Self::Node> {
+            self.nodes.get(node_id)?.as_ref()
+        }
+
+        fn get_node_mut<'a>(&'a mut self, node_id: UTreeNodeID<Self>) -> Option<&'a mut Self::Node> {
+            self.nodes.get_mut(node_id)?.as_mut()
+        }
+
+        fn get_node_ids(&self) -> impl Iterator<Item = UTreeNodeID<Self>> {
+            (0..self.nodes.len()).filter(|x| self.nodes[*x].is_some())
+        }
+
+        fn set_node(&mut self, node: Self::Node) {
+            let node_id = node.get_id();
+            if let Some(taxa) = node.get_taxa() {
+                self.taxa_node_id_map.insert(taxa.clone(), node_id);
+            }
+            if self.nodes.len() <= node_id {
+                self.nodes.resize(node_id + 1, None);
+            }
+            self.nodes[node_id] = Some(node);
+        }
+
+        fn remove_node(&mut self, node_id: UTreeNodeID<Self>) -> Option<Self::Node> {
+            let node = self.nodes.get_mut(node_id)?.take()?;
+            for neighbour_id in node.get_neighbours() {
+                if let Some(neighbour) = self.get_node_mut(neighbour_id) {
+                    neighbour.remove_neighbour(&node_id);
+                }
+            }
+            if let Some(taxa) = node.get_taxa() {
+                self.taxa_node_id_map.remove(taxa);
+            }
+            Some(node)
+        }
+
+        /// Splits an edge in the tree with provided node. Edge weight is split equally on both sides of the node.
+        fn split_edge(&mut self, edge: (UTreeNodeID<Self>, UTreeNodeID<Self>), node: Self::Node) {
+            let half_weight = self
+                .get_edge_weight(edge.0, edge.1)
+                .map(|w| w / (W::one() + W::one()));
+            let n_id = node.get_id();
+            self.set_node(node);
+            self.remove_edge(edge.0, edge.1);
+            self.set_edge(edge.0, n_id);
+            self.set_edge(n_id, edge.1);
+            self.set_edge_weight((edge.0, n_id), half_weight);
+            self.set_edge_weight((n_id, edge.1), half_weight);
+        }
+
+        /// Removes a node of degree 2, connecting its two neighbours with an edge whose weight is the sum of the two removed edges.
+        fn supress_node(&mut self, node_id: UTreeNodeID<Self>) -> Option<()> {
+            let neighbour_ids = self.get_node_neighbour_ids(node_id).collect_vec();
+            if neighbour_ids.len() != 2 {
+                return None;
+            }
+            let weight = match (
+                self.get_edge_weight(node_id, neighbour_ids[0]),
+                self.get_edge_weight(node_id, neighbour_ids[1]),
+            ) {
+                (Some(w1), Some(w2)) => Some(w1 + w2),
+                (w1, w2) => w1.or(w2),
+            };
+            self.remove_node(node_id);
+            self.set_edge(neighbour_ids[0], neighbour_ids[1]);
+            self.set_edge_weight((neighbour_ids[0], neighbour_ids[1]), weight);
+            Some(())
+        }
+    }
+
+    impl<T,W> UnrootedMetaTree for SimpleUnrootedTree<T,W>
+    where
+        T: NodeTaxa,
+        W: EdgeWeight,
+    {
+        fn get_taxa_node(&self, taxa: &UTreeNodeMeta<Self>) -> Option<&Self::Node> {
+            self.get_node(*self.taxa_node_id_map.get(taxa)?)
+        }
+
+        fn num_taxa(&self) -> usize {
+            self.taxa_node_id_map.len()
+        }
+
+        fn set_node_taxa(&mut self, node_id: UTreeNodeID<Self>, taxa: Option<UTreeNodeMeta<Self>>) {
+            // the old taxa may already have been reassigned to another node
+            if let Some(old_taxa) = self.get_node_taxa(node_id).cloned() {
+                if self.taxa_node_id_map.get(&old_taxa) == Some(&node_id) {
+                    self.taxa_node_id_map.remove(&old_taxa);
+                }
+            }
+            self.get_node_mut(node_id).unwrap().set_taxa(taxa.clone());
+            if let Some(t) = taxa {
+                self.taxa_node_id_map.insert(t, node_id);
+            }
+        }
+
+        fn get_taxa_space<'a>(&'a self) -> impl ExactSizeIterator<Item = &'a UTreeNodeMeta<Self>> {
+            self.taxa_node_id_map.keys()
+        }
+    }
+
+    impl<T,W> UnrootedWeightedTree for SimpleUnrootedTree<T,W>
+    where
+        T: NodeTaxa,
+        W: EdgeWeight,
+    {}
+
+    impl<T,W> TbrDistance for SimpleUnrootedTree<T,W>
+    where
+        T: NodeTaxa,
+        W: EdgeWeight,
+    {}
+
+    /// Unroots a rooted tree. A root of degree 2 without a taxa annotation is suppressed and the weights of its two edges are merged. NodeIDs are preserved.
+    impl<T,W,Z> From<SimpleRootedTree<T,W,Z>> for SimpleUnrootedTree<T,W>
+    where
+        T: NodeTaxa,
+        W: EdgeWeight,
+        Z: NodeWeight,
+    {
+        fn from(tree: SimpleRootedTree<T,W,Z>) -> Self {
+            let mut unrooted_tree = SimpleUnrootedTree::with_capacity(tree.nodes.len());
+            for node in tree.get_nodes() {
+                let mut unrooted_node = UnrootedNode::new(node.get_id());
+                unrooted_node.set_taxa(node.get_taxa().cloned());
+                unrooted_tree.set_node(unrooted_node);
+            }
+            for node in tree.get_nodes() {
+                if let Some(parent_id) = node.get_parent() {
+                    unrooted_tree.set_edge(parent_id, node.get_id());
+                    unrooted_tree.set_edge_weight((parent_id, node.get_id()), node.get_weight());
+                }
+            }
+            let root_id = tree.get_root_id();
+            if tree.get_root().num_children() == 2 && tree.get_node_taxa(root_id).is_none() {
+                unrooted_tree.supress_node(root_id);
+            }
+            unrooted_tree
+        }
+    }
+
+    impl<T,W,Z> SimpleRootedTree<T,W,Z>
+    where
+        T: NodeTaxa,
+        W: EdgeWeight,
+        Z: NodeWeight,
+    {
+        /// Returns an unrooted copy of the tree. A root of degree 2 without a taxa annotation is suppressed.
+        pub fn unroot(&self) -> SimpleUnrootedTree<T,W> {
+            self.clone().into()
+        }
+    }
 }
@@ -0,0 +1,934 @@
+use itertools::Itertools;
+
+#[cfg(feature = "non_crypto_hash")]
+use fxhash::FxHashMap as HashMap;
+#[cfg(not(feature = "non_crypto_hash"))]
+use std::collections::HashMap;
+
+use crate::prelude::*;
+use crate::tree::distances::{leaf_taxa_index, min_cost_assignment, CompactTree, LcaIndex, RestrictedTree};
+
+/// A trait describing computation of maximum agreement subtrees of two rooted trees.
+///
+/// The maximum agreement subtree (MAST) is the largest set of taxa on which both trees induce the same rooted topology. For binary trees it is computed in O(n log^3 n) time by matching the subtrees hanging off heavy paths of both trees in order, in the manner of the agreement matchings of Cole et al. Otherwise it is computed using the dynamic program of Steel and Warnow over all pairs of nodes of both trees, matching children of multifurcating nodes by a maximum weight bipartite matching, in O(n^2 d) time for trees with maximum degree d and O(n^2) space.
+pub trait MaximumAgreementSubtree: RootedMetaTree + Clusters + ContractTree
+where
+    <Self as RootedTree>::Node: RootedMetaNode,
+{
+    /// Returns the taxa of a maximum agreement subtree of self and tree.
+    fn mast_taxa(&self, tree: &Self) -> Vec<TreeNodeMeta<Self>> {
+        let taxa_map = leaf_taxa_index(&[self, tree]);
+        let taxa = taxa_map
+            .iter()
+            .sorted_by_key(|(_, idx)| **idx)
+            .map(|(taxa, _)| (*taxa).clone())
+            .collect_vec();
+        let t1 = CompactTree::new(self, &taxa_map);
+        let t2 = CompactTree::new(tree, &taxa_map);
+        let mast_taxa = match t1.is_binary() && t2.is_binary() {
+            true => BinaryMast::new(&t1, &t2).taxa(),
+            false => MastTable::new(&t1, &t2).taxa(),
+        };
+        mast_taxa.into_iter().map(|t| taxa[t].clone()).collect_vec()
+    }
+
+    /// Returns the number of taxa in a maximum agreement subtree of self and tree.
+    fn mast_size(&self, tree: &Self) -> usize {
+        let taxa_map = leaf_taxa_index(&[self, tree]);
+        let t1 = CompactTree::new(self, &taxa_map);
+        let t2 = CompactTree::new(tree, &taxa_map);
+        match t1.is_binary() && t2.is_binary() {
+            true => BinaryMast::new(&t1, &t2).size(),
+            false => MastTable::new(&t1, &t2).size(),
+        }
+    }
+
+    /// Returns a maximum agreement subtree of self and tree, contracted from self. Returns Err if the trees do not share any taxa.
+    fn mast(&self, tree: &Self) -> Result<Self, DistanceError> {
+        let leaf_ids = self
+            .mast_taxa(tree)
+            .iter()
+            .filter_map(|taxa| self.get_taxa_node_id(taxa))
+            .collect_vec();
+        if leaf_ids.is_empty() {
+            return Err(DistanceError::NoSharedTaxa);
+        }
+        self.contract_tree(leaf_ids.as_slice())
+            .map_err(|_| DistanceError::NoSharedTaxa)
+    }
+}
+
+/// Sizes of maximum agreement subtrees of every pair of subtrees of two trees
+struct MastTable<'a> {
+    t1: &'a CompactTree,
+    t2: &'a CompactTree,
+    table: Vec<u32>,
+}
+
+impl<'a> MastTable<'a> {
+    fn new(t1: &'a CompactTree, t2: &'a CompactTree) -> Self {
+        let mut mast_table = MastTable {
+            t1,
+            t2,
+            table: vec![0; t1.len() * t2.len()],
+        };
+        // nodes of both trees are stored in post-order
+        for u in 0..t1.len() {
+            for v in 0..t2.len() {
+                let size = match (t1.children[u].is_empty(), t2.children[v].is_empty()) {
+                    (true, true) => {
+                        (t1.leaf_taxa[u].is_some() && t1.leaf_taxa[u] == t2.leaf_taxa[v]) as u32
+                    }
+                    (true, false) => mast_table.max_below_second(u, v),
+                    (false, true) => mast_table.max_below_first(u, v),
+                    (false, false) => mast_table
+                        .max_below_first(u, v)
+                        .max(mast_table.max_below_second(u, v))
+                        .max(mast_table.children_matching(u, v).0),
+                };
+                mast_table.table[u * t2.len() + v] = size;
+            }
+        }
+        mast_table
+    }
+
+    fn get(&self, u: usize, v: usize) -> u32 {
+        self.table[u * self.t2.len() + v]
+    }
+
+    /// Returns the number of taxa in a maximum agreement subtree of both trees
+    fn size(&self) -> usize {
+        self.get(self.t1.len() - 1, self.t2.len() - 1) as usize
+    }
+
+    /// Returns the taxa indices of a maximum agreement subtree of both trees
+    fn taxa(&self) -> Vec<usize> {
+        let mut mast_taxa = vec![];
+        let mut stack = vec![(self.t1.len() - 1, self.t2.len() - 1)];
+        while let Some((u, v)) = stack.pop() {
+            let size = self.get(u, v);
+            if size == 0 {
+                continue;
+            }
+            if self.t1.children[u].is_empty() && self.t2.children[v].is_empty() {
+                mast_taxa.extend(self.t1.leaf_taxa[u]);
+                continue;
+            }
+            if let Some(c) = self.t1.children[u].iter().find(|c| self.get(**c, v) == size) {
+                stack.push((*c, v));
+                continue;
+            }
+            if let Some(c) = self.t2.children[v].iter().find(|c| self.get(u, **c) == size) {
+                stack.push((u, *c));
+                continue;
+            }
+            let (_, pairs) = self.children_matching(u, v);
+            stack.extend(pairs);
+        }
+        mast_taxa
+    }
+
+    fn max_below_first(&self, u: usize, v: usize) -> u32 {
+        self.t1.children[u]
+            .iter()
+            .map(|c| self.get(*c, v))
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn max_below_second(&self, u: usize, v: usize) -> u32 {
+        self.t2.children[v]
+            .iter()
+            .map(|c| self.get(u, *c))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Returns the maximum total size over matchings of children of two nodes, along with the matched pairs of children
+    fn children_matching(&self, u: usize, v: usize) -> (u32, Vec<(usize, usize)>) {
+        let children_1 = &self.t1.children[u];
+        let children_2 = &self.t2.children[v];
+        if children_1.len() == 2 && children_2.len() == 2 {
+            let straight = self.get(children_1[0], children_2[0]) + self.get(children_1[1], children_2[1]);
+            let crossed = self.get(children_1[0], children_2[1]) + self.get(children_1[1], children_2[0]);
+            return match straight >= crossed {
+                true => (
+                    straight,
+                    vec![(children_1[0], children_2[0]), (children_1[1], children_2[1])],
+                ),
+                false => (
+                    crossed,
+                    vec![(children_1[0], children_2[1]), (children_1[1], children_2[0])],
+                ),
+            };
+        }
+        let size = children_1.len().max(children_2.len());
+        let cost = (0..size)
+            .map(|i| {
+                (0..size)
+                    .map(|j| match (children_1.get(i), children_2.get(j)) {
+                        (Some(c_1), Some(c_2)) => -(self.get(*c_1, *c_2) as f64),
+                        _ => 0.0,
+                    })
+                    .collect_vec()
+            })
+            .collect_vec();
+        let (_, assignment) = min_cost_assignment(&cost);
+        let pairs = assignment
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, j)| Some((*children_1.get(i)?, *children_2.get(j)?)))
+            .collect_vec();
+        let total = pairs.iter().map(|(c_1, c_2)| self.get(*c_1, *c_2)).sum();
+        (total, pairs)
+    }
+}
+
+/// Maximum agreement subtrees of two binary trees, found by comparing heavy paths of both trees.
+///
+/// Each heavy path of the first tree is compared against the second tree restricted to the taxa below its top. The subtrees hanging off the path are coloured by their position along it, and their maximum agreement subtrees with the restricted tree are already known. An agreement subtree of a node on the path with a node of the restricted tree then either matches the pendant subtrees of both paths in order, or ends by crossing a pendant of one path with the rest of the other. These are found for every node of a heavy path of the restricted tree by a single sweep from its bottom, over range maxima indexed by colour. Every taxa lies below O(log n) tops of heavy paths in both trees, so the sweeps handle O(n log^2 n) colours in total, each in O(log n) time over the range maxima, for O(n log^3 n) time overall.
+struct BinaryMast<'a> {
+    t1: &'a CompactTree,
+    lca: LcaIndex,
+    /// Leaf of the second tree with each taxa
+    leaf_2: Vec<Option<usize>>,
+    sets: AgreementSets,
+    /// For each top of a heavy path of the first tree, its maximum agreement subtree with each node of the second tree restricted to its taxa
+    tables: Vec<HashMap<usize, Agreement>>,
+}
+
+impl<'a> BinaryMast<'a> {
+    fn new(t1: &'a CompactTree, t2: &'a CompactTree) -> Self {
+        let num_taxa = t1
+            .leaf_taxa
+            .iter()
+            .chain(t2.leaf_taxa.iter())
+            .flatten()
+            .max()
+            .map_or(0, |t| t + 1);
+        let mut leaf_2 = vec![None; num_taxa];
+        for (v, taxa) in t2.leaf_taxa.iter().enumerate() {
+            if let Some(t) = taxa {
+                leaf_2[*t] = Some(v);
+            }
+        }
+        let mut mast = BinaryMast {
+            t1,
+            lca: LcaIndex::new(t2),
+            leaf_2,
+            sets: AgreementSets::default(),
+            tables: vec![HashMap::default(); t1.len()],
+        };
+        // nodes are stored in post-order, so the pendants of a path are done before its top
+        let mut shared = vec![0; t1.len()];
+        for (u, taxa) in t1.leaf_taxa.iter().enumerate() {
+            shared[u] = match taxa {
+                Some(t) => mast.leaf_2[*t].is_some() as usize,
+                None => t1.children[u].iter().map(|c| shared[*c]).sum(),
+            };
+        }
+        let heavy = t1
+            .children
+            .iter()
+            .map(|c| c.iter().copied().max_by_key(|c| shared[*c]))
+            .collect_vec();
+        for (u, parent) in t1.parent.iter().enumerate() {
+            let is_top = parent.is_none_or(|p| heavy[p] != Some(u));
+            if is_top && shared[u] > 0 {
+                mast.tables[u] = mast.path_tables(u, &heavy);
+            }
+        }
+        mast
+    }
+
+    fn best(&self) -> Agreement {
+        self.tables[self.t1.len() - 1]
+            .values()
+            .fold(Agreement::EMPTY, |best, a| best.max(*a))
+    }
+
+    /// Returns the number of taxa in a maximum agreement subtree of both trees
+    fn size(&self) -> usize {
+        self.best().size as usize
+    }
+
+    /// Returns the taxa indices of a maximum agreement subtree of both trees
+    fn taxa(&self) -> Vec<usize> {
+        self.sets.taxa(self.best())
+    }
+
+    /// Returns the maximum agreement subtrees of the heavy path starting at top with each node of the second tree restricted to its taxa
+    fn path_tables(&mut self, top: usize, heavy: &[Option<usize>]) -> HashMap<usize, Agreement> {
+        let t1 = self.t1;
+        let mut path = vec![top];
+        while let Some(c) = heavy[*path.last().unwrap()] {
+            path.push(c);
+        }
+        // the colour of a pendant is its position along the path, with the bottom leaf last
+        let mut leaves = vec![];
+        let mut tables = Vec::with_capacity(path.len());
+        for (colour, u) in path.iter().enumerate() {
+            let pendant = t1.children[*u]
+                .iter()
+                .copied()
+                .find(|c| Some(*c) != heavy[*u])
+                .unwrap_or(*u);
+            let mut stack = vec![pendant];
+            while let Some(w) = stack.pop() {
+                stack.extend(t1.children[w].iter());
+                if let Some(v) = t1.leaf_taxa[w].and_then(|t| self.leaf_2[t]) {
+                    leaves.push((v, colour));
+                }
+            }
+            tables.push(match pendant == *u {
+                true => t1.leaf_taxa[*u]
+                    .and_then(|t| Some((self.leaf_2[t]?, self.sets.taxa_set(t))))
+                    .into_iter()
+                    .collect(),
+                false => std::mem::take(&mut self.tables[pendant]),
+            });
+        }
+        let restricted = RestrictedTree::new(&self.lca, leaves);
+        let mut found = vec![Agreement::EMPTY; restricted.len()];
+        let mut steps = vec![vec![]; restricted.len()];
+        // nodes of the restricted tree are stored in pre-order, so the pendants of a path are done before its top
+        for x in (0..restricted.len()).rev() {
+            if restricted.parent[x].is_none_or(|p| restricted.heavy[p] != Some(x)) {
+                self.sweep(&restricted, x, &tables, &mut found, &mut steps);
+            }
+        }
+        restricted.nodes.into_iter().zip(found).collect()
+    }
+
+    /// Finds the maximum agreement subtrees of the path of the first tree with each node along the heavy path of the restricted tree starting at top.
+    ///
+    /// For the top of a pendant, also records the agreement subtrees with each suffix of the path of the first tree as steps, holding the maximum agreement subtree using only colours at least as large as each colour.
+    fn sweep(
+        &mut self,
+        restricted: &RestrictedTree,
+        top: usize,
+        tables: &[HashMap<usize, Agreement>],
+        found: &mut [Agreement],
+        steps: &mut [Vec<(usize, Agreement)>],
+    ) {
+        let mut path = vec![top];
+        while let Some(c) = restricted.heavy[*path.last().unwrap()] {
+            path.push(c);
+        }
+        let bottom = *path.last().unwrap();
+        // colours of the pendant of each node along the path, with the lowest common ancestor of their leaves
+        let pendant_colours = path[..path.len() - 1]
+            .iter()
+            .map(|x| {
+                let mut lcas: HashMap<usize, usize> = HashMap::default();
+                let mut stack = vec![restricted.light_child(*x)];
+                while let Some(y) = stack.pop() {
+                    stack.extend(restricted.children[y].iter());
+                    if let Some(c) = restricted.colour[y] {
+                        let v = restricted.nodes[y];
+                        lcas.entry(c)
+                            .and_modify(|w| *w = self.lca.lca(*w, v))
+                            .or_insert(v);
+                    }
+                }
+                lcas.into_iter().sorted().collect_vec()
+            })
+            .collect_vec();
+        let colours = pendant_colours
+            .iter()
+            .flatten()
+            .map(|(c, _)| *c)
+            .chain(restricted.colour[bottom])
+            .sorted()
+            .dedup()
+            .collect_vec();
+        let position = |colour: usize| colours.partition_point(|c| *c < colour);
+        let mut matched = MaxTree::new(colours.len());
+        let mut crossed = CrossTree::new(colours.len());
+        let mut present = vec![false; colours.len()];
+        for (t, x) in path.iter().enumerate().rev() {
+            let events = match *x == bottom {
+                true => vec![(restricted.colour[bottom].unwrap(), restricted.nodes[bottom])],
+                false => pendant_colours[t].clone(),
+            };
+            if *x != bottom {
+                // match a colour with the pendant, then continue below on both paths
+                let pairs = events
+                    .iter()
+                    .map(|(c, w)| {
+                        let below = best_from(&matched, &mut crossed, position(c + 1));
+                        let below = self.sets.store(below);
+                        (position(*c), tables[*c][w].join(below))
+                    })
+                    .collect_vec();
+                for (p, a) in pairs {
+                    matched.raise(p, a);
+                }
+                let pendant_steps = &steps[restricted.light_child(*x)];
+                for (c, a) in pendant_steps {
+                    matched.raise(position(*c), *a);
+                }
+                // cross a colour with the path below and the rest of the path of the first tree with the pendant
+                let mut suffix = Agreement::EMPTY;
+                for j in (0..pendant_steps.len()).rev() {
+                    suffix = suffix.max(pendant_steps[j].1);
+                    let start = match j {
+                        0 => 0,
+                        _ => position(pendant_steps[j - 1].0),
+                    };
+                    crossed.raise(start, position(pendant_steps[j].0), suffix);
+                }
+            }
+            for (c, w) in events {
+                let p = position(c);
+                let v = match present[p] {
+                    true => restricted.nodes[*x],
+                    false => w,
+                };
+                crossed.set_shared(p, tables[c][&v]);
+                present[p] = true;
+            }
+            found[*x] = self.sets.store(best_from(&matched, &mut crossed, 0));
+        }
+        if restricted.parent[top].is_some() {
+            let mut top_steps = vec![];
+            for c in colours.iter().rev() {
+                let a = best_from(&matched, &mut crossed, position(*c));
+                if a.size > top_steps.last().map_or(0, |(_, b): &(usize, Agreement)| b.size) {
+                    top_steps.push((*c, self.sets.store(a)));
+                }
+            }
+            top_steps.reverse();
+            steps[top] = top_steps;
+        }
+    }
+}
+
+/// Returns the largest agreement subtree over positions from pos, ending in either a matched or a crossed colour
+fn best_from(matched: &MaxTree, crossed: &mut CrossTree, pos: usize) -> Agreement {
+    let (shared, crossing) = crossed.max_from(pos);
+    matched.max_from(pos).max(shared).max(crossing)
+}
+
+const NO_SET: u32 = u32::MAX;
+
+/// An agreement subtree, as its number of taxa and up to two stored taxa sets whose union is its taxa
+#[derive(Debug, Clone, Copy)]
+struct Agreement {
+    size: u32,
+    sets: [u32; 2],
+}
+
+impl Agreement {
+    const EMPTY: Agreement = Agreement {
+        size: 0,
+        sets: [NO_SET, NO_SET],
+    };
+
+    fn max(self, other: Agreement) -> Agreement {
+        match other.size > self.size {
+            true => other,
+            false => self,
+        }
+    }
+
+    /// Returns the union of two agreement subtrees, each given by a single stored set
+    fn join(self, other: Agreement) -> Agreement {
+        Agreement {
+            size: self.size + other.size,
+            sets: [self.sets[0], other.sets[0]],
+        }
+    }
+}
+
+enum SetPart {
+    Taxa(usize),
+    Union(u32, u32),
+}
+
+/// Taxa sets of agreement subtrees, each stored as a single taxa or the union of two stored sets
+#[derive(Default)]
+struct AgreementSets {
+    parts: Vec<SetPart>,
+}
+
+impl AgreementSets {
+    fn taxa_set(&mut self, taxa: usize) -> Agreement {
+        self.parts.push(SetPart::Taxa(taxa));
+        Agreement {
+            size: 1,
+            sets: [self.parts.len() as u32 - 1, NO_SET],
+        }
+    }
+
+    /// Returns the agreement subtree with its taxa given by a single stored set
+    fn store(&mut self, agreement: Agreement) -> Agreement {
+        let set = match agreement.sets {
+            [set, NO_SET] | [NO_SET, set] => set,
+            [first, second] => {
+                self.parts.push(SetPart::Union(first, second));
+                self.parts.len() as u32 - 1
+            }
+        };
+        Agreement {
+            size: agreement.size,
+            sets: [set, NO_SET],
+        }
+    }
+
+    fn taxa(&self, agreement: Agreement) -> Vec<usize> {
+        let mut taxa = vec![];
+        let mut stack = agreement.sets.iter().copied().filter(|s| *s != NO_SET).collect_vec();
+        while let Some(set) = stack.pop() {
+            match self.parts[set as usize] {
+                SetPart::Taxa(t) => taxa.push(t),
+                SetPart::Union(first, second) => stack.extend([first, second]),
+            }
+        }
+        taxa
+    }
+}
+
+/// Maxima of agreement subtrees over suffixes of positions, which only increase
+struct MaxTree {
+    best: Vec<Agreement>,
+}
+
+impl MaxTree {
+    fn new(len: usize) -> Self {
+        MaxTree {
+            best: vec![Agreement::EMPTY; 2 * len],
+        }
+    }
+
+    fn raise(&mut self, pos: usize, agreement: Agreement) {
+        let mut idx = pos + self.best.len() / 2;
+        self.best[idx] = self.best[idx].max(agreement);
+        while idx > 1 {
+            idx /= 2;
+            self.best[idx] = self.best[2 * idx].max(self.best[2 * idx + 1]);
+        }
+    }
+
+    fn max_from(&self, pos: usize) -> Agreement {
+        let (mut l, mut r) = (pos + self.best.len() / 2, self.best.len());
+        let mut best = Agreement::EMPTY;
+        while l < r {
+            if l % 2 == 1 {
+                best = best.max(self.best[l]);
+                l += 1;
+            }
+            if r % 2 == 1 {
+                r -= 1;
+                best = best.max(self.best[r]);
+            }
+            l /= 2;
+            r /= 2;
+        }
+        best
+    }
+}
+
+/// Agreement subtrees crossing each colour with the path of the restricted tree below.
+///
+/// Each position holds the agreement subtree of its colour with the current node of the path (shared), and the best union of an earlier shared subtree with a pendant agreement subtree raised over a range of positions while it was current. Raises are applied lazily to whole ranges, and a pending raise is settled when the shared subtree of a position grows.
+struct CrossTree {
+    len: usize,
+    shared: Vec<Agreement>,
+    best: Vec<Agreement>,
+    pending: Vec<Option<Agreement>>,
+}
+
+impl CrossTree {
+    fn new(len: usize) -> Self {
+        CrossTree {
+            len,
+            shared: vec![Agreement::EMPTY; 4 * len],
+            best: vec![Agreement::EMPTY; 4 * len],
+            pending: vec![None; 4 * len],
+        }
+    }
+
+    fn apply(&mut self, idx: usize, agreement: Agreement) {
+        self.best[idx] = self.best[idx].max(self.shared[idx].join(agreement));
+        self.pending[idx] = Some(self.pending[idx].map_or(agreement, |a| a.max(agreement)));
+    }
+
+    fn push(&mut self, idx: usize) {
+        if let Some(a) = self.pending[idx].take() {
+            self.apply(2 * idx, a);
+            self.apply(2 * idx + 1, a);
+        }
+    }
+
+    fn pull(&mut self, idx: usize) {
+        self.shared[idx] = self.shared[2 * idx].max(self.shared[2 * idx + 1]);
+        self.best[idx] = self.best[2 * idx].max(self.best[2 * idx + 1]);
+    }
+
+    /// Raises the pendant agreement subtree over positions start..end
+    fn raise(&mut self, start: usize, end: usize, agreement: Agreement) {
+        if start < end && agreement.size > 0 {
+            self.raise_below(1, 0, self.len, start, end, agreement);
+        }
+    }
+
+    fn raise_below(&mut self, idx: usize, l: usize, r: usize, start: usize, end: usize, agreement: Agreement) {
+        if end <= l || r <= start {
+            return;
+        }
+        if start <= l && r <= end {
+            self.apply(idx, agreement);
+            return;
+        }
+        self.push(idx);
+        let mid = (l + r) / 2;
+        self.raise_below(2 * idx, l, mid, start, end, agreement);
+        self.raise_below(2 * idx + 1, mid, r, start, end, agreement);
+        self.pull(idx);
+    }
+
+    fn set_shared(&mut self, pos: usize, agreement: Agreement) {
+        let (mut idx, mut l, mut r) = (1, 0, self.len);
+        let mut path = vec![];
+        while r - l > 1 {
+            self.push(idx);
+            path.push(idx);
+            let mid = (l + r) / 2;
+            match pos < mid {
+                true => (idx, r) = (2 * idx, mid),
+                false => (idx, l) = (2 * idx + 1, mid),
+            }
+        }
+        // the best union with the previous shared subtree is already settled
+        self.pending[idx] = None;
+        self.shared[idx] = agreement;
+        for idx in path.into_iter().rev() {
+            self.pull(idx);
+        }
+    }
+
+    /// Returns the largest shared and crossing agreement subtrees over positions from pos
+    fn max_from(&mut self, pos: usize) -> (Agreement, Agreement) {
+        self.max_below(1, 0, self.len, pos)
+    }
+
+    fn max_below(&mut self, idx: usize, l: usize, r: usize, pos: usize) -> (Agreement, Agreement) {
+        if r <= pos {
+            return (Agreement::EMPTY, Agreement::EMPTY);
+        }
+        if pos <= l {
+            return (self.shared[idx], self.best[idx]);
+        }
+        self.push(idx);
+        let mid = (l + r) / 2;
+        let (shared_1, best_1) = self.max_below(2 * idx, l, mid, pos);
+        let (shared_2, best_2) = self.max_below(2 * idx + 1, mid, r, pos);
+        (shared_1.max(shared_2), best_1.max(best_2))
+    }
+}
+
+/// An agreement forest of two rooted trees, as the taxa sets of its components
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgreementForest<M> {
+    /// Taxa of the component containing the root of both trees
+    pub root_component: Vec<M>,
+    /// Taxa of the remaining components, each corresponding to a subtree moved by an SPR
+    pub components: Vec<Vec<M>>,
+}
+
+impl<M> AgreementForest<M> {
+    /// Returns the number of SPR moves induced by the forest
+    pub fn spr_distance(&self) -> usize {
+        self.components.len()
+    }
+}
+
+/// A trait describing computation of rooted SPR distances of binary trees via maximum agreement forests.
+///
+/// The rooted SPR distance is the number of components of a maximum agreement forest less one, where a leaf is attached above the root of both trees. The exact forest is found by the three-way branching algorithm of Whidden, Beiko and Zeh in O(3^k n^2) time for distance k, while the approximate forest is found in O(n^2) time with at most three times as many components.
+pub trait SprDistance: RootedMetaTree + Clusters
+where
+    <Self as RootedTree>::Node: RootedMetaNode,
+{
+    /// Returns a maximum agreement forest of self and tree
+    fn spr_forest(&self, tree: &Self) -> Result<AgreementForest<TreeNodeMeta<Self>>, DistanceError> {
+        let (state, taxa) = MafState::new(self, tree)?;
+        let upper_bound = state.clone().approximate();
+        let forest = (0..upper_bound.cuts)
+            .find_map(|max_cuts| state.clone().search(max_cuts))
+            .unwrap_or(upper_bound);
+        Ok(forest.agreement_forest(&taxa))
+    }
+
+    /// Returns an agreement forest of self and tree with at most three times as many components as a maximum agreement forest
+    fn spr_forest_approx(&self, tree: &Self) -> Result<AgreementForest<TreeNodeMeta<Self>>, DistanceError> {
+        let (state, taxa) = MafState::new(self, tree)?;
+        Ok(state.approximate().agreement_forest(&taxa))
+    }
+
+    /// Returns the rooted SPR distance between self and tree
+    fn spr_distance(&self, tree: &Self) -> Result<usize, DistanceError> {
+        Ok(self.spr_forest(tree)?.spr_distance())
+    }
+}
+
+/// A forest of binary trees with leaves labelled by merged taxa sets
+#[derive(Debug, Clone)]
+struct LabelledForest {
+    parent: Vec<Option<usize>>,
+    children: Vec<Vec<usize>>,
+    /// Label of each leaf
+    label: Vec<Option<usize>>,
+    /// Node of each label
+    leaf_of: Vec<usize>,
+}
+
+impl LabelledForest {
+    /// Builds a forest with a single tree, where a leaf labelled `num_taxa` is attached above the root
+    fn new(tree: &CompactTree, num_taxa: usize) -> Result<Self, DistanceError> {
+        let num_nodes = tree.len();
+        let mut parent = tree.parent.clone();
+        let mut children = tree.children.clone();
+        let mut label = tree.leaf_taxa.clone();
+        if children.iter().any(|c| !c.is_empty() && c.len() != 2)
+            || label.iter().zip(children.iter()).any(|(l, c)| l.is_none() && c.is_empty())
+        {
+            return Err(DistanceError::NotBinary);
+        }
+        // root leaf and new root
+        parent[num_nodes - 1] = Some(num_nodes + 1);
+        parent.extend([Some(num_nodes + 1), None]);
+        children.extend([vec![], vec![num_nodes - 1, num_nodes]]);
+        label.extend([Some(num_taxa), None]);
+        let mut leaf_of = vec![0; num_taxa + 1];
+        for (node, l) in label.iter().enumerate() {
+            if let Some(l) = l {
+                leaf_of[*l] = node;
+            }
+        }
+        Ok(LabelledForest {
+            parent,
+            children,
+            label,
+            leaf_of,
+        })
+    }
+
+    fn sibling(&self, node: usize) -> Option<usize> {
+        let parent = self.parent[node]?;
+        self.children[parent].iter().copied().find(|c| *c != node)
+    }
+
+    fn component_root(&self, mut node: usize) -> usize {
+        while let Some(parent) = self.parent[node] {
+            node = parent;
+        }
+        node
+    }
+
+    fn is_singleton(&self, node: usize) -> bool {
+        self.parent[node].is_none() && self.children[node].is_empty()
+    }
+
+    /// Returns a pair of leaves with a common parent in a component with more than one leaf
+    fn cherry(&self) -> Option<(usize, usize)> {
+        (0..self.parent.len()).find_map(|node| match self.children[node].as_slice() {
+            [x, y] if self.children[*x].is_empty() && self.children[*y].is_empty() => Some((*x, *y)),
+            _ => None,
+        })
+    }
+
+    /// Cuts the edge above a node, suppressing its former parent
+    fn cut(&mut self, node: usize) {
+        let Some(parent) = self.parent[node] else {
+            return;
+        };
+        let sibling = self.sibling(node).unwrap();
+        self.parent[node] = None;
+        self.children[parent].clear();
+        let grandparent = self.parent[parent].take();
+        self.parent[sibling] = grandparent;
+        if let Some(grandparent) = grandparent {
+            for c in self.children[grandparent].iter_mut() {
+                if *c == parent {
+                    *c = sibling;
+                }
+            }
+        }
+    }
+
+    /// Replaces a cherry by its parent, which becomes a leaf with the label of the first leaf
+    fn contract(&mut self, x: usize, y: usize) {
+        let parent = self.parent[x].unwrap();
+        let label = self.label[x].unwrap();
+        self.children[parent].clear();
+        for leaf in [x, y] {
+            self.parent[leaf] = None;
+            self.label[leaf] = None;
+        }
+        self.label[parent] = Some(label);
+        self.leaf_of[label] = parent;
+    }
+
+    /// Returns roots of subtrees pendant to the path between two nodes in the same component
+    fn pendants(&self, x: usize, y: usize) -> Vec<usize> {
+        let path_to_root = |mut node: usize| {
+            let mut path = vec![node];
+            while let Some(parent) = self.parent[node] {
+                path.push(parent);
+                node = parent;
+            }
+            path
+        };
+        let path_x = path_to_root(x);
+        let path_y = path_to_root(y);
+        let lca = *path_x.iter().find(|node| path_y.contains(node)).unwrap();
+        let below_lca = |path: &Vec<usize>| path.iter().copied().take_while(|node| *node != lca).collect_vec();
+        let (below_x, below_y) = (below_lca(&path_x), below_lca(&path_y));
+        below_x
+            .iter()
+            .chain(below_y.iter())
+            .filter_map(|node| self.sibling(*node))
+            .filter(|sibling| !below_x.contains(sibling) && !below_y.contains(sibling))
+            .collect_vec()
+    }
+}
+
+/// State of the search for a maximum agreement forest, where the first tree is reduced as cherries agreeing with the forest are contracted
+#[derive(Debug, Clone)]
+struct MafState {
+    t1: LabelledForest,
+    forest: LabelledForest,
+    /// Taxa of each label
+    members: Vec<Vec<usize>>,
+    /// Number of edges cut in the forest
+    cuts: usize,
+}
+
+/// Next step in the search for an agreement forest
+enum MafStep {
+    /// The first tree is fully contracted
+    Done,
+    /// Either of two leaves of the forest must be cut
+    Separate(usize, usize),
+    /// Either of two leaves of the forest or all pendant subtrees between them must be cut
+    Split(usize, usize, Vec<usize>),
+}
+
+impl MafState {
+    fn new<T>(t1: &T, t2: &T) -> Result<(Self, Vec<TreeNodeMeta<T>>), DistanceError>
+    where
+        T: RootedMetaTree + Clusters,
+        <T as RootedTree>::Node: RootedMetaNode,
+    {
+        let taxa_map = leaf_taxa_index(&[t1, t2]);
+        let taxa = taxa_map
+            .iter()
+            .sorted_by_key(|(_, idx)| **idx)
+            .map(|(taxa, _)| (*taxa).clone())
+            .collect_vec();
+        let compact_1 = CompactTree::new(t1, &taxa_map);
+        let compact_2 = CompactTree::new(t2, &taxa_map);
+        if compact_1.num_leaves != taxa.len() || compact_2.num_leaves != taxa.len() {
+            return Err(DistanceError::TaxaMismatch);
+        }
+        let state = MafState {
+            t1: LabelledForest::new(&compact_1, taxa.len())?,
+            forest: LabelledForest::new(&compact_2, taxa.len())?,
+            members: (0..=taxa.len()).map(|x| vec![x]).collect_vec(),
+            cuts: 0,
+        };
+        Ok((state, taxa))
+    }
+
+    /// Contracts cherries and removes leaves isolated in the forest until a cut is required
+    fn reduce(&mut self) -> MafStep {
+        loop {
+            let Some((a, c)) = self.t1.cherry() else {
+                return MafStep::Done;
+            };
+            let (label_a, label_c) = (self.t1.label[a].unwrap(), self.t1.label[c].unwrap());
+            let (f_a, f_c) = (self.forest.leaf_of[label_a], self.forest.leaf_of[label_c]);
+            if self.forest.parent[f_a].is_some() && self.forest.parent[f_a] == self.forest.parent[f_c] {
+                self.t1.contract(a, c);
+                self.forest.contract(f_a, f_c);
+                let merged = std::mem::take(&mut self.members[label_c]);
+                self.members[label_a].extend(merged);
+            } else if self.forest.is_singleton(f_a) {
+                self.t1.cut(a);
+            } else if self.forest.is_singleton(f_c) {
+                self.t1.cut(c);
+            } else if self.forest.component_root(f_a) != self.forest.component_root(f_c) {
+                return MafStep::Separate(f_a, f_c);
+            } else {
+                return MafStep::Split(f_a, f_c, self.forest.pendants(f_a, f_c));
+            }
+        }
+    }
+
+    fn cut(mut self, nodes: &[usize]) -> Self {
+        for node in nodes {
+            self.forest.cut(*node);
+            self.cuts += 1;
+        }
+        self
+    }
+
+    /// Returns a maximum agreement forest with at most max_cuts cut edges, if one exists
+    fn search(mut self, max_cuts: usize) -> Option<Self> {
+        let branches = match self.reduce() {
+            MafStep::Done => return Some(self),
+            MafStep::Separate(x, y) => vec![vec![x], vec![y]],
+            MafStep::Split(x, y, pendants) => vec![vec![x], vec![y], pendants],
+        };
+        branches
+            .into_iter()
+            .filter(|cut| self.cuts + cut.len() <= max_cuts)
+            .find_map(|cut| self.clone().cut(&cut).search(max_cuts))
+    }
+
+    /// Returns an agreement forest by cutting every candidate edge at each step
+    fn approximate(mut self) -> Self {
+        loop {
+            self = match self.reduce() {
+                MafStep::Done => return self,
+                MafStep::Separate(x, y) => self.cut(&[x, y]),
+                MafStep::Split(x, y, pendants) => self.cut(&[x, y, pendants[0]]),
+            };
+        }
+    }
+
+    fn agreement_forest<M: Clone>(&self, taxa: &[M]) -> AgreementForest<M> {
+        let root_label = taxa.len();
+        let mut root_component = vec![];
+        let mut components = vec![];
+        for root in (0..self.forest.parent.len()).filter(|node| {
+            self.forest.parent[*node].is_none()
+                && (!self.forest.children[*node].is_empty() || self.forest.label[*node].is_some())
+        }) {
+            let mut stack = vec![root];
+            let mut component = vec![];
+            while let Some(node) = stack.pop() {
+                match self.forest.label[node] {
+                    Some(label) if self.forest.children[node].is_empty() => component.extend(self.members[label].iter().copied()),
+                    _ => stack.extend(self.forest.children[node].iter().copied()),
+                }
+            }
+            match component.contains(&root_label) {
+                true => {
+                    root_component = component
+                        .into_iter()
+                        .filter(|x| *x != root_label)
+                        .map(|x| taxa[x].clone())
+                        .collect_vec()
+                }
+                false => components.push(component.into_iter().map(|x| taxa[x].clone()).collect_vec()),
+            }
+        }
+        AgreementForest {
+            root_component,
+            components,
+        }
+    }
+}
@@ -0,0 +1,894 @@
+use itertools::Itertools;
+use num::NumCast;
+
+#[cfg(feature = "non_crypto_hash")]
+use fxhash::FxHashMap as HashMap;
+#[cfg(not(feature = "non_crypto_hash"))]
+use std::collections::HashMap;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use crate::prelude::*;
+
+/// A symmetric matrix of pairwise distances between taxa with a zero diagonal, storing only the entries below the diagonal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PairwiseDistances<T, W>
+where
+    T: NodeTaxa,
+    W: EdgeWeight,
+{
+    taxa: Vec<T>,
+    distances: Vec<W>,
+}
+
+impl<T, W> PairwiseDistances<T, W>
+where
+    T: NodeTaxa,
+    W: EdgeWeight,
+{
+    /// Creates a matrix of zero distances between taxa
+    pub fn new(taxa: Vec<T>) -> Self {
+        let num_taxa = taxa.len();
+        PairwiseDistances {
+            taxa,
+            distances: vec![W::zero(); num_taxa * num_taxa.saturating_sub(1) / 2],
+        }
+    }
+
+    /// Creates a matrix from full rows of distances, where row i holds the distances from taxa i. Returns Err if the rows are not square or not symmetric.
+    pub fn from_rows(taxa: Vec<T>, rows: &[Vec<W>]) -> Result<Self, BuildError> {
+        if rows.len() != taxa.len() || rows.iter().any(|row| row.len() != taxa.len()) {
+            return Err(BuildError::NotSquare);
+        }
+        let mut matrix = PairwiseDistances::new(taxa);
+        for (row, col) in (0..rows.len()).tuple_combinations() {
+            if rows[row][col] != rows[col][row] {
+                return Err(BuildError::Asymmetric { row, col });
+            }
+            matrix.set(row, col, rows[row][col]);
+        }
+        Ok(matrix)
+    }
+
+    /// Creates a matrix of path lengths between the leaves of a tree, where missing edge weights are counted as one.
+    pub fn from_tree<R>(tree: &R) -> Self
+    where
+        R: RootedMetaTree + RootedWeightedTree,
+        <R as RootedTree>::Node: RootedMetaNode<Meta = T> + RootedWeightedNode<Weight = W>,
+    {
+        let leaf_ids = tree.get_leaf_ids().collect_vec();
+        let mut matrix = PairwiseDistances::new(
+            leaf_ids
+                .iter()
+                .map(|id| tree.get_node_taxa_cloned(*id).unwrap())
+                .collect_vec(),
+        );
+        let edge_length = |n_id: TreeNodeID<R>| tree.get_node(n_id).unwrap().get_weight().unwrap_or(W::one());
+        for (idx, leaf_id) in leaf_ids.iter().enumerate() {
+            // lengths of paths from the leaf to every node, treating the tree as undirected
+            let mut lengths: HashMap<TreeNodeID<R>, W> = vec![].into_iter().collect();
+            let mut stack = vec![(*leaf_id, None, W::zero())];
+            while let Some((n_id, prev_id, length)) = stack.pop() {
+                lengths.insert(n_id, length);
+                let neighbours = tree
+                    .get_node_children_ids(n_id)
+                    .map(|c_id| (c_id, edge_length(c_id)))
+                    .chain(tree.get_node_parent_id(n_id).map(|p_id| (p_id, edge_length(n_id))));
+                for (neighbour_id, edge) in neighbours.filter(|(id, _)| Some(*id) != prev_id) {
+                    stack.push((neighbour_id, Some(n_id), length + edge));
+                }
+            }
+            for (other, other_id) in leaf_ids.iter().enumerate().take(idx) {
+                matrix.set(idx, other, lengths[other_id]);
+            }
+        }
+        matrix
+    }
+
+    /// Returns the taxa of the matrix in order of their index
+    pub fn taxa(&self) -> &[T] {
+        &self.taxa
+    }
+
+    /// Returns number of taxa in the matrix
+    pub fn num_taxa(&self) -> usize {
+        self.taxa.len()
+    }
+
+    /// Returns the distance between taxa with indices row and col
+    pub fn get(&self, row: usize, col: usize) -> W {
+        match row == col {
+            true => W::zero(),
+            false => self.distances[lower_index(row, col)],
+        }
+    }
+
+    /// Sets the distance between taxa with indices row and col. The diagonal is always zero, so setting it has no effect.
+    pub fn set(&mut self, row: usize, col: usize, distance: W) {
+        if row != col {
+            self.distances[lower_index(row, col)] = distance;
+        }
+    }
+
+    /// Returns the entries below the diagonal as f64 in row-major order
+    pub(crate) fn lower_triangle(&self) -> Vec<f64> {
+        self.distances.iter().map(|d| d.to_f64().unwrap()).collect_vec()
+    }
+}
+
+/// Returns position of an entry of a symmetric matrix in a row-major lower triangle without the diagonal
+pub(crate) fn lower_index(row: usize, col: usize) -> usize {
+    let (row, col) = match row > col {
+        true => (row, col),
+        false => (col, row),
+    };
+    row * (row - 1) / 2 + col
+}
+
+/// Selects where a tree built from distances is rooted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RootPlacement {
+    /// Rooted at the node created by the final join
+    #[default]
+    Arbitrary,
+    /// Rooted at the midpoint of the longest leaf to leaf path
+    Midpoint,
+}
+
+/// Sequence of joins building a tree over leaves 0..n, where the k-th join creates node n+k with the given children and edge lengths
+pub(crate) struct Joins {
+    pub(crate) num_leaves: usize,
+    pub(crate) children: Vec<Vec<(usize, f64)>>,
+}
+
+impl Joins {
+    /// Builds a tree rooted at the node created by the final join, labelling leaves by taxa. Also returns the tree node of each leaf and join in order.
+    pub(crate) fn build_tree<R>(&self, taxa: &[TreeNodeMeta<R>]) -> (R, Vec<TreeNodeID<R>>)
+    where
+        R: RootedMetaTree + RootedWeightedTree,
+        <R as RootedTree>::Node: RootedMetaNode + RootedWeightedNode,
+        TreeNodeID<R>: From<usize>,
+    {
+        let mut tree = <R as RootedTree>::new();
+        let root_id = tree.get_root_id();
+        let root_idx = self.num_leaves + self.children.len() - 1;
+        let mut node_ids = vec![root_id; root_idx + 1];
+        let mut new_ids = (0..).map(TreeNodeID::<R>::from).filter(|id| *id != root_id);
+        let mut stack = vec![(root_id, root_idx)];
+        while let Some((n_id, idx)) = stack.pop() {
+            for (child, length) in self.children[idx - self.num_leaves].iter() {
+                let mut node = <R as RootedTree>::Node::new(new_ids.next().unwrap());
+                node.set_weight(<TreeNodeWeight<R> as NumCast>::from(*length));
+                match *child < self.num_leaves {
+                    true => node.set_taxa(Some(taxa[*child].clone())),
+                    false => stack.push((node.get_id(), *child)),
+                }
+                node_ids[*child] = node.get_id();
+                tree.add_child(n_id, node);
+            }
+        }
+        (tree, node_ids)
+    }
+}
+
+/// Returns the neighbor-joining joins of the lower triangle of a distance matrix over num_taxa taxa. Negative edge lengths are set to zero.
+///
+/// If variance_aware is true, distances to new clusters are weighted to minimise their variance as in BIONJ, where the variances of the input distances are taken to be the distances themselves.
+pub(crate) fn neighbor_joins(mut distances: Vec<f64>, num_taxa: usize, variance_aware: bool) -> Joins {
+    let mut joins = Joins {
+        num_leaves: num_taxa,
+        children: vec![],
+    };
+    let mut variances = variance_aware.then(|| distances.clone());
+    // matrix slots of clusters not yet joined, along with the node of each slot
+    let mut active = (0..num_taxa).collect_vec();
+    let mut slot_node = (0..num_taxa).collect_vec();
+    let mut row_sums = (0..num_taxa)
+        .map(|x| (0..num_taxa).filter(|y| x != *y).map(|y| distances[lower_index(x, y)]).sum::<f64>())
+        .collect_vec();
+    while active.len() > 3 {
+        let num_active = active.len();
+        // active slots are in increasing order, so each row is read from a contiguous part of the lower triangle
+        let row_min = |a: usize| {
+            let row = active[a] * (active[a] - 1) / 2;
+            (0..a)
+                .map(|b| {
+                    let q_value = (num_active - 2) as f64 * distances[row + active[b]]
+                        - row_sums[active[a]]
+                        - row_sums[active[b]];
+                    (q_value, a, b)
+                })
+                .min_by(|x, y| x.0.total_cmp(&y.0))
+                .unwrap()
+        };
+        let order = |x: &(f64, usize, usize), y: &(f64, usize, usize)| {
+            x.0.total_cmp(&y.0).then((x.1, x.2).cmp(&(y.1, y.2)))
+        };
+        #[cfg(feature = "parallel")]
+        let (_, a, b) = (1..num_active).into_par_iter().map(row_min).min_by(order).unwrap();
+        #[cfg(not(feature = "parallel"))]
+        let (_, a, b) = (1..num_active).map(row_min).min_by(order).unwrap();
+
+        let (x, y) = (active[a], active[b]);
+        let d_xy = distances[lower_index(x, y)];
+        let length_x = d_xy / 2.0 + (row_sums[x] - row_sums[y]) / (2.0 * (num_active - 2) as f64);
+        let length_y = d_xy - length_x;
+        joins.children.push(vec![
+            (slot_node[x], length_x.max(0.0)),
+            (slot_node[y], length_y.max(0.0)),
+        ]);
+        // weight of x in distances from the new cluster, which is a half for neighbor-joining
+        let lambda = match variances.as_ref() {
+            Some(v) if v[lower_index(x, y)] > 0.0 => {
+                let v_diff = active
+                    .iter()
+                    .filter(|z| **z != x && **z != y)
+                    .map(|z| v[lower_index(y, *z)] - v[lower_index(x, *z)])
+                    .sum::<f64>();
+                (0.5 + v_diff / (2.0 * (num_active - 2) as f64 * v[lower_index(x, y)])).clamp(0.0, 1.0)
+            }
+            _ => 0.5,
+        };
+        // the new cluster takes the slot of x
+        row_sums[x] = 0.0;
+        for z in active.iter().filter(|z| **z != x && **z != y) {
+            let (d_xz, d_yz) = (distances[lower_index(x, *z)], distances[lower_index(y, *z)]);
+            let d_new = lambda * (d_xz - length_x) + (1.0 - lambda) * (d_yz - length_y);
+            distances[lower_index(x, *z)] = d_new;
+            row_sums[*z] += d_new - d_xz - d_yz;
+            row_sums[x] += d_new;
+            if let Some(v) = variances.as_mut() {
+                v[lower_index(x, *z)] = lambda * v[lower_index(x, *z)] + (1.0 - lambda) * v[lower_index(y, *z)]
+                    - lambda * (1.0 - lambda) * v[lower_index(x, y)];
+            }
+        }
+        slot_node[x] = num_taxa + joins.children.len() - 1;
+        active.remove(b);
+    }
+    joins.children.push(match active.as_slice() {
+        [x, y, z] => {
+            let (d_xy, d_xz, d_yz) = (
+                distances[lower_index(*x, *y)],
+                distances[lower_index(*x, *z)],
+                distances[lower_index(*y, *z)],
+            );
+            vec![
+                (slot_node[*x], ((d_xy + d_xz - d_yz) / 2.0).max(0.0)),
+                (slot_node[*y], ((d_xy + d_yz - d_xz) / 2.0).max(0.0)),
+                (slot_node[*z], ((d_xz + d_yz - d_xy) / 2.0).max(0.0)),
+            ]
+        }
+        [x, y] => {
+            let d_xy = distances[lower_index(*x, *y)];
+            vec![(slot_node[*x], d_xy / 2.0), (slot_node[*y], d_xy / 2.0)]
+        }
+        _ => active.iter().map(|x| (slot_node[*x], 0.0)).collect_vec(),
+    });
+    joins
+}
+
+/// A trait describing construction of trees from pairwise distances between taxa by neighbor-joining.
+///
+/// The matrix is stored compactly as its lower triangle and joins run in O(n^3) time, with the search for the closest pair of clusters run in parallel under the `parallel` feature.
+pub trait NeighborJoining: Rooting
+where
+    <Self as RootedTree>::Node: RootedMetaNode + RootedWeightedNode,
+    TreeNodeID<Self>: From<usize>,
+{
+    /// Returns the neighbor-joining tree of a distance matrix. Negative edge lengths are set to zero.
+    fn neighbor_joining(
+        matrix: &PairwiseDistances<TreeNodeMeta<Self>, TreeNodeWeight<Self>>,
+        root: RootPlacement,
+    ) -> Result<Self, BuildError> {
+        if matrix.num_taxa() < 2 {
+            return Err(BuildError::TooFewTaxa { min: 2 });
+        }
+        let joins = neighbor_joins(matrix.lower_triangle(), matrix.num_taxa(), false);
+        let (mut tree, _) = joins.build_tree::<Self>(matrix.taxa());
+        if root == RootPlacement::Midpoint {
+            tree.midpoint_root()?;
+        }
+        Ok(tree)
+    }
+
+    /// Returns the BIONJ tree of a distance matrix, which selects pairs to join by the neighbor-joining criterion but weights distances to new clusters to minimise their variance. Negative edge lengths are set to zero.
+    fn bionj(
+        matrix: &PairwiseDistances<TreeNodeMeta<Self>, TreeNodeWeight<Self>>,
+        root: RootPlacement,
+    ) -> Result<Self, BuildError> {
+        if matrix.num_taxa() < 2 {
+            return Err(BuildError::TooFewTaxa { min: 2 });
+        }
+        let joins = neighbor_joins(matrix.lower_triangle(), matrix.num_taxa(), true);
+        let (mut tree, _) = joins.build_tree::<Self>(matrix.taxa());
+        if root == RootPlacement::Midpoint {
+            tree.midpoint_root()?;
+        }
+        Ok(tree)
+    }
+}
+
+/// Returns the joins of the lower triangle of a distance matrix over num_taxa taxa by average linkage, along with the height of each join. If weighted is true, the two clusters joined contribute equally to distances from the new cluster (WPGMA), otherwise each taxa contributes equally (UPGMA).
+pub(crate) fn average_linkage_joins(mut distances: Vec<f64>, num_taxa: usize, weighted: bool) -> (Joins, Vec<f64>) {
+    let mut joins = Joins {
+        num_leaves: num_taxa,
+        children: vec![],
+    };
+    let mut heights = vec![0.0; num_taxa];
+    let mut active = (0..num_taxa).collect_vec();
+    let mut slot_node = (0..num_taxa).collect_vec();
+    let mut slot_size = vec![1_usize; num_taxa];
+    while active.len() > 1 {
+        let (a, b) = (0..active.len())
+            .tuple_combinations()
+            .min_by(|(a, b), (c, d)| {
+                distances[lower_index(active[*a], active[*b])].total_cmp(&distances[lower_index(active[*c], active[*d])])
+            })
+            .unwrap();
+        let (x, y) = (active[a], active[b]);
+        // heights only decrease for negative distances, in which case edges are given zero length
+        let height = (distances[lower_index(x, y)] / 2.0)
+            .max(heights[slot_node[x]])
+            .max(heights[slot_node[y]]);
+        joins.children.push(vec![
+            (slot_node[x], height - heights[slot_node[x]]),
+            (slot_node[y], height - heights[slot_node[y]]),
+        ]);
+        heights.push(height);
+        let (size_x, size_y) = match weighted {
+            true => (1.0, 1.0),
+            false => (slot_size[x] as f64, slot_size[y] as f64),
+        };
+        // the new cluster takes the slot of x
+        for z in active.iter().filter(|z| **z != x && **z != y) {
+            distances[lower_index(x, *z)] = (size_x * distances[lower_index(x, *z)]
+                + size_y * distances[lower_index(y, *z)])
+                / (size_x + size_y);
+        }
+        slot_node[x] = num_taxa + joins.children.len() - 1;
+        slot_size[x] += slot_size[y];
+        active.remove(b);
+    }
+    (joins, heights)
+}
+
+/// A trait describing construction of ultrametric trees from pairwise distances between taxa by average linkage clustering.
+///
+/// The height of each node is set as its zeta value, with edge weights set to differences of heights.
+pub trait AverageLinkage: RootedMetaTree + RootedWeightedTree + PathFunction
+where
+    <Self as RootedTree>::Node: RootedMetaNode + RootedWeightedNode + RootedZetaNode,
+    TreeNodeID<Self>: From<usize>,
+{
+    /// Returns the UPGMA tree of a distance matrix, where the distance between clusters is the mean distance between their taxa.
+    fn upgma(matrix: &PairwiseDistances<TreeNodeMeta<Self>, TreeNodeWeight<Self>>) -> Result<Self, BuildError> {
+        average_linkage_tree(matrix, false)
+    }
+
+    /// Returns the WPGMA tree of a distance matrix, where the distance between clusters is the mean of the distances of the two clusters joined to form it.
+    fn wpgma(matrix: &PairwiseDistances<TreeNodeMeta<Self>, TreeNodeWeight<Self>>) -> Result<Self, BuildError> {
+        average_linkage_tree(matrix, true)
+    }
+}
+
+fn average_linkage_tree<R>(
+    matrix: &PairwiseDistances<TreeNodeMeta<R>, TreeNodeWeight<R>>,
+    weighted: bool,
+) -> Result<R, BuildError>
+where
+    R: RootedMetaTree + RootedWeightedTree + PathFunction,
+    <R as RootedTree>::Node: RootedMetaNode + RootedWeightedNode + RootedZetaNode,
+    TreeNodeID<R>: From<usize>,
+{
+    if matrix.num_taxa() < 2 {
+        return Err(BuildError::TooFewTaxa { min: 2 });
+    }
+    let (joins, heights) = average_linkage_joins(matrix.lower_triangle(), matrix.num_taxa(), weighted);
+    let (mut tree, node_ids) = joins.build_tree::<R>(matrix.taxa());
+    for (n_id, height) in node_ids.into_iter().zip(heights) {
+        tree.set_node_zeta(n_id, <TreeNodeZeta<R> as NumCast>::from(height));
+    }
+    Ok(tree)
+}
+
+/// Selects the topological moves used to search for balanced minimum evolution trees
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TopologySearch {
+    /// Nearest neighbor interchanges
+    #[default]
+    Nni,
+    /// Subtree prune and regraft moves, of which nearest neighbor interchanges are a special case
+    Spr,
+}
+
+/// Unrooted binary tree over leaves 0..n with internal nodes n.., searched for balanced minimum evolution trees
+#[derive(Debug, Clone)]
+struct BmeTree {
+    num_leaves: usize,
+    neighbours: Vec<Vec<usize>>,
+}
+
+impl BmeTree {
+    /// Returns the unrooted binary tree of a rooted tree, suppressing nodes of degree two and resolving multifurcations arbitrarily. Leaves are numbered by the index of their taxa.
+    fn from_tree<R>(tree: &R, taxa_idx: &HashMap<&TreeNodeMeta<R>, usize>) -> Result<Self, BuildError>
+    where
+        R: RootedMetaTree,
+        <R as RootedTree>::Node: RootedMetaNode,
+    {
+        let num_leaves = taxa_idx.len();
+        let mut node_idx: HashMap<TreeNodeID<R>, usize> = vec![].into_iter().collect();
+        let mut seen = vec![false; num_leaves];
+        let mut num_nodes = num_leaves;
+        for n_id in tree.get_node_ids() {
+            match tree.is_leaf(n_id) {
+                true => {
+                    let idx = *tree
+                        .get_node_taxa(n_id)
+                        .and_then(|taxa| taxa_idx.get(taxa))
+                        .ok_or(BuildError::TaxaMismatch)?;
+                    if seen[idx] {
+                        return Err(BuildError::TaxaMismatch);
+                    }
+                    seen[idx] = true;
+                    node_idx.insert(n_id, idx);
+                }
+                false => {
+                    node_idx.insert(n_id, num_nodes);
+                    num_nodes += 1;
+                }
+            }
+        }
+        if seen.iter().any(|s| !s) {
+            return Err(BuildError::TaxaMismatch);
+        }
+        let mut neighbours = vec![vec![]; num_nodes];
+        for n_id in tree.get_node_ids() {
+            if let Some(p_id) = tree.get_node_parent_id(n_id) {
+                neighbours[node_idx[&n_id]].push(node_idx[&p_id]);
+                neighbours[node_idx[&p_id]].push(node_idx[&n_id]);
+            }
+        }
+        // suppress internal nodes of degree one or two, which may expose other such nodes
+        let mut stack = (num_leaves..num_nodes).collect_vec();
+        while let Some(u) = stack.pop() {
+            match neighbours[u].clone().as_slice() {
+                [v] => {
+                    neighbours[*v].retain(|w| *w != u);
+                    neighbours[u].clear();
+                    stack.push(*v);
+                }
+                [v, w] => {
+                    neighbours[*v].iter_mut().filter(|z| **z == u).for_each(|z| *z = *w);
+                    neighbours[*w].iter_mut().filter(|z| **z == u).for_each(|z| *z = *v);
+                    neighbours[u].clear();
+                }
+                _ => {}
+            }
+        }
+        // resolve multifurcations by moving pairs of neighbours below new nodes
+        for u in num_leaves..num_nodes {
+            while neighbours[u].len() > 3 {
+                let w = neighbours.len();
+                let num_neighbours = neighbours[u].len();
+                let moved = neighbours[u].split_off(num_neighbours - 2);
+                for v in moved.iter() {
+                    neighbours[*v].iter_mut().filter(|z| **z == u).for_each(|z| *z = w);
+                }
+                neighbours.push(moved);
+                neighbours[w].push(u);
+                neighbours[u].push(w);
+            }
+        }
+        // renumber internal nodes contiguously
+        let kept = (0..neighbours.len())
+            .filter(|u| *u < num_leaves || !neighbours[*u].is_empty())
+            .collect_vec();
+        let mut new_idx = vec![usize::MAX; neighbours.len()];
+        kept.iter().enumerate().for_each(|(idx, u)| new_idx[*u] = idx);
+        Ok(BmeTree {
+            num_leaves,
+            neighbours: kept
+                .iter()
+                .map(|u| neighbours[*u].iter().map(|v| new_idx[*v]).collect_vec())
+                .collect_vec(),
+        })
+    }
+
+    /// Returns the nodes of the subtree containing to, when the edge between from and to is removed
+    fn subtree_nodes(&self, from: usize, to: usize) -> Vec<usize> {
+        let mut nodes = vec![];
+        let mut stack = vec![(to, from)];
+        while let Some((u, prev)) = stack.pop() {
+            nodes.push(u);
+            stack.extend(self.neighbours[u].iter().filter(|v| **v != prev).map(|v| (*v, u)));
+        }
+        nodes
+    }
+
+    /// Prunes the subtree containing x away from its neighbour a and regrafts it onto the edge between b and c. Returns false if the move is not possible.
+    fn regraft(&mut self, x: usize, a: usize, b: usize, c: usize) -> bool {
+        let others = self.neighbours[a].iter().copied().filter(|v| *v != x).collect_vec();
+        if others.len() != 2 || others.len() == self.neighbours[a].len() || !self.neighbours[b].contains(&c) {
+            return false;
+        }
+        let pruned = self.subtree_nodes(a, x);
+        if [a, b, c].iter().any(|u| pruned.contains(u)) || b == a || c == a {
+            return false;
+        }
+        let (n1, n2) = (others[0], others[1]);
+        self.neighbours[n1].iter_mut().filter(|z| **z == a).for_each(|z| *z = n2);
+        self.neighbours[n2].iter_mut().filter(|z| **z == a).for_each(|z| *z = n1);
+        self.neighbours[b].iter_mut().filter(|z| **z == c).for_each(|z| *z = a);
+        self.neighbours[c].iter_mut().filter(|z| **z == b).for_each(|z| *z = a);
+        self.neighbours[a] = vec![x, b, c];
+        true
+    }
+
+    /// Returns the joins of the tree rooted at its last internal node, with edge lengths given by a function of the nodes of each edge
+    fn to_joins(&self, edge_length: impl Fn(usize, usize) -> f64) -> Joins {
+        let root = self.neighbours.len() - 1;
+        let mut children = vec![vec![]; self.neighbours.len() - self.num_leaves];
+        let mut stack = vec![(root, usize::MAX)];
+        let mut order = vec![];
+        while let Some((u, prev)) = stack.pop() {
+            order.push(u);
+            for v in self.neighbours[u].iter().filter(|v| **v != prev) {
+                children[u - self.num_leaves].push((*v, edge_length(u, *v)));
+                if *v >= self.num_leaves {
+                    stack.push((*v, u));
+                }
+            }
+        }
+        // joins are ordered so that the root is the final join
+        let mut joins_idx = vec![usize::MAX; self.neighbours.len()];
+        let internal = order.into_iter().rev().collect_vec();
+        internal
+            .iter()
+            .enumerate()
+            .for_each(|(idx, u)| joins_idx[*u] = self.num_leaves + idx);
+        let index = |u: usize| match u < self.num_leaves {
+            true => u,
+            false => joins_idx[u],
+        };
+        Joins {
+            num_leaves: self.num_leaves,
+            children: internal
+                .iter()
+                .map(|u| {
+                    children[u - self.num_leaves]
+                        .iter()
+                        .map(|(v, length)| (index(*v), *length))
+                        .collect_vec()
+                })
+                .collect_vec(),
+        }
+    }
+}
+
+/// Subtree of a tree rooted at leaf 0, either below a node or its complement
+#[derive(Debug, Clone, Copy)]
+enum Subtree {
+    Down(usize),
+    Up(usize),
+}
+
+/// Balanced average distances between all pairs of disjoint subtrees of a BmeTree, where the balanced average distance between two subtrees is the mean of the balanced average distances between the subtrees of either one and the other.
+///
+/// Subtrees are described with the tree rooted at leaf 0. The averages for a pair of nodes hold the distance between the subtrees below them if they are disjoint, and otherwise between the subtree below the descendant and the complement of the subtree below the ancestor. For a tree with m = 2n - 2 nodes these take m(m - 1)/2 floats, about 16n^2 bytes.
+struct BalancedAverages {
+    parent: Vec<usize>,
+    children: Vec<Vec<usize>>,
+    position: Vec<usize>,
+    size: Vec<usize>,
+    /// Balanced average distances between the subtree below each node and the root leaf
+    root_averages: Vec<f64>,
+    averages: Vec<f64>,
+}
+
+impl BalancedAverages {
+    /// Computes balanced average distances in O(n^2) time from the lower triangle of a distance matrix between leaves
+    fn new(tree: &BmeTree, distances: &[f64]) -> Self {
+        let num_nodes = tree.neighbours.len();
+        let mut averages = BalancedAverages {
+            parent: vec![usize::MAX; num_nodes],
+            children: vec![vec![]; num_nodes],
+            position: vec![0; num_nodes],
+            size: vec![0; num_nodes],
+            root_averages: vec![0.0; num_nodes],
+            averages: vec![0.0; num_nodes * (num_nodes - 1) / 2],
+        };
+        averages.update(tree, distances, true);
+        averages
+    }
+
+    /// Updates the balanced average distances after the topology of the tree has changed, recomputing those between pairs of subtrees of which either has changed, or all of them if all is true.
+    ///
+    /// Applying k moves to a tree of diameter d changes the subtrees below O(kd) nodes, each paired with O(n) others, and the complements of subtrees, which are paired with the O(nd) subtrees below their descendants. The update then takes O(knd) time, and at most the O(n^2) time of computing all averages.
+    fn update(&mut self, tree: &BmeTree, distances: &[f64], all: bool) {
+        let num_nodes = tree.neighbours.len();
+        let mut parent = vec![usize::MAX; num_nodes];
+        let mut preorder = vec![];
+        let mut stack = vec![0];
+        while let Some(u) = stack.pop() {
+            preorder.push(u);
+            let p = parent[u];
+            for v in tree.neighbours[u].iter().filter(|v| **v != p) {
+                parent[*v] = u;
+                stack.push(*v);
+            }
+        }
+        let children = (0..num_nodes)
+            .map(|u| tree.neighbours[u].iter().copied().filter(|v| *v != parent[u]).sorted().collect_vec())
+            .collect_vec();
+        let mut position = vec![0; num_nodes];
+        preorder.iter().enumerate().for_each(|(pos, u)| position[*u] = pos);
+        let mut size = vec![1; num_nodes];
+        for u in preorder.iter().rev().filter(|u| **u != 0) {
+            size[parent[*u]] += size[*u];
+        }
+        let is_ancestor = |u: usize, v: usize| position[u] <= position[v] && position[v] < position[u] + size[u];
+        let (old_position, old_size) = (std::mem::take(&mut self.position), std::mem::take(&mut self.size));
+        let was_ancestor =
+            |u: usize, v: usize| old_position[u] <= old_position[v] && old_position[v] < old_position[u] + old_size[u];
+
+        // nodes whose children have changed, and nodes whose subtrees or complements of subtrees have changed
+        let rewired = (0..num_nodes).map(|u| all || children[u] != self.children[u]).collect_vec();
+        let mut dirty = rewired.clone();
+        for u in preorder.iter().rev().filter(|u| **u != 0) {
+            dirty[parent[*u]] |= dirty[*u];
+        }
+        let mut up_dirty = vec![all; num_nodes];
+        for w in preorder.iter().filter(|w| **w != 0) {
+            let p = parent[*w];
+            up_dirty[*w] |= match p {
+                0 => self.parent[*w] != 0,
+                _ => {
+                    let sibling = *children[p].iter().find(|s| **s != *w).unwrap();
+                    up_dirty[p] || rewired[p] || parent[p] != self.parent[p] || dirty[sibling]
+                }
+            };
+        }
+
+        // nodes other than the root leaf, with children before their parents
+        let order = preorder.iter().rev().copied().filter(|u| *u != 0).collect_vec();
+        let dirty_nodes = order.iter().copied().filter(|u| dirty[*u]).collect_vec();
+        for v in order.iter().filter(|v| dirty[**v]) {
+            self.root_averages[*v] = match *v < tree.num_leaves {
+                true => distances[lower_index(*v, 0)],
+                false => children[*v].iter().map(|c| self.root_averages[*c]).sum::<f64>() / 2.0,
+            };
+        }
+        for (pos, v) in order.iter().enumerate() {
+            let others = match dirty[*v] {
+                true => &order[..pos],
+                false => &dirty_nodes[..dirty_nodes.partition_point(|w| position[*w] > position[*v])],
+            };
+            for w in others.iter().filter(|w| !is_ancestor(*v, **w)) {
+                self.averages[lower_index(*v, *w)] = match (*v < tree.num_leaves, *w < tree.num_leaves) {
+                    (false, _) => children[*v].iter().map(|c| self.averages[lower_index(*c, *w)]).sum::<f64>() / 2.0,
+                    (true, false) => children[*w].iter().map(|c| self.averages[lower_index(*v, *c)]).sum::<f64>() / 2.0,
+                    (true, true) => distances[lower_index(*v, *w)],
+                };
+            }
+        }
+        for v in order.iter() {
+            let mut ancestors = vec![];
+            let mut u = parent[*v];
+            while u != 0 {
+                ancestors.push(u);
+                u = parent[u];
+            }
+            let mut ancestors = ancestors.into_iter().rev();
+            let Some(mut prev) = ancestors.next() else {
+                continue;
+            };
+            if dirty[*v] || up_dirty[prev] || !was_ancestor(prev, *v) {
+                self.averages[lower_index(*v, prev)] = self.root_averages[*v];
+            }
+            for w in ancestors {
+                if dirty[*v] || up_dirty[w] || !was_ancestor(w, *v) {
+                    let sibling = *children[prev].iter().find(|s| **s != w).unwrap();
+                    self.averages[lower_index(*v, w)] =
+                        (self.averages[lower_index(*v, prev)] + self.averages[lower_index(*v, sibling)]) / 2.0;
+                }
+                prev = w;
+            }
+        }
+        self.parent = parent;
+        self.children = children;
+        self.position = position;
+        self.size = size;
+    }
+
+    /// Returns the subtree containing to, when the edge between from and to is removed
+    fn subtree(&self, from: usize, to: usize) -> Subtree {
+        match self.parent[to] == from {
+            true => Subtree::Down(to),
+            false => Subtree::Up(from),
+        }
+    }
+
+    /// Returns the balanced average distance between two disjoint subtrees
+    fn delta(&self, x: Subtree, y: Subtree) -> f64 {
+        match (x, y) {
+            (Subtree::Down(u), Subtree::Down(v))
+            | (Subtree::Down(u), Subtree::Up(v))
+            | (Subtree::Up(u), Subtree::Down(v)) => self.averages[lower_index(u, v)],
+            (Subtree::Up(_), Subtree::Up(_)) => unreachable!("complements of subtrees are never disjoint"),
+        }
+    }
+
+    /// Returns the balanced minimum evolution length of the edge between u and v
+    fn edge_length(&self, tree: &BmeTree, u: usize, v: usize) -> f64 {
+        let sides = |from: usize, to: usize| {
+            tree.neighbours[from]
+                .iter()
+                .filter(|w| **w != to)
+                .map(|w| self.subtree(from, *w))
+                .collect_vec()
+        };
+        match (tree.neighbours[u].len(), tree.neighbours[v].len()) {
+            (1, _) | (_, 1) => {
+                let (leaf, a) = match tree.neighbours[u].len() {
+                    1 => (u, v),
+                    _ => (v, u),
+                };
+                let x = self.subtree(a, leaf);
+                let others = sides(a, leaf);
+                (self.delta(x, others[0]) + self.delta(x, others[1]) - self.delta(others[0], others[1])) / 2.0
+            }
+            _ => {
+                let (ab, cd) = (sides(u, v), sides(v, u));
+                let cross = ab
+                    .iter()
+                    .cartesian_product(cd.iter())
+                    .map(|(x, y)| self.delta(*x, *y))
+                    .sum::<f64>();
+                cross / 4.0 - (self.delta(ab[0], ab[1]) + self.delta(cd[0], cd[1])) / 2.0
+            }
+        }
+    }
+
+    /// Returns the balanced minimum evolution length of a tree, which is the sum of its edge lengths
+    fn tree_length(&self, tree: &BmeTree) -> f64 {
+        (0..tree.neighbours.len())
+            .flat_map(|u| tree.neighbours[u].iter().filter(move |v| u < **v).map(move |v| (u, *v)))
+            .map(|(u, v)| self.edge_length(tree, u, v))
+            .sum()
+    }
+
+    /// Returns the decrease in tree length and the regrafting edge of the best move of each subtree that shortens the tree, where subtrees are regrafted at most radius edges away.
+    ///
+    /// Moving a subtree is a sequence of nearest neighbor interchanges, each of which changes the length of the tree by a quarter of the difference of sums of balanced average distances between the subtrees around an edge. The subtree left behind the moving subtree only differs from a subtree of the original tree by the moving subtree, so each change is found in constant time.
+    fn best_moves(&self, tree: &BmeTree, radius: usize) -> Vec<(f64, [usize; 4])> {
+        let mut moves = vec![];
+        for a in tree.num_leaves..tree.neighbours.len() {
+            for x in tree.neighbours[a].iter().copied() {
+                let pruned = self.subtree(a, x);
+                let others = tree.neighbours[a].iter().copied().filter(|v| *v != x).collect_vec();
+                let mut best: Option<(f64, [usize; 4])> = None;
+                for (start, behind) in [(others[0], others[1]), (others[1], others[0])] {
+                    let behind = self.subtree(a, behind);
+                    // (node, previous node, steps, distance between pruned subtree and the subtree behind it, decrease in length)
+                    let mut stack = vec![(start, a, 0, self.delta(pruned, behind), 0.0)];
+                    while let Some((b, prev, steps, pruned_behind, decrease)) = stack.pop() {
+                        let onward = tree.neighbours[b].iter().copied().filter(|v| *v != prev).collect_vec();
+                        if onward.len() != 2 {
+                            continue;
+                        }
+                        for (c, s) in [(onward[0], onward[1]), (onward[1], onward[0])] {
+                            let (subtree_c, subtree_s) = (self.subtree(b, c), self.subtree(b, s));
+                            let behind_s = self.delta(self.subtree(b, prev), subtree_s)
+                                + 0.5_f64.powi(steps as i32 + 1)
+                                    * (self.delta(behind, subtree_s) - self.delta(pruned, subtree_s));
+                            let decrease = decrease
+                                + ((pruned_behind + self.delta(subtree_s, subtree_c))
+                                    - (self.delta(pruned, subtree_c) + behind_s))
+                                    / 4.0;
+                            if best.is_none_or(|(d, _)| decrease > d) {
+                                best = Some((decrease, [x, a, b, c]));
+                            }
+                            if steps + 1 < radius {
+                                let pruned_behind = (pruned_behind + self.delta(pruned, subtree_s)) / 2.0;
+                                stack.push((c, b, steps + 1, pruned_behind, decrease));
+                            }
+                        }
+                    }
+                }
+                moves.extend(best.filter(|(decrease, _)| *decrease > 0.0));
+            }
+        }
+        moves
+    }
+}
+
+/// Returns a tree of locally minimal balanced minimum evolution length found from a starting tree by moving subtrees at most radius edges away, along with its balanced average distances.
+///
+/// Each round applies the best moves of distinct parts of the tree together, falling back to the single best move if they do not shorten the tree together. The balanced average distances are updated after each round rather than recomputed, as in FastME.
+fn bme_search(mut tree: BmeTree, distances: &[f64], radius: usize) -> (BmeTree, BalancedAverages) {
+    let mut averages = BalancedAverages::new(&tree, distances);
+    let mut length = averages.tree_length(&tree);
+    loop {
+        let tolerance = 1e-10 * (1.0 + length.abs());
+        let moves = averages
+            .best_moves(&tree, radius)
+            .into_iter()
+            .filter(|(decrease, _)| *decrease > tolerance)
+            .sorted_by(|x, y| y.0.total_cmp(&x.0))
+            .collect_vec();
+        let Some((_, best_move)) = moves.first().copied() else {
+            return (tree, averages);
+        };
+        let previous = tree.clone();
+        let mut touched = vec![false; tree.neighbours.len()];
+        for (_, [x, a, b, c]) in moves {
+            let nodes = tree.neighbours[a].iter().copied().chain([a, b, c]).collect_vec();
+            if nodes.iter().all(|u| !touched[*u]) && tree.regraft(x, a, b, c) {
+                nodes.into_iter().for_each(|u| touched[u] = true);
+            }
+        }
+        averages.update(&tree, distances, false);
+        let mut new_length = averages.tree_length(&tree);
+        if new_length >= length - tolerance {
+            tree = previous.clone();
+            let [x, a, b, c] = best_move;
+            tree.regraft(x, a, b, c);
+            averages.update(&tree, distances, false);
+            new_length = averages.tree_length(&tree);
+            if new_length >= length - tolerance {
+                averages.update(&previous, distances, false);
+                return (previous, averages);
+            }
+        }
+        length = new_length;
+    }
+}
+
+/// A trait describing construction of trees from pairwise distances between taxa under the balanced minimum evolution criterion, in the manner of FastME.
+///
+/// The balanced minimum evolution length of an unrooted binary tree is the sum of the lengths of its edges fitted by balanced least squares. Balanced average distances between all pairs of disjoint subtrees are stored, so searches take O(n^2) memory, about 16n^2 bytes for n taxa. They are computed once in O(n^2) time, and updated after each round of k moves in O(knd) time for trees of diameter d. Finding the best moves of a round takes O(n) time for nearest neighbor interchanges and O(n^2) time for subtree prune and regraft moves.
+pub trait BalancedMinimumEvolution: NeighborJoining
+where
+    <Self as RootedTree>::Node: RootedMetaNode + RootedWeightedNode,
+    TreeNodeID<Self>: From<usize>,
+{
+    /// Returns a tree of locally minimal balanced minimum evolution length, found by improving the topology of a starting tree with moves of subtrees. Multifurcations of the starting tree are resolved arbitrarily and edge weights are set to balanced minimum evolution lengths, where negative lengths are set to zero.
+    fn bme_refine(
+        start: &Self,
+        matrix: &PairwiseDistances<TreeNodeMeta<Self>, TreeNodeWeight<Self>>,
+        search: TopologySearch,
+        root: RootPlacement,
+    ) -> Result<Self, BuildError> {
+        let taxa_idx: HashMap<&TreeNodeMeta<Self>, usize> =
+            matrix.taxa().iter().enumerate().map(|(idx, taxa)| (taxa, idx)).collect();
+        if taxa_idx.len() != matrix.num_taxa() {
+            return Err(BuildError::TaxaMismatch);
+        }
+        let start = BmeTree::from_tree(start, &taxa_idx)?;
+        if matrix.num_taxa() < 4 {
+            return Self::bionj(matrix, root);
+        }
+        let radius = match search {
+            TopologySearch::Nni => 1,
+            TopologySearch::Spr => usize::MAX,
+        };
+        let (bme_tree, averages) = bme_search(start, &matrix.lower_triangle(), radius);
+        let joins = bme_tree.to_joins(|u, v| averages.edge_length(&bme_tree, u, v).max(0.0));
+        let (mut tree, _) = joins.build_tree::<Self>(matrix.taxa());
+        if root == RootPlacement::Midpoint {
+            tree.midpoint_root()?;
+        }
+        Ok(tree)
+    }
+
+    /// Returns a tree of locally minimal balanced minimum evolution length, found by improving the BIONJ tree of a distance matrix with moves of subtrees.
+    fn bme(
+        matrix: &PairwiseDistances<TreeNodeMeta<Self>, TreeNodeWeight<Self>>,
+        search: TopologySearch,
+        root: RootPlacement,
+    ) -> Result<Self, BuildError> {
+        let start = Self::bionj(matrix, RootPlacement::Arbitrary)?;
+        Self::bme_refine(&start, matrix, search, root)
+    }
+}
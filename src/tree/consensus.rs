@@ -0,0 +1,479 @@
+use itertools::Itertools;
+use num::{NumCast, ToPrimitive};
+use vers_vecs::BitVec;
+
+#[cfg(feature = "non_crypto_hash")]
+use fxhash::{FxHashMap as HashMap, FxHashSet as HashSet};
+#[cfg(not(feature = "non_crypto_hash"))]
+use std::collections::{HashMap, HashSet};
+
+use crate::prelude::*;
+use crate::tree::distances::{leaf_taxa_index, node_clusters};
+
+/// Selects which clades are included in a consensus tree
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ConsensusMethod {
+    /// Clades present in every tree
+    Strict,
+    /// Clades present in a fraction of trees of at least a threshold between 0.5 and 1, or more than half of the trees if the threshold is 0.5
+    Majority(f64),
+    /// Majority-rule clades, followed by less frequent clades greedily in decreasing order of frequency while compatible with the clades already included
+    #[default]
+    ExtendedMajority,
+}
+
+/// A trait describing construction of consensus trees from a collection of rooted trees on the same taxa.
+///
+/// Clades of the consensus tree are labelled with their frequencies as zeta values of internal nodes.
+pub trait Consensus: RootedMetaTree + RootedWeightedTree + Clusters + PathFunction
+where
+    <Self as RootedTree>::Node: RootedMetaNode + RootedWeightedNode + RootedZetaNode,
+    TreeNodeID<Self>: From<usize>,
+{
+    /// Returns the consensus tree of trees. If average_branch_lengths is true, the length of each edge is the mean length of the edges above its clade over the trees containing the clade, ignoring missing edge weights.
+    fn consensus(trees: &[Self], method: ConsensusMethod, average_branch_lengths: bool) -> Result<Self, ConsensusError> {
+        let clade_counts = CladeCounts::new(trees)?;
+        let num_trees = trees.len() as f64;
+        let nontrivial = (0..clade_counts.clades.len())
+            .filter(|idx| clade_counts.clades[*idx].count_ones() > 1)
+            .collect_vec();
+        let clades = match method {
+            ConsensusMethod::Strict => nontrivial
+                .into_iter()
+                .filter(|idx| clade_counts.counts[*idx] == trees.len())
+                .collect_vec(),
+            ConsensusMethod::Majority(threshold) => {
+                if !(0.5..=1.0).contains(&threshold) {
+                    return Err(ConsensusError::InvalidThreshold { threshold });
+                }
+                // clades in exactly half of the trees may conflict, so the threshold is strict at 0.5
+                let frequent = |frequency: f64| match threshold > 0.5 {
+                    true => frequency >= threshold,
+                    false => frequency > threshold,
+                };
+                nontrivial
+                    .into_iter()
+                    .filter(|idx| frequent(clade_counts.counts[*idx] as f64 / num_trees))
+                    .collect_vec()
+            }
+            ConsensusMethod::ExtendedMajority => {
+                let mut clades: Vec<usize> = vec![];
+                for idx in nontrivial
+                    .into_iter()
+                    .sorted_by_key(|idx| std::cmp::Reverse(clade_counts.counts[*idx]))
+                {
+                    let clade = &clade_counts.clades[idx];
+                    if clades.iter().all(|c| is_compatible(clade, &clade_counts.clades[*c])) {
+                        clades.push(idx);
+                    }
+                }
+                clades
+            }
+        };
+        let clusters = clades.iter().map(|idx| &clade_counts.clades[*idx]).collect_vec();
+        let (mut tree, node_clusters) = clade_counts.build_tree(&clusters);
+        for (n_id, bp) in node_clusters {
+            let idx = clade_counts.clade_idx[&bp];
+            if clade_counts.clades[idx].count_ones() > 1 {
+                let frequency = clade_counts.counts[idx] as f64 / num_trees;
+                tree.set_node_zeta(n_id, <TreeNodeZeta<Self> as NumCast>::from(frequency));
+            }
+            if average_branch_lengths && clade_counts.weight_counts[idx] > 0 {
+                let weight = clade_counts.weight_sums[idx] / clade_counts.weight_counts[idx] as f64;
+                tree.get_node_mut(n_id)
+                    .unwrap()
+                    .set_weight(<TreeNodeWeight<Self> as NumCast>::from(weight));
+            }
+        }
+        Ok(tree)
+    }
+}
+
+/// Returns true if two clusters are disjoint or nested
+pub(crate) fn is_compatible(x: &BitVec, y: &BitVec) -> bool {
+    let overlap = x.mask_and(y).unwrap().count_ones();
+    overlap == 0 || overlap == x.count_ones() || overlap == y.count_ones()
+}
+
+/// Frequencies of the clusters of a collection of rooted trees on the same taxa
+pub(crate) struct CladeCounts<'a, T>
+where
+    T: RootedMetaTree,
+    <T as RootedTree>::Node: RootedMetaNode,
+{
+    /// Taxa in order of their index
+    pub(crate) taxa: Vec<&'a TreeNodeMeta<T>>,
+    pub(crate) taxa_map: HashMap<&'a TreeNodeMeta<T>, usize>,
+    /// Clusters of all non-root nodes in order of first appearance
+    pub(crate) clades: Vec<BitVec>,
+    pub(crate) clade_idx: HashMap<BitVec, usize>,
+    /// Number of trees containing each cluster
+    pub(crate) counts: Vec<usize>,
+    /// Total length of the edges above each cluster
+    pub(crate) weight_sums: Vec<f64>,
+    /// Number of weighted edges above each cluster
+    pub(crate) weight_counts: Vec<usize>,
+}
+
+impl<'a, T> CladeCounts<'a, T>
+where
+    T: RootedMetaTree + RootedWeightedTree + Clusters,
+    <T as RootedTree>::Node: RootedMetaNode + RootedWeightedNode,
+{
+    pub(crate) fn new(trees: &'a [T]) -> Result<Self, ConsensusError> {
+        let first_tree = trees.first().ok_or(ConsensusError::NoTrees)?;
+        let taxa_map = leaf_taxa_index(&[first_tree]);
+        let taxa = taxa_map
+            .iter()
+            .sorted_by_key(|(_, idx)| **idx)
+            .map(|(taxa, _)| *taxa)
+            .collect_vec();
+        let mut clade_counts = CladeCounts {
+            taxa,
+            taxa_map,
+            clades: vec![],
+            clade_idx: vec![].into_iter().collect(),
+            counts: vec![],
+            weight_sums: vec![],
+            weight_counts: vec![],
+        };
+        for tree in trees {
+            let leaf_taxa: HashSet<_> = tree.get_leaf_ids().filter_map(|id| tree.get_node_taxa(id)).collect();
+            if leaf_taxa.len() != clade_counts.taxa.len() || leaf_taxa.iter().any(|t| !clade_counts.taxa_map.contains_key(t)) {
+                return Err(ConsensusError::TaxaMismatch);
+            }
+            let mut tree_clades: HashSet<usize> = vec![].into_iter().collect();
+            for (n_id, bp) in clade_counts.tree_clades(tree) {
+                let idx = clade_counts.clade_index(bp);
+                if let Some(w) = tree.get_node(n_id).unwrap().get_weight() {
+                    clade_counts.weight_sums[idx] += w.to_f64().unwrap_or(0.0);
+                    clade_counts.weight_counts[idx] += 1;
+                }
+                tree_clades.insert(idx);
+            }
+            for idx in tree_clades {
+                clade_counts.counts[idx] += 1;
+            }
+        }
+        Ok(clade_counts)
+    }
+
+    /// Returns the index of a cluster, adding it if it was not seen before
+    fn clade_index(&mut self, bp: BitVec) -> usize {
+        if let Some(idx) = self.clade_idx.get(&bp) {
+            return *idx;
+        }
+        self.clades.push(bp.clone());
+        self.clade_idx.insert(bp, self.clades.len() - 1);
+        self.counts.push(0);
+        self.weight_sums.push(0.0);
+        self.weight_counts.push(0);
+        self.clades.len() - 1
+    }
+
+    /// Returns the clusters of all non-root nodes of a tree
+    pub(crate) fn tree_clades(&self, tree: &T) -> Vec<(TreeNodeID<T>, BitVec)> {
+        node_clusters(tree, &self.taxa_map, self.taxa.len())
+            .into_iter()
+            .filter(|(n_id, bp)| *n_id != tree.get_root_id() && bp.count_ones() > 0)
+            .collect_vec()
+    }
+
+    /// Builds a tree from pairwise compatible nontrivial clusters, returning the tree along with the cluster of each non-root node
+    pub(crate) fn build_tree(&self, clusters: &[&BitVec]) -> (T, Vec<(TreeNodeID<T>, BitVec)>)
+    where
+        TreeNodeID<T>: From<usize>,
+    {
+        let mut tree = <T as RootedTree>::new();
+        let root_id = tree.get_root_id();
+        let mut new_ids = (0..).map(TreeNodeID::<T>::from).filter(|id| *id != root_id);
+        let mut node_clusters: Vec<(TreeNodeID<T>, BitVec)> = vec![];
+        // clusters are added in decreasing order of size, so that every cluster follows the clusters containing it
+        let smallest_superset = |bp: &BitVec, node_clusters: &[(TreeNodeID<T>, BitVec)]| {
+            node_clusters
+                .iter()
+                .filter(|(_, cluster)| cluster.mask_and(bp).unwrap().count_ones() == bp.count_ones())
+                .last()
+                .map_or(root_id, |(n_id, _)| *n_id)
+        };
+        let singletons = (0..self.taxa.len()).map(|t| {
+            let mut bp = BitVec::from_zeros(self.taxa.len());
+            bp.flip_bit(t);
+            bp
+        });
+        for bp in clusters
+            .iter()
+            .map(|bp| (*bp).clone())
+            .sorted_by_key(|bp| std::cmp::Reverse(bp.count_ones()))
+            .chain(singletons)
+        {
+            let parent_id = smallest_superset(&bp, &node_clusters);
+            let mut node = <T as RootedTree>::Node::new(new_ids.next().unwrap());
+            if bp.count_ones() == 1 {
+                let t = (0..self.taxa.len()).find(|t| bp.is_bit_set(*t).unwrap()).unwrap();
+                node.set_taxa(Some(self.taxa[t].clone()));
+            }
+            node_clusters.push((node.get_id(), bp));
+            tree.add_child(parent_id, node);
+        }
+        (tree, node_clusters)
+    }
+}
+
+/// Selects how clade credibilities are combined into the credibility of a tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CladeCredibility {
+    /// Product of the frequencies of the clades of a tree
+    #[default]
+    Product,
+    /// Sum of the frequencies of the clades of a tree
+    Sum,
+}
+
+/// Summary of the heights of a clade over the trees containing it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeightSummary {
+    /// Mean height
+    pub mean: f64,
+    /// Median height
+    pub median: f64,
+    /// Lower bound of the 95% highest posterior density interval
+    pub hpd_lower: f64,
+    /// Upper bound of the 95% highest posterior density interval
+    pub hpd_upper: f64,
+}
+
+impl HeightSummary {
+    fn new(mut heights: Vec<f64>) -> Self {
+        heights.sort_by(|x, y| x.total_cmp(y));
+        let num = heights.len();
+        let mean = heights.iter().sum::<f64>() / num as f64;
+        let median = match num % 2 {
+            0 => (heights[num / 2 - 1] + heights[num / 2]) / 2.0,
+            _ => heights[num / 2],
+        };
+        // shortest interval containing 95% of the heights
+        let window = ((0.95 * num as f64).ceil() as usize).max(1);
+        let (hpd_lower, hpd_upper) = (0..=num - window)
+            .map(|start| (heights[start], heights[start + window - 1]))
+            .min_by(|x, y| (x.1 - x.0).total_cmp(&(y.1 - y.0)))
+            .unwrap();
+        HeightSummary {
+            mean,
+            median,
+            hpd_lower,
+            hpd_upper,
+        }
+    }
+}
+
+/// A trait describing selection of maximum clade credibility (MCC) trees from a posterior sample of rooted trees on the same taxa.
+///
+/// The height of a node is its distance from the tip furthest from the root, where missing edge weights are counted as zero.
+pub trait MaximumCladeCredibility: Consensus
+where
+    <Self as RootedTree>::Node: RootedMetaNode + RootedWeightedNode + RootedZetaNode,
+    TreeNodeID<Self>: From<usize>,
+{
+    /// Returns the index of the tree with the greatest credibility, where the credibility of a clade is its frequency among trees. The first such tree is returned in case of ties.
+    fn mcc_index(trees: &[Self], credibility: CladeCredibility) -> Result<usize, ConsensusError> {
+        let clade_counts = CladeCounts::new(trees)?;
+        Ok(max_credibility_index(trees, &clade_counts, credibility))
+    }
+
+    /// Returns the MCC tree, with each node annotated with the mean height of its clade over the trees containing the clade as its zeta value and edge weights set to differences of mean heights. Also returns summaries of the heights of each clade.
+    #[allow(clippy::type_complexity)]
+    fn mcc_tree(
+        trees: &[Self],
+        credibility: CladeCredibility,
+    ) -> Result<(Self, Vec<(TreeNodeID<Self>, HeightSummary)>), ConsensusError> {
+        let clade_counts = CladeCounts::new(trees)?;
+        let mut mcc_tree = trees[max_credibility_index(trees, &clade_counts, credibility)].clone();
+        let num_taxa = clade_counts.taxa.len();
+        let mut node_clades = clade_counts.tree_clades(&mcc_tree);
+        node_clades.push((mcc_tree.get_root_id(), BitVec::from_ones(num_taxa)));
+        let mut clade_heights: HashMap<BitVec, Vec<f64>> = node_clades
+            .iter()
+            .map(|(_, bp)| (bp.clone(), vec![]))
+            .collect();
+        for tree in trees {
+            let clusters = node_clusters(tree, &clade_counts.taxa_map, num_taxa);
+            let mut depths: HashMap<TreeNodeID<Self>, f64> = vec![].into_iter().collect();
+            for (n_id, _) in clusters.iter().rev() {
+                let depth = match tree.get_node_parent_id(*n_id) {
+                    Some(p_id) => {
+                        depths[&p_id]
+                            + tree
+                                .get_node(*n_id)
+                                .unwrap()
+                                .get_weight()
+                                .and_then(|w| w.to_f64())
+                                .unwrap_or(0.0)
+                    }
+                    None => 0.0,
+                };
+                depths.insert(*n_id, depth);
+            }
+            let max_depth = depths.values().copied().fold(0.0, f64::max);
+            let mut seen: HashSet<&BitVec> = vec![].into_iter().collect();
+            // the height of a clade is that of its most recent common ancestor, the highest node with the clade as its cluster
+            for (n_id, bp) in clusters.iter().rev() {
+                if let Some(heights) = clade_heights.get_mut(bp) {
+                    if seen.insert(bp) {
+                        heights.push(max_depth - depths[n_id]);
+                    }
+                }
+            }
+        }
+        let summaries = node_clades
+            .into_iter()
+            .map(|(n_id, bp)| (n_id, HeightSummary::new(clade_heights[&bp].clone())))
+            .collect_vec();
+        let mean_heights: HashMap<TreeNodeID<Self>, f64> =
+            summaries.iter().map(|(n_id, summary)| (*n_id, summary.mean)).collect();
+        for (n_id, mean_height) in mean_heights.iter() {
+            mcc_tree.set_node_zeta(*n_id, <TreeNodeZeta<Self> as NumCast>::from(*mean_height));
+            if let Some(p_id) = mcc_tree.get_node_parent_id(*n_id) {
+                let weight = <TreeNodeWeight<Self> as NumCast>::from(mean_heights[&p_id] - mean_height);
+                mcc_tree.get_node_mut(*n_id).unwrap().set_weight(weight);
+            }
+        }
+        Ok((mcc_tree, summaries))
+    }
+}
+
+/// Returns the index of the first tree with the greatest credibility given the clade counts of trees
+fn max_credibility_index<T>(trees: &[T], clade_counts: &CladeCounts<T>, credibility: CladeCredibility) -> usize
+where
+    T: RootedMetaTree + RootedWeightedTree + Clusters,
+    <T as RootedTree>::Node: RootedMetaNode + RootedWeightedNode,
+{
+    let num_trees = trees.len() as f64;
+    let tree_credibility = |tree: &T| {
+        let frequencies = clade_counts
+            .tree_clades(tree)
+            .into_iter()
+            .filter(|(_, bp)| bp.count_ones() > 1)
+            .map(|(_, bp)| clade_counts.counts[clade_counts.clade_idx[&bp]] as f64 / num_trees);
+        match credibility {
+            CladeCredibility::Product => frequencies.map(|f| f.ln()).sum::<f64>(),
+            CladeCredibility::Sum => frequencies.sum::<f64>(),
+        }
+    };
+    let credibilities = trees.iter().map(tree_credibility).collect_vec();
+    (0..trees.len())
+        .rev()
+        .max_by(|x, y| credibilities[*x].total_cmp(&credibilities[*y]))
+        .unwrap()
+}
+
+/// A trait describing construction of Adams consensus trees from a collection of rooted trees on the same taxa.
+///
+/// The Adams consensus tree preserves nesting shared by all trees even where clades differ, by recursively splitting each set of taxa into the blocks of the common refinement of the partitions induced by its most recent common ancestor in every tree.
+pub trait AdamsConsensus: Consensus
+where
+    <Self as RootedTree>::Node: RootedMetaNode + RootedWeightedNode + RootedZetaNode,
+    TreeNodeID<Self>: From<usize>,
+{
+    /// Returns the Adams consensus tree of trees.
+    fn adams_consensus(trees: &[Self]) -> Result<Self, ConsensusError> {
+        let clade_counts = CladeCounts::new(trees)?;
+        let num_taxa = clade_counts.taxa.len();
+        let tree_clusters = trees
+            .iter()
+            .map(|tree| {
+                node_clusters(tree, &clade_counts.taxa_map, num_taxa)
+                    .into_iter()
+                    .collect::<HashMap<_, _>>()
+            })
+            .collect_vec();
+        let mut clusters: Vec<BitVec> = vec![];
+        let mut stack = vec![(0..num_taxa).collect_vec()];
+        while let Some(subset) = stack.pop() {
+            let mut subset_bp = BitVec::from_zeros(num_taxa);
+            subset.iter().for_each(|t| subset_bp.flip_bit(*t));
+            // the children of the most recent common ancestor of subset in each tree, lowest first
+            let lca_children = trees
+                .iter()
+                .zip(tree_clusters.iter())
+                .map(|(tree, clusters)| {
+                    let mut lca_id = tree.get_root_id();
+                    while let Some(child_id) = tree.get_node_children_ids(lca_id).find(|c_id| {
+                        clusters[c_id].mask_and(&subset_bp).unwrap().count_ones() == subset_bp.count_ones()
+                    }) {
+                        lca_id = child_id;
+                    }
+                    tree.get_node_children_ids(lca_id)
+                        .map(|c_id| &clusters[&c_id])
+                        .collect_vec()
+                })
+                .collect_vec();
+            let blocks = subset.iter().into_group_map_by(|t| {
+                lca_children
+                    .iter()
+                    .map(|children| {
+                        children
+                            .iter()
+                            .position(|bp| bp.is_bit_set(**t).unwrap())
+                    })
+                    .collect_vec()
+            });
+            for block in blocks.into_values().filter(|block| block.len() > 1) {
+                let block = block.into_iter().copied().collect_vec();
+                let mut bp = BitVec::from_zeros(num_taxa);
+                block.iter().for_each(|t| bp.flip_bit(*t));
+                clusters.push(bp);
+                stack.push(block);
+            }
+        }
+        let (mut tree, node_clusters) = clade_counts.build_tree(&clusters.iter().collect_vec());
+        for (n_id, bp) in node_clusters.into_iter().filter(|(_, bp)| bp.count_ones() > 1) {
+            let frequency = clade_counts.clade_idx.get(&bp).map_or(0, |idx| clade_counts.counts[*idx]) as f64
+                / trees.len() as f64;
+            tree.set_node_zeta(n_id, <TreeNodeZeta<Self> as NumCast>::from(frequency));
+        }
+        Ok(tree)
+    }
+}
+
+/// Selects the distance minimised by a median tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MedianMetric {
+    /// Robinson Foulds distance
+    #[default]
+    RobinsonFoulds,
+    /// Cluster Affinity cost from the median tree to each tree
+    ClusterAffinity,
+    /// Cophenetic distance with the given norm, using zeta values of the trees
+    Cophenetic(u32),
+}
+
+/// A trait describing selection of median trees from a collection of rooted trees on the same taxa.
+pub trait MedianTree: Consensus + RobinsonFoulds + ClusterAffinity + CopheneticDistance
+where
+    <Self as RootedTree>::Node: RootedMetaNode + RootedWeightedNode + RootedZetaNode,
+    TreeNodeID<Self>: From<usize>,
+    TreeNodeZeta<Self>: NodeWeight,
+{
+    /// Returns the input tree with the least total distance to all trees. The first such tree is returned in case of ties.
+    fn median_tree(trees: &[Self], metric: MedianMetric) -> Result<&Self, ConsensusError> {
+        CladeCounts::new(trees)?;
+        if let MedianMetric::Cophenetic(_) = metric {
+            if trees.iter().any(|tree| !tree.is_all_zeta_set()) {
+                return Err(ConsensusError::ZetaNotSet);
+            }
+        }
+        let distance = |x: &Self, y: &Self| match metric {
+            MedianMetric::RobinsonFoulds => x.rfs(y) as f64,
+            MedianMetric::ClusterAffinity => x.ca(y) as f64,
+            MedianMetric::Cophenetic(norm) => x.cophen_dist(y, norm).to_f64().unwrap_or(f64::INFINITY),
+        };
+        let total_distances = trees
+            .iter()
+            .map(|x| trees.iter().map(|y| distance(x, y)).sum::<f64>())
+            .collect_vec();
+        Ok(&trees[(0..trees.len())
+            .min_by(|x, y| total_distances[*x].total_cmp(&total_distances[*y]))
+            .unwrap()])
+    }
+}
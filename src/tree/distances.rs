@@ -76,6 +76,16 @@ where
     ) -> TreeNodeWeight<Self>;
 }
 
+/// Selects whether trees are compared by their rooted clusters or unrooted splits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SplitMode {
+    /// Compare clusters of the rooted trees
+    Rooted,
+    /// Compare splits of the trees ignoring the root
+    #[default]
+    Unrooted,
+}
+
 /// A trait describing naive computation of Robinson Foulds distance
 pub trait RobinsonFoulds
 where
@@ -162,6 +172,305 @@ where
         
         dist / 2
     }
+
+    /// Returns Robinson Foulds distance between tree and self as the number of nontrivial splits (clusters in rooted mode) present in exactly one of the trees.
+    fn rf(&self, tree: &Self, mode: SplitMode) -> usize {
+        let taxa_map = leaf_taxa_index(&[self, tree]);
+        let self_splits = tree_splits(self, &taxa_map, mode);
+        let tree_splits = tree_splits(tree, &taxa_map, mode);
+        self_splits.symmetric_difference(&tree_splits).count()
+    }
+
+    /// Returns Robinson Foulds distance between tree and self normalized by the largest possible distance between two trees on n taxa, which is 2(n-3) for unrooted splits and 2(n-2) for rooted clusters of binary trees. Returns 0 if there are too few taxa for a nontrivial split.
+    fn nrf(&self, tree: &Self, mode: SplitMode) -> f64 {
+        let taxa_map = leaf_taxa_index(&[self, tree]);
+        let self_splits = tree_splits(self, &taxa_map, mode);
+        let tree_splits = tree_splits(tree, &taxa_map, mode);
+        let max_dist = match mode {
+            SplitMode::Rooted => 2 * taxa_map.len().saturating_sub(2),
+            SplitMode::Unrooted => 2 * taxa_map.len().saturating_sub(3),
+        };
+        match max_dist {
+            0 => 0.0,
+            _ => self_splits.symmetric_difference(&tree_splits).count() as f64 / max_dist as f64,
+        }
+    }
+
+    /// Returns Robinson Foulds distance between tree and self normalized by the total number of nontrivial splits (clusters in rooted mode) in both trees. This agrees with `nrf` for binary trees and is larger when either tree is unresolved. Returns 0 if neither tree has a nontrivial split.
+    fn nrf_splits(&self, tree: &Self, mode: SplitMode) -> f64 {
+        let taxa_map = leaf_taxa_index(&[self, tree]);
+        let self_splits = tree_splits(self, &taxa_map, mode);
+        let tree_splits = tree_splits(tree, &taxa_map, mode);
+        let max_dist = self_splits.len() + tree_splits.len();
+        match max_dist {
+            0 => 0.0,
+            _ => self_splits.symmetric_difference(&tree_splits).count() as f64 / max_dist as f64,
+        }
+    }
+}
+
+/// A trait describing computation of generalized Robinson Foulds distances, where splits of two trees are matched by similarity rather than identity.
+///
+/// Each distance finds an optimal one-to-one matching between the nontrivial splits of both trees using the Hungarian algorithm, where unmatched splits are paired with an empty split. In rooted mode clusters are compared as splits against an additional root taxa. All methods take \Theta(k^3) time for k splits.
+pub trait GeneralizedRobinsonFoulds
+where
+    Self: RootedTree + RootedMetaTree + Clusters,
+    <Self as RootedTree>::Node: RootedMetaNode,
+{
+    /// Returns the matching split distance between tree and self. The cost of matching two splits is the minimum number of taxa that must be moved to turn one into the other.
+    fn matching_split_distance(&self, tree: &Self, mode: SplitMode) -> usize {
+        let (self_splits, tree_splits, num_bits) = matched_splits(self, tree, mode);
+        let cost = self_splits
+            .iter()
+            .map(|x| {
+                tree_splits
+                    .iter()
+                    .map(|y| {
+                        let mut diff = x.clone();
+                        let _ = diff.apply_mask_xor(y);
+                        let diff = diff.count_ones() as usize;
+                        diff.min(num_bits - diff) as f64
+                    })
+                    .collect_vec()
+            })
+            .collect_vec();
+        min_cost_matching(&cost).round() as usize
+    }
+
+    /// Returns the clustering information distance between tree and self, in bits. This is the total clustering entropy of both trees less twice the mutual clustering information of an optimal matching of splits.
+    fn clustering_info_distance(&self, tree: &Self, mode: SplitMode) -> f64 {
+        let (self_splits, tree_splits, num_bits) = matched_splits(self, tree, mode);
+        let n = num_bits as f64;
+        let entropy = |x: &BitVec| {
+            let p = x.count_ones() as f64 / n;
+            [p, 1.0 - p]
+                .into_iter()
+                .filter(|p| *p > 0.0)
+                .map(|p| -p * p.log2())
+                .sum::<f64>()
+        };
+        let total_entropy: f64 = self_splits.iter().chain(tree_splits.iter()).map(entropy).sum();
+        let cost = self_splits
+            .iter()
+            .map(|x| {
+                tree_splits
+                    .iter()
+                    .map(|y| {
+                        let (a_1, a_2, common) = split_overlap(x, y);
+                        let counts = [
+                            (common, a_1, a_2),
+                            (a_1 - common, a_1, num_bits - a_2),
+                            (a_2 - common, num_bits - a_1, a_2),
+                            (num_bits + common - a_1 - a_2, num_bits - a_1, num_bits - a_2),
+                        ];
+                        -counts
+                            .into_iter()
+                            .filter(|(xy, _, _)| *xy > 0)
+                            .map(|(xy, x, y)| {
+                                let p_xy = xy as f64 / n;
+                                p_xy * (p_xy * n * n / (x as f64 * y as f64)).log2()
+                            })
+                            .sum::<f64>()
+                    })
+                    .collect_vec()
+            })
+            .collect_vec();
+        let mutual_info = -min_cost_matching(&cost);
+        (total_entropy - 2.0 * mutual_info).max(0.0)
+    }
+
+    /// Returns the shared phylogenetic information between tree and self, in bits. The information shared by two compatible splits is the sum of their information contents less the information content of both splits together, and zero if they are incompatible.
+    fn shared_phylo_info(&self, tree: &Self, mode: SplitMode) -> f64 {
+        let (self_splits, tree_splits, num_bits) = matched_splits(self, tree, mode);
+        let info = PhyloInfo::new(num_bits);
+        let cost = self_splits
+            .iter()
+            .map(|x| {
+                tree_splits
+                    .iter()
+                    .map(|y| -info.shared_split_info(x, y))
+                    .collect_vec()
+            })
+            .collect_vec();
+        -min_cost_matching(&cost)
+    }
+
+    /// Returns the different phylogenetic information between tree and self, in bits. This is the total information content of splits in both trees less twice the shared phylogenetic information.
+    fn phylo_info_distance(&self, tree: &Self, mode: SplitMode) -> f64 {
+        let (self_splits, tree_splits, num_bits) = matched_splits(self, tree, mode);
+        let info = PhyloInfo::new(num_bits);
+        let total_info: f64 = self_splits
+            .iter()
+            .chain(tree_splits.iter())
+            .map(|x| info.split_info(x.count_ones() as usize))
+            .sum();
+        (total_info - 2.0 * self.shared_phylo_info(tree, mode)).max(0.0)
+    }
+}
+
+/// Returns the sizes of the first side of two splits along with the size of their intersection.
+fn split_overlap(x: &BitVec, y: &BitVec) -> (usize, usize, usize) {
+    let mut common = x.clone();
+    let _ = common.apply_mask_and(y);
+    (
+        x.count_ones() as usize,
+        y.count_ones() as usize,
+        common.count_ones() as usize,
+    )
+}
+
+/// Returns the nontrivial splits of two trees padded with empty splits to equal length, along with the number of bits in a split.
+fn matched_splits<T>(t1: &T, t2: &T, mode: SplitMode) -> (Vec<BitVec>, Vec<BitVec>, usize)
+where
+    T: RootedMetaTree + Clusters,
+    <T as RootedTree>::Node: RootedMetaNode,
+{
+    let taxa_map = leaf_taxa_index(&[t1, t2]);
+    let num_bits = match mode {
+        SplitMode::Rooted => taxa_map.len() + 1,
+        SplitMode::Unrooted => taxa_map.len(),
+    };
+    let mut t1_splits = tree_splits(t1, &taxa_map, mode).into_iter().collect_vec();
+    let mut t2_splits = tree_splits(t2, &taxa_map, mode).into_iter().collect_vec();
+    let num_splits = t1_splits.len().max(t2_splits.len());
+    t1_splits.resize(num_splits, BitVec::from_zeros(num_bits));
+    t2_splits.resize(num_splits, BitVec::from_zeros(num_bits));
+    (t1_splits, t2_splits, num_bits)
+}
+
+/// Information content of splits over a fixed number of taxa, in bits.
+struct PhyloInfo {
+    /// Number of taxa
+    num_taxa: usize,
+    /// Base 2 logarithm of double factorials of odd numbers, indexed by (k+1)/2 for k!!
+    log_double_factorial: Vec<f64>,
+}
+
+impl PhyloInfo {
+    fn new(num_taxa: usize) -> Self {
+        let mut log_double_factorial = vec![0.0; num_taxa + 1];
+        for idx in 1..=num_taxa {
+            log_double_factorial[idx] = log_double_factorial[idx - 1] + ((2 * idx - 1) as f64).log2();
+        }
+        PhyloInfo {
+            num_taxa,
+            log_double_factorial,
+        }
+    }
+
+    /// Returns log2 of the number of unrooted binary trees with a given number of leaves, or rooted binary trees with one fewer leaf
+    fn log_num_trees(&self, num_leaves: usize) -> f64 {
+        // (2n-5)!!
+        self.log_double_factorial[num_leaves.saturating_sub(2)]
+    }
+
+    /// Returns the information content of a split with a side of the given size
+    fn split_info(&self, size: usize) -> f64 {
+        if size == 0 || size == self.num_taxa {
+            return 0.0;
+        }
+        self.log_num_trees(self.num_taxa)
+            - self.log_num_trees(size + 1)
+            - self.log_num_trees(self.num_taxa - size + 1)
+    }
+
+    /// Returns the sum of the information contents of two splits less the information content of both splits together, or zero if the splits are incompatible
+    fn shared_split_info(&self, x: &BitVec, y: &BitVec) -> f64 {
+        let n = self.num_taxa;
+        let (a_1, a_2, common) = split_overlap(x, y);
+        if a_1 == 0 || a_1 == n || a_2 == 0 || a_2 == n {
+            return 0.0;
+        }
+        // (smaller side, larger side) of a pair of nested sides, if any
+        let nested = [
+            (a_1 == common).then_some((a_1, a_2)),
+            (a_2 == common).then_some((a_2, a_1)),
+            (common == 0).then_some((a_1, n - a_2)),
+            (n + common == a_1 + a_2).then_some((n - a_1, a_2)),
+        ]
+        .into_iter()
+        .flatten()
+        .next();
+        let Some((inner, outer)) = nested else {
+            return 0.0;
+        };
+        // trees containing both splits are formed by a tree on the inner side, a tree on the complement
+        // of the outer side, and a tree connecting them to the remaining taxa
+        let joint_info = self.log_num_trees(n)
+            - self.log_num_trees(inner + 1)
+            - self.log_num_trees(n - outer + 1)
+            - self.log_num_trees(outer - inner + 2);
+        self.split_info(a_1) + self.split_info(a_2) - joint_info
+    }
+}
+
+/// Returns the minimum total cost of a perfect matching in a square cost matrix using the Hungarian algorithm in \Theta(n^3) time.
+pub(crate) fn min_cost_matching(cost: &[Vec<f64>]) -> f64 {
+    min_cost_assignment(cost).0
+}
+
+/// Returns the minimum total cost of a perfect matching in a square cost matrix along with the column assigned to each row, using the Hungarian algorithm in \Theta(n^3) time.
+pub(crate) fn min_cost_assignment(cost: &[Vec<f64>]) -> (f64, Vec<usize>) {
+    let n = cost.len();
+    if n == 0 {
+        return (0.0, vec![]);
+    }
+    // potentials and matching use 1-based indexing with column 0 as a sentinel
+    let mut u = vec![0.0; n + 1];
+    let mut v = vec![0.0; n + 1];
+    let mut row_of = vec![0; n + 1];
+    let mut way = vec![0; n + 1];
+    for row in 1..=n {
+        row_of[0] = row;
+        let mut col_0 = 0;
+        let mut min_v = vec![f64::INFINITY; n + 1];
+        let mut used = vec![false; n + 1];
+        loop {
+            used[col_0] = true;
+            let row_0 = row_of[col_0];
+            let mut delta = f64::INFINITY;
+            let mut col_1 = 0;
+            for col in 1..=n {
+                if !used[col] {
+                    let cur = cost[row_0 - 1][col - 1] - u[row_0] - v[col];
+                    if cur < min_v[col] {
+                        min_v[col] = cur;
+                        way[col] = col_0;
+                    }
+                    if min_v[col] < delta {
+                        delta = min_v[col];
+                        col_1 = col;
+                    }
+                }
+            }
+            for col in 0..=n {
+                if used[col] {
+                    u[row_of[col]] += delta;
+                    v[col] -= delta;
+                } else {
+                    min_v[col] -= delta;
+                }
+            }
+            col_0 = col_1;
+            if row_of[col_0] == 0 {
+                break;
+            }
+        }
+        loop {
+            let col_1 = way[col_0];
+            row_of[col_0] = row_of[col_1];
+            col_0 = col_1;
+            if col_0 == 0 {
+                break;
+            }
+        }
+    }
+    let mut assignment = vec![0; n];
+    for col in 1..=n {
+        assignment[row_of[col] - 1] = col - 1;
+    }
+    let total = (0..n).map(|row| cost[row][assignment[row]]).sum();
+    (total, assignment)
 }
 
 /// A trait describing naive computation of Cluster Matching distance
@@ -244,8 +553,1338 @@ where
     Self: RootedWeightedTree + RootedMetaTree + Clusters,
     <Self as RootedTree>::Node: RootedWeightedNode + RootedMetaNode,
 {
-    /// Returns weighted Robinson Foulds distance between tree and self.
-    fn wrfs(&self, tree: &Self) -> TreeNodeWeight<Self>;
+    /// Returns weighted Robinson Foulds distance between tree and self. This is the sum of absolute differences in edge weights over the union of splits of both trees, where a split missing from a tree has weight zero. Missing edge weights are counted as zero.
+    fn wrfs(&self, tree: &Self) -> TreeNodeWeight<Self> {
+        let taxa_map = leaf_taxa_index(&[self, tree]);
+        let self_splits = weighted_splits(self, &taxa_map);
+        let tree_splits = weighted_splits(tree, &taxa_map);
+        let self_dist: TreeNodeWeight<Self> = self_splits
+            .iter()
+            .map(|(split, w)| {
+                (*w - tree_splits
+                    .get(split)
+                    .copied()
+                    .unwrap_or(TreeNodeWeight::<Self>::zero()))
+                .abs()
+            })
+            .sum();
+        let tree_dist: TreeNodeWeight<Self> = tree_splits
+            .iter()
+            .filter(|(split, _)| !self_splits.contains_key(*split))
+            .map(|(_, w)| w.abs())
+            .sum();
+        self_dist + tree_dist
+    }
+}
+
+/// Policy for handling edges without a weight in branch length based distances
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MissingWeightPolicy {
+    /// Missing edge weights are counted as zero
+    #[default]
+    Zero,
+    /// Missing edge weights result in an error
+    Error,
+}
+
+/// A trait describing computation of the Kuhner-Felsenstein branch score distance
+pub trait BranchScore
+where
+    Self: RootedWeightedTree + RootedMetaTree + Clusters,
+    <Self as RootedTree>::Node: RootedWeightedNode + RootedMetaNode,
+{
+    /// Returns the branch score distance between tree and self. This is the euclidean norm of the differences in edge weights over the union of splits of both trees, where a split missing from a tree has weight zero.
+    fn branch_score(
+        &self,
+        tree: &Self,
+        policy: MissingWeightPolicy,
+    ) -> Result<TreeNodeWeight<Self>, DistanceError> {
+        if policy == MissingWeightPolicy::Error {
+            for t in [self, tree] {
+                if let Some(node) = t
+                    .get_nodes()
+                    .find(|node| node.get_parent().is_some() && node.get_weight().is_none())
+                {
+                    return Err(DistanceError::MissingEdgeWeight {
+                        parent: node.get_parent().unwrap().to_string(),
+                        child: node.get_id().to_string(),
+                    });
+                }
+            }
+        }
+        let taxa_map = leaf_taxa_index(&[self, tree]);
+        let self_splits = weighted_splits(self, &taxa_map);
+        let tree_splits = weighted_splits(tree, &taxa_map);
+        let self_dist: TreeNodeWeight<Self> = self_splits
+            .iter()
+            .map(|(split, w)| {
+                let diff = *w - tree_splits
+                    .get(split)
+                    .copied()
+                    .unwrap_or(TreeNodeWeight::<Self>::zero());
+                diff * diff
+            })
+            .sum();
+        let tree_dist: TreeNodeWeight<Self> = tree_splits
+            .iter()
+            .filter(|(split, _)| !self_splits.contains_key(*split))
+            .map(|(_, w)| *w * *w)
+            .sum();
+        Ok((self_dist + tree_dist).sqrt())
+    }
+}
+
+/// Returns an index of the leaf taxa of a set of trees.
+pub(crate) fn leaf_taxa_index<'a, T>(trees: &[&'a T]) -> HashMap<&'a TreeNodeMeta<T>, usize>
+where
+    T: RootedMetaTree,
+    <T as RootedTree>::Node: RootedMetaNode,
+{
+    trees
+        .iter()
+        .flat_map(|tree| {
+            tree.get_leaf_ids()
+                .filter_map(|id| tree.get_node_taxa(id))
+                .collect_vec()
+        })
+        .unique()
+        .enumerate()
+        .map(|(idx, taxa)| (taxa, idx))
+        .collect()
+}
+
+/// Counts of quartets by their resolution in two trees
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct QuartetReport {
+    /// Total number of quartets
+    pub total: u64,
+    /// Quartets resolved identically in both trees
+    pub shared: u64,
+    /// Quartets resolved differently in both trees
+    pub different: u64,
+    /// Quartets resolved in the first tree only
+    pub resolved_first: u64,
+    /// Quartets resolved in the second tree only
+    pub resolved_second: u64,
+    /// Quartets unresolved in both trees
+    pub unresolved: u64,
+}
+
+impl QuartetReport {
+    /// Returns the number of quartets that are not resolved identically (or unresolved) in both trees
+    pub fn distance(&self) -> u64 {
+        self.total - self.shared - self.unresolved
+    }
+}
+
+/// A trait describing computation of quartet distance between two trees on the same leaf taxa.
+///
+/// Quartets are counted by their butterflies using claims of internal nodes, where a butterfly ab|cd is claimed by the node at which a and b branch apart. Shared and different butterflies are counted over all pairs of internal nodes of both trees from the overlaps of their incident subtrees, taking O(n^2 min(d_1, d_2)) time and O(n d_1) space for trees with maximum degrees d_1 and d_2, which is O(n^2) time for trees of bounded degree. Multifurcating trees are supported and quartets not resolved in a tree are reported separately.
+pub trait QuartetDistance
+where
+    Self: RootedMetaTree + Clusters,
+    <Self as RootedTree>::Node: RootedMetaNode,
+{
+    /// Returns counts of quartets by their resolution in self and tree.
+    fn quartet_report(&self, tree: &Self) -> Result<QuartetReport, DistanceError> {
+        let taxa_map = leaf_taxa_index(&[self, tree]);
+        let t1 = CompactTree::new(self, &taxa_map);
+        let t2 = CompactTree::new(tree, &taxa_map);
+        let num_taxa = taxa_map.len();
+        if t1.num_leaves != num_taxa || t2.num_leaves != num_taxa {
+            return Err(DistanceError::TaxaMismatch);
+        }
+        let n = num_taxa as i128;
+        let total = n * (n - 1) * (n - 2) * (n - 3) / 24;
+        if num_taxa < 4 {
+            return Ok(QuartetReport {
+                total: total.max(0) as u64,
+                ..Default::default()
+            });
+        }
+        let butterflies_1 = t1.num_butterflies();
+        let butterflies_2 = t2.num_butterflies();
+
+        let mut shared_claims = 0_i128;
+        let mut different_claims = 0_i128;
+        let mut in_cluster = vec![false; num_taxa];
+        let mut overlap: Vec<Vec<i64>> = vec![];
+        let mut claims = ClaimMatrix::default();
+        // nodes of the second tree that claim butterflies, with the sizes of their incident subtrees
+        let claims_2 = (0..t2.len())
+            .filter(|u_2| t2.degree(*u_2) > 2)
+            .map(|u_2| (u_2, t2.subtree_sizes(u_2)))
+            .collect_vec();
+        for u in (0..t1.len()).filter(|u| t1.degree(*u) > 2) {
+            // overlaps of each subtree below u with each cluster of the second tree
+            overlap.clear();
+            for c in t1.children[u].iter() {
+                in_cluster.iter_mut().for_each(|x| *x = false);
+                t1.mark_cluster(*c, &mut in_cluster);
+                overlap.push(t2.cluster_overlaps(&in_cluster));
+            }
+            let subtree_sizes = t1.subtree_sizes(u);
+            for (u_2, subtree_sizes_2) in claims_2.iter() {
+                claims.reset(t1.degree(u), subtree_sizes_2.len());
+                for (i, row) in overlap.iter().enumerate() {
+                    for (j, c) in t2.children[*u_2].iter().enumerate() {
+                        claims.set(i, j, row[*c]);
+                    }
+                    if t2.parent[*u_2].is_some() {
+                        let num_children = t2.children[*u_2].len();
+                        let rest = subtree_sizes[i] - (0..num_children).map(|j| claims.get(i, j)).sum::<i64>();
+                        claims.set(i, num_children, rest);
+                    }
+                }
+                if t1.parent[u].is_some() {
+                    let num_children = t1.children[u].len();
+                    for (j, size) in subtree_sizes_2.iter().enumerate() {
+                        let rest = size - (0..num_children).map(|i| claims.get(i, j)).sum::<i64>();
+                        claims.set(num_children, j, rest);
+                    }
+                }
+                let (shared, different) = claims.counts();
+                shared_claims += shared as i128;
+                different_claims += different as i128;
+            }
+        }
+        let shared = shared_claims / 2;
+        let different = different_claims / 4;
+        Ok(QuartetReport {
+            total: total as u64,
+            shared: shared as u64,
+            different: different as u64,
+            resolved_first: (butterflies_1 - shared - different) as u64,
+            resolved_second: (butterflies_2 - shared - different) as u64,
+            unresolved: (total - butterflies_1 - butterflies_2 + shared + different) as u64,
+        })
+    }
+
+    /// Returns the number of quartets that are not resolved identically (or unresolved) in self and tree.
+    fn quartet_distance(&self, tree: &Self) -> Result<u64, DistanceError> {
+        Ok(self.quartet_report(tree)?.distance())
+    }
+}
+
+/// Counts of rooted triplets by their resolution in two trees
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TripletReport {
+    /// Total number of triplets
+    pub total: u64,
+    /// Triplets resolved identically in both trees
+    pub shared: u64,
+    /// Triplets resolved differently in both trees
+    pub different: u64,
+    /// Triplets resolved in the first tree only
+    pub resolved_first: u64,
+    /// Triplets resolved in the second tree only
+    pub resolved_second: u64,
+    /// Triplets unresolved in both trees
+    pub unresolved: u64,
+}
+
+impl TripletReport {
+    /// Returns the number of triplets that are not resolved identically (or unresolved) in both trees
+    pub fn distance(&self) -> u64 {
+        self.total - self.shared - self.unresolved
+    }
+}
+
+/// A trait describing computation of rooted triplet distance between two trees on the same leaf taxa.
+///
+/// A resolved triplet ab|c is claimed by the lowest common ancestor of a, b and c, where a and b lie below the same child and c below another. Nodes of the first tree are visited along heavy paths with the leaves below the current heavy child marked in the second tree, so that each leaf is marked O(log n) times at a cost of O(log^2 n) each, and the remaining triplets claimed by a node are counted over the second tree restricted to the leaves below its light children. Triplets resolved alike and triplets unresolved in both trees are counted this way in O(n log^3 n) time and O(n) space, and the other counts follow from the number of resolved triplets in each tree. Multifurcating trees are supported and triplets not resolved in a tree are reported separately.
+pub trait TripletDistance
+where
+    Self: RootedMetaTree + Clusters + EulerWalk,
+    <Self as RootedTree>::Node: RootedMetaNode,
+{
+    /// Returns counts of triplets by their resolution in self and tree.
+    fn triplet_report(&self, tree: &Self) -> Result<TripletReport, DistanceError> {
+        let taxa_map = leaf_taxa_index(&[self, tree]);
+        let t1 = CompactTree::new(self, &taxa_map);
+        let t2 = CompactTree::new(tree, &taxa_map);
+        let num_taxa = taxa_map.len();
+        if t1.num_leaves != num_taxa || t2.num_leaves != num_taxa {
+            return Err(DistanceError::TaxaMismatch);
+        }
+        let n = num_taxa as i128;
+        let total = (n * (n - 1) * (n - 2) / 6).max(0);
+        let resolved_1 = t1.num_resolved_triplets();
+        let resolved_2 = t2.num_resolved_triplets();
+        if num_taxa < 3 {
+            return Ok(TripletReport {
+                total: total as u64,
+                ..Default::default()
+            });
+        }
+        let sweep = TripletSweep::new(&t1, &t2);
+        let (shared, unresolved) = (sweep.shared, sweep.unresolved);
+        let resolved_first = total - resolved_2 - unresolved;
+        let resolved_second = total - resolved_1 - unresolved;
+        Ok(TripletReport {
+            total: total as u64,
+            shared: shared as u64,
+            different: (resolved_1 - shared - resolved_first) as u64,
+            resolved_first: resolved_first as u64,
+            resolved_second: resolved_second as u64,
+            unresolved: unresolved as u64,
+        })
+    }
+
+    /// Returns counts of triplets by their resolution in self and tree by enumerating all triplets. This takes O(n^3) time when constant-time LCA queries are precomputed for both trees.
+    fn triplet_report_naive(&self, tree: &Self) -> Result<TripletReport, DistanceError> {
+        let self_taxa = self
+            .get_leaf_ids()
+            .filter_map(|id| self.get_node_taxa(id))
+            .collect::<HashSet<_>>();
+        let tree_taxa = tree
+            .get_leaf_ids()
+            .filter_map(|id| tree.get_node_taxa(id))
+            .collect::<HashSet<_>>();
+        if self_taxa != tree_taxa {
+            return Err(DistanceError::TaxaMismatch);
+        }
+        // index of the pair of leaves separated from the third leaf, if any
+        let resolution = |t: &Self, ids: &[TreeNodeID<Self>]| {
+            let lcas = [
+                t.get_lca_id(&[ids[0], ids[1]]),
+                t.get_lca_id(&[ids[0], ids[2]]),
+                t.get_lca_id(&[ids[1], ids[2]]),
+            ];
+            (0..3).find(|x| lcas.iter().filter(|y| **y != lcas[*x]).count() == 2)
+        };
+        let mut report = TripletReport::default();
+        for triplet in self_taxa.iter().combinations(3) {
+            let self_ids = triplet
+                .iter()
+                .map(|x| self.get_taxa_node_id(x).unwrap())
+                .collect_vec();
+            let tree_ids = triplet
+                .iter()
+                .map(|x| tree.get_taxa_node_id(x).unwrap())
+                .collect_vec();
+            report.total += 1;
+            match (resolution(self, &self_ids), resolution(tree, &tree_ids)) {
+                (Some(x), Some(y)) if x == y => report.shared += 1,
+                (Some(_), Some(_)) => report.different += 1,
+                (Some(_), None) => report.resolved_first += 1,
+                (None, Some(_)) => report.resolved_second += 1,
+                (None, None) => report.unresolved += 1,
+            }
+        }
+        Ok(report)
+    }
+
+    /// Returns the number of triplets that are not resolved identically (or unresolved) in self and tree.
+    fn triplet_distance(&self, tree: &Self) -> Result<u64, DistanceError> {
+        Ok(self.triplet_report(tree)?.distance())
+    }
+}
+
+/// Overlaps between the subtrees incident on a pair of internal nodes, along with buffers used to count pairs of claims of the nodes.
+///
+/// Entry (i, j) of the matrix is the number of leaves in subtree i of the first node and subtree j of the second node. Counts of pairs of claims are unchanged by swapping the nodes, so the matrix is stored transposed when the first node has more subtrees, and counting takes O(rc min(r, c)) time for nodes with r and c subtrees.
+#[derive(Default)]
+struct ClaimMatrix {
+    transposed: bool,
+    rows: usize,
+    cols: usize,
+    m: Vec<i64>,
+    row_sums: Vec<i64>,
+    col_sums: Vec<i64>,
+    row_sq: Vec<i64>,
+    col_sq: Vec<i64>,
+    row_rem_sq: Vec<i64>,
+    col_rem_sq: Vec<i64>,
+    row_weighted: Vec<i64>,
+    col_weighted: Vec<i64>,
+    gram: Vec<i64>,
+}
+
+impl ClaimMatrix {
+    fn reset(&mut self, rows: usize, cols: usize) {
+        self.transposed = rows > cols;
+        let (rows, cols) = match self.transposed {
+            true => (cols, rows),
+            false => (rows, cols),
+        };
+        self.rows = rows;
+        self.cols = cols;
+        for (buf, len) in [
+            (&mut self.m, rows * cols),
+            (&mut self.row_sums, rows),
+            (&mut self.col_sums, cols),
+            (&mut self.row_sq, rows),
+            (&mut self.col_sq, cols),
+            (&mut self.row_rem_sq, cols),
+            (&mut self.col_rem_sq, rows),
+            (&mut self.row_weighted, cols),
+            (&mut self.col_weighted, rows),
+            (&mut self.gram, rows * rows),
+        ] {
+            buf.clear();
+            buf.resize(len, 0);
+        }
+    }
+
+    fn get(&self, i: usize, j: usize) -> i64 {
+        self.m[self.index(i, j)]
+    }
+
+    fn set(&mut self, i: usize, j: usize, value: i64) {
+        let idx = self.index(i, j);
+        self.m[idx] = value;
+    }
+
+    /// Returns the position of entry (i, j) in the stored matrix
+    fn index(&self, i: usize, j: usize) -> usize {
+        match self.transposed {
+            true => j * self.cols + i,
+            false => i * self.cols + j,
+        }
+    }
+
+    /// Returns entry (i, j) of the stored matrix
+    fn entry(&self, i: usize, j: usize) -> i64 {
+        self.m[i * self.cols + j]
+    }
+
+    /// Returns the number of pairs of claims whose grouped pairs of leaves are identical, and the number whose grouped pairs share exactly one leaf.
+    fn counts(&mut self) -> (i64, i64) {
+        let (rows, cols) = (self.rows, self.cols);
+        for i in 0..rows {
+            for j in 0..cols {
+                let x = self.entry(i, j);
+                self.row_sums[i] += x;
+                self.col_sums[j] += x;
+                self.row_sq[i] += x * x;
+                self.col_sq[j] += x * x;
+            }
+        }
+        for i in 0..rows {
+            for j in 0..cols {
+                let x = self.entry(i, j);
+                // sums of squared row sums with a column removed, and of squared column sums with a row removed
+                self.row_rem_sq[j] += (self.row_sums[i] - x).pow(2);
+                self.col_rem_sq[i] += (self.col_sums[j] - x).pow(2);
+                self.row_weighted[j] += x * (self.row_sums[i] - x);
+                self.col_weighted[i] += x * (self.col_sums[j] - x);
+            }
+            for k in 0..rows {
+                self.gram[i * rows + k] = (0..cols).map(|j| self.entry(i, j) * self.entry(k, j)).sum();
+            }
+        }
+        let total: i64 = self.row_sums.iter().sum();
+        let sq_total: i64 = self.row_sq.iter().sum();
+
+        let mut shared = 0;
+        let mut different = 0;
+        for k in 0..rows {
+            for l in 0..cols {
+                let m_kl = self.entry(k, l);
+                if m_kl == 0 {
+                    continue;
+                }
+                // leaves outside subtree k of the first node and subtree l of the second node
+                let rest = total - self.row_sums[k] - self.col_sums[l] + m_kl;
+                let rest_row_sq = self.row_rem_sq[l] - (self.row_sums[k] - m_kl).pow(2);
+                let rest_col_sq = self.col_rem_sq[k] - (self.col_sums[l] - m_kl).pow(2);
+                let rest_sq = sq_total - self.row_sq[k] - self.col_sq[l] + m_kl * m_kl;
+                let split_pairs = (rest * rest - rest_row_sq - rest_col_sq + rest_sq) / 2;
+                shared += m_kl * (m_kl - 1) / 2 * split_pairs;
+
+                let p = self.col_sums[l] - m_kl;
+                let q = self.row_sums[k] - m_kl;
+                let p_weighted = self.row_weighted[l] - m_kl * (self.row_sums[k] - m_kl);
+                let q_weighted = self.col_weighted[k] - m_kl * (self.col_sums[l] - m_kl);
+                let triple: i64 = (0..rows)
+                    .filter(|i| *i != k)
+                    .map(|i| self.entry(i, l) * (self.gram[i * rows + k] - self.entry(i, l) * m_kl))
+                    .sum();
+                different += m_kl * (rest * p * q - q * p_weighted - p * q_weighted + triple);
+            }
+        }
+        (shared, different)
+    }
+}
+
+/// A tree stored in post-order with leaves indexed by taxa
+pub(crate) struct CompactTree {
+    /// Parent of each node
+    pub(crate) parent: Vec<Option<usize>>,
+    /// Children of each node
+    pub(crate) children: Vec<Vec<usize>>,
+    /// Taxa index of each leaf
+    pub(crate) leaf_taxa: Vec<Option<usize>>,
+    /// Number of leaves in the cluster of each node
+    pub(crate) cluster_size: Vec<i64>,
+    /// Number of leaves in the tree
+    pub(crate) num_leaves: usize,
+}
+
+impl CompactTree {
+    pub(crate) fn new<T>(tree: &T, taxa_map: &HashMap<&TreeNodeMeta<T>, usize>) -> Self
+    where
+        T: RootedMetaTree + Clusters,
+        <T as RootedTree>::Node: RootedMetaNode,
+    {
+        let node_ids = tree.postord_ids(tree.get_root_id()).collect_vec();
+        let node_idx: HashMap<TreeNodeID<T>, usize> =
+            node_ids.iter().enumerate().map(|(idx, id)| (*id, idx)).collect();
+        let parent = node_ids
+            .iter()
+            .map(|id| tree.get_node_parent_id(*id).map(|p| node_idx[&p]))
+            .collect_vec();
+        let children = node_ids
+            .iter()
+            .map(|id| tree.get_node_children_ids(*id).map(|c| node_idx[&c]).collect_vec())
+            .collect_vec();
+        let leaf_taxa = node_ids
+            .iter()
+            .map(|id| match tree.is_leaf(*id) {
+                true => tree.get_node_taxa(*id).and_then(|t| taxa_map.get(t)).copied(),
+                false => None,
+            })
+            .collect_vec();
+        let mut cluster_size = vec![0; node_ids.len()];
+        for idx in 0..node_ids.len() {
+            cluster_size[idx] = match leaf_taxa[idx] {
+                Some(_) => 1,
+                None => children[idx].iter().map(|c| cluster_size[*c]).sum(),
+            };
+        }
+        let num_leaves = leaf_taxa.iter().flatten().unique().count();
+        CompactTree {
+            parent,
+            children,
+            leaf_taxa,
+            cluster_size,
+            num_leaves,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.parent.len()
+    }
+
+    /// Returns true if every internal node has exactly two children
+    pub(crate) fn is_binary(&self) -> bool {
+        self.children.iter().all(|c| c.is_empty() || c.len() == 2)
+    }
+
+    fn degree(&self, idx: usize) -> usize {
+        self.children[idx].len() + self.parent[idx].map_or(0, |_| 1)
+    }
+
+    /// Returns the number of leaves in each subtree incident on a node, with the subtree containing the parent last
+    fn subtree_sizes(&self, idx: usize) -> Vec<i64> {
+        let mut sizes = self.children[idx]
+            .iter()
+            .map(|c| self.cluster_size[*c])
+            .collect_vec();
+        if self.parent[idx].is_some() {
+            sizes.push(self.num_leaves as i64 - self.cluster_size[idx]);
+        }
+        sizes
+    }
+
+    /// Marks the taxa in the cluster of a node
+    fn mark_cluster(&self, idx: usize, in_cluster: &mut [bool]) {
+        let mut stack = vec![idx];
+        while let Some(n) = stack.pop() {
+            if let Some(t) = self.leaf_taxa[n] {
+                in_cluster[t] = true;
+            }
+            stack.extend(self.children[n].iter());
+        }
+    }
+
+    /// Returns the number of marked taxa in the cluster of each node
+    pub(crate) fn cluster_overlaps(&self, in_cluster: &[bool]) -> Vec<i64> {
+        let mut overlaps = vec![0; self.len()];
+        for idx in 0..self.len() {
+            overlaps[idx] = match self.leaf_taxa[idx] {
+                Some(t) => in_cluster[t] as i64,
+                None => self.children[idx].iter().map(|c| overlaps[*c]).sum(),
+            };
+        }
+        overlaps
+    }
+
+    /// Returns the number of resolved rooted triplets in the tree
+    fn num_resolved_triplets(&self) -> i128 {
+        (0..self.len())
+            .map(|idx| {
+                let sizes = self.children[idx]
+                    .iter()
+                    .map(|c| self.cluster_size[*c])
+                    .collect_vec();
+                let size: i64 = sizes.iter().sum();
+                sizes
+                    .iter()
+                    .map(|a| (a * (a - 1) / 2 * (size - a)) as i128)
+                    .sum::<i128>()
+            })
+            .sum()
+    }
+
+    /// Returns the number of butterflies (resolved quartets) in the tree
+    fn num_butterflies(&self) -> i128 {
+        let n = self.num_leaves as i64;
+        let claims: i128 = (0..self.len())
+            .filter(|idx| self.degree(*idx) > 2)
+            .map(|idx| {
+                let sizes = self.subtree_sizes(idx);
+                let sq_sum: i64 = sizes.iter().map(|x| x * x).sum();
+                sizes
+                    .iter()
+                    .map(|a| a * (a - 1) / 2 * (((n - a) * (n - a) - (sq_sum - a * a)) / 2))
+                    .sum::<i64>() as i128
+            })
+            .sum();
+        claims / 2
+    }
+}
+
+/// Constant time lowest common ancestor queries, by range minima over an Euler tour of a tree
+pub(crate) struct LcaIndex {
+    /// Pre-order index of each node
+    pub(crate) preorder: Vec<usize>,
+    /// Largest pre-order index below each node
+    last: Vec<usize>,
+    /// First position of each node in the Euler tour
+    first: Vec<usize>,
+    depth: Vec<usize>,
+    /// Shallowest node of the Euler tour in each range of length 2^i
+    sparse: Vec<Vec<usize>>,
+}
+
+impl LcaIndex {
+    pub(crate) fn new(tree: &CompactTree) -> Self {
+        let mut preorder = vec![0; tree.len()];
+        let mut last = vec![0; tree.len()];
+        let mut first = vec![0; tree.len()];
+        let mut depth = vec![0; tree.len()];
+        let mut tour = vec![];
+        let mut visited = 0;
+        let mut stack = vec![(tree.len() - 1, 0)];
+        while let Some((v, next)) = stack.pop() {
+            if next == 0 {
+                preorder[v] = visited;
+                first[v] = tour.len();
+                visited += 1;
+            }
+            tour.push(v);
+            match tree.children[v].get(next) {
+                Some(c) => {
+                    depth[*c] = depth[v] + 1;
+                    stack.push((v, next + 1));
+                    stack.push((*c, 0));
+                }
+                None => last[v] = visited - 1,
+            }
+        }
+        let mut sparse = vec![tour];
+        while 2 << (sparse.len() - 1) <= sparse[0].len() {
+            let half = 1 << (sparse.len() - 1);
+            let prev = sparse.last().unwrap();
+            let level = (0..prev.len() - half)
+                .map(|i| match depth[prev[i]] <= depth[prev[i + half]] {
+                    true => prev[i],
+                    false => prev[i + half],
+                })
+                .collect_vec();
+            sparse.push(level);
+        }
+        LcaIndex {
+            preorder,
+            last,
+            first,
+            depth,
+            sparse,
+        }
+    }
+
+    pub(crate) fn is_ancestor(&self, u: usize, v: usize) -> bool {
+        self.preorder[u] <= self.preorder[v] && self.preorder[v] <= self.last[u]
+    }
+
+    pub(crate) fn lca(&self, u: usize, v: usize) -> usize {
+        let (l, r) = match self.first[u] <= self.first[v] {
+            true => (self.first[u], self.first[v]),
+            false => (self.first[v], self.first[u]),
+        };
+        let level = (r - l + 1).ilog2() as usize;
+        let (a, b) = (self.sparse[level][l], self.sparse[level][r + 1 - (1 << level)]);
+        match self.depth[a] <= self.depth[b] {
+            true => a,
+            false => b,
+        }
+    }
+}
+
+/// A subtree of a tree restricted to a set of coloured leaves, with unary nodes suppressed
+pub(crate) struct RestrictedTree {
+    /// Node of the original tree for each node, in pre-order
+    pub(crate) nodes: Vec<usize>,
+    pub(crate) parent: Vec<Option<usize>>,
+    pub(crate) children: Vec<Vec<usize>>,
+    /// Colour of each leaf
+    pub(crate) colour: Vec<Option<usize>>,
+    /// Child with the most leaves below it
+    pub(crate) heavy: Vec<Option<usize>>,
+}
+
+impl RestrictedTree {
+    pub(crate) fn new(lca: &LcaIndex, mut leaves: Vec<(usize, usize)>) -> Self {
+        leaves.sort_by_key(|(v, _)| lca.preorder[*v]);
+        let mut nodes = leaves.iter().map(|(v, _)| *v).collect_vec();
+        nodes.extend(leaves.windows(2).map(|w| lca.lca(w[0].0, w[1].0)));
+        nodes.sort_by_key(|v| lca.preorder[*v]);
+        nodes.dedup();
+        let leaf_colour: HashMap<usize, usize> = leaves.into_iter().collect();
+        let colour = nodes.iter().map(|v| leaf_colour.get(v).copied()).collect_vec();
+        let mut parent = vec![None; nodes.len()];
+        let mut children = vec![vec![]; nodes.len()];
+        let mut stack: Vec<usize> = vec![];
+        for (x, v) in nodes.iter().enumerate() {
+            while let Some(y) = stack.last() {
+                if lca.is_ancestor(nodes[*y], *v) {
+                    break;
+                }
+                stack.pop();
+            }
+            if let Some(y) = stack.last() {
+                parent[x] = Some(*y);
+                children[*y].push(x);
+            }
+            stack.push(x);
+        }
+        let mut size = vec![0; nodes.len()];
+        for x in (0..nodes.len()).rev() {
+            size[x] = match colour[x] {
+                Some(_) => 1,
+                None => children[x].iter().map(|y| size[*y]).sum(),
+            };
+        }
+        let heavy = children
+            .iter()
+            .map(|c| c.iter().copied().max_by_key(|y| size[*y]))
+            .collect_vec();
+        RestrictedTree {
+            nodes,
+            parent,
+            children,
+            colour,
+            heavy,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub(crate) fn light_child(&self, x: usize) -> usize {
+        self.children[x]
+            .iter()
+            .copied()
+            .find(|y| Some(*y) != self.heavy[x])
+            .unwrap()
+    }
+}
+
+/// Returns the number of pairs of n items
+fn pairs(n: i64) -> i64 {
+    n * (n - 1) / 2
+}
+
+/// Prefix sums of an array under point updates, as a Fenwick tree
+struct PrefixSums {
+    sums: Vec<i64>,
+}
+
+impl PrefixSums {
+    fn new(len: usize) -> Self {
+        PrefixSums {
+            sums: vec![0; len + 1],
+        }
+    }
+
+    fn add(&mut self, pos: usize, value: i64) {
+        let mut idx = pos + 1;
+        while idx < self.sums.len() {
+            self.sums[idx] += value;
+            idx += idx & idx.wrapping_neg();
+        }
+    }
+
+    /// Returns the sum of entries at positions up to and including pos
+    fn sum_to(&self, pos: usize) -> i64 {
+        let mut idx = pos + 1;
+        let mut sum = 0;
+        while idx > 0 {
+            sum += self.sums[idx];
+            idx -= idx & idx.wrapping_neg();
+        }
+        sum
+    }
+
+    /// Returns the sum of entries at positions start..=end
+    fn sum(&self, start: usize, end: usize) -> i64 {
+        match start {
+            0 => self.sum_to(end),
+            _ => self.sum_to(end) - self.sum_to(start - 1),
+        }
+    }
+}
+
+/// Counts of marked leaves below each node of a tree, kept along its heavy paths.
+///
+/// Nodes are numbered so that each heavy path occupies consecutive positions from its top, and the count of a node is stored as a sum of differences over positions up to its own. Each position also holds the number of pairs of marked leaves below each light child of its node. Since a path to the root meets O(log n) heavy paths, marking a leaf and summing pairs below the nodes hanging off a path to the root each take O(log^2 n) time.
+struct HeavyPaths {
+    parent: Vec<Option<usize>>,
+    heavy: Vec<Option<usize>>,
+    /// Top of the heavy path of each node
+    head: Vec<usize>,
+    pos: Vec<usize>,
+    /// Differences of the number of marked leaves below consecutive positions
+    marked: PrefixSums,
+    /// Pairs of marked leaves below the light children of each position
+    light_pairs: PrefixSums,
+    num_marked: i64,
+}
+
+impl HeavyPaths {
+    fn new(tree: &CompactTree) -> Self {
+        let heavy = tree
+            .children
+            .iter()
+            .map(|c| c.iter().copied().max_by_key(|v| tree.cluster_size[*v]))
+            .collect_vec();
+        let root = tree.len() - 1;
+        let mut head = vec![root; tree.len()];
+        let mut pos = vec![0; tree.len()];
+        let mut stack = vec![root];
+        let mut next = 0;
+        while let Some(v) = stack.pop() {
+            pos[v] = next;
+            next += 1;
+            // the heavy child is visited next so that it follows its parent
+            for c in tree.children[v].iter() {
+                if Some(*c) != heavy[v] {
+                    head[*c] = *c;
+                    stack.push(*c);
+                }
+            }
+            if let Some(h) = heavy[v] {
+                head[h] = head[v];
+                stack.push(h);
+            }
+        }
+        HeavyPaths {
+            parent: tree.parent.clone(),
+            heavy,
+            head,
+            pos,
+            marked: PrefixSums::new(tree.len() + 1),
+            light_pairs: PrefixSums::new(tree.len()),
+            num_marked: 0,
+        }
+    }
+
+    /// Returns the number of marked leaves below a node
+    fn count(&self, v: usize) -> i64 {
+        self.marked.sum_to(self.pos[v])
+    }
+
+    /// Marks a leaf (delta = 1) or unmarks it (delta = -1)
+    fn mark(&mut self, leaf: usize, delta: i64) {
+        self.num_marked += delta;
+        let mut v = leaf;
+        loop {
+            let top = self.head[v];
+            self.marked.add(self.pos[top], delta);
+            self.marked.add(self.pos[v] + 1, -delta);
+            let Some(p) = self.parent[top] else {
+                break;
+            };
+            let count = self.count(top);
+            self.light_pairs.add(self.pos[p], pairs(count) - pairs(count - delta));
+            v = p;
+        }
+    }
+
+    /// Returns the number of pairs of marked leaves below a common child of a proper ancestor of v, where the child is not an ancestor of v
+    fn pairs_off_path(&self, v: usize) -> i64 {
+        let mut total = 0;
+        let mut child = v;
+        while let Some(u) = self.parent[child] {
+            let top = self.head[u];
+            total += self.light_pairs.sum(self.pos[top], self.pos[u]);
+            // pairs below the heavy children of nodes above u on its heavy path lie off the path
+            if let Some(h) = self.heavy[u] {
+                total += pairs(self.count(h));
+            }
+            total -= pairs(self.count(child));
+            child = top;
+        }
+        total
+    }
+
+    /// Returns the child of u that is an ancestor of v, where u is a proper ancestor of v
+    fn child_towards(&self, u: usize, mut v: usize) -> usize {
+        loop {
+            let top = self.head[v];
+            if top == self.head[u] {
+                return self.heavy[u].unwrap();
+            }
+            let p = self.parent[top].unwrap();
+            if p == u {
+                return top;
+            }
+            v = p;
+        }
+    }
+}
+
+/// Counts of rooted triplets claimed by the nodes of a tree and resolved alike or unresolved in a second tree.
+///
+/// Nodes are visited along heavy paths from the bottom, with the leaves below the heavy child of the current node marked in the second tree. The leaves below its light children are coloured by child, and triplets with two marked leaves are counted from the marked pairs hanging off the path of each coloured leaf, while the remaining triplets are counted over the second tree restricted to the coloured leaves. Each leaf is marked once for each light node above it.
+struct TripletSweep<'a> {
+    tree: &'a CompactTree,
+    heavy: Vec<Option<usize>>,
+    /// Node of each taxa in the second tree
+    leaf_2: Vec<usize>,
+    lca: LcaIndex,
+    paths: HeavyPaths,
+    shared: i128,
+    unresolved: i128,
+}
+
+impl<'a> TripletSweep<'a> {
+    fn new(t1: &'a CompactTree, t2: &CompactTree) -> Self {
+        let heavy = t1
+            .children
+            .iter()
+            .map(|c| c.iter().copied().max_by_key(|v| t1.cluster_size[*v]))
+            .collect_vec();
+        let mut leaf_2 = vec![0; t2.num_leaves];
+        for (v, t) in t2.leaf_taxa.iter().enumerate() {
+            if let Some(t) = t {
+                leaf_2[*t] = v;
+            }
+        }
+        let mut sweep = TripletSweep {
+            tree: t1,
+            heavy,
+            leaf_2,
+            lca: LcaIndex::new(t2),
+            paths: HeavyPaths::new(t2),
+            shared: 0,
+            unresolved: 0,
+        };
+        sweep.sweep(t1.len() - 1);
+        sweep
+    }
+
+    /// Returns the nodes of the second tree for the taxa below a node
+    fn leaves_below(&self, v: usize) -> Vec<usize> {
+        let mut leaves = vec![];
+        let mut stack = vec![v];
+        while let Some(u) = stack.pop() {
+            if let Some(t) = self.tree.leaf_taxa[u] {
+                leaves.push(self.leaf_2[t]);
+            }
+            stack.extend(self.tree.children[u].iter());
+        }
+        leaves
+    }
+
+    /// Counts the triplets claimed by the nodes of the heavy path starting at top, leaving the leaves below top marked
+    fn sweep(&mut self, top: usize) {
+        let mut path = vec![top];
+        while let Some(h) = self.heavy[*path.last().unwrap()] {
+            path.push(h);
+        }
+        let mut light = vec![];
+        for v in path.iter() {
+            let mut coloured = vec![];
+            for (colour, c) in self.tree.children[*v].iter().enumerate() {
+                if Some(*c) != self.heavy[*v] {
+                    self.sweep(*c);
+                    let leaves = self.leaves_below(*c);
+                    for leaf in leaves.iter() {
+                        self.paths.mark(*leaf, -1);
+                    }
+                    coloured.extend(leaves.into_iter().map(|leaf| (leaf, colour)));
+                }
+            }
+            light.push(coloured);
+        }
+        for leaf in self.leaves_below(*path.last().unwrap()) {
+            self.paths.mark(leaf, 1);
+        }
+        for coloured in light.into_iter().rev().skip(1) {
+            if !coloured.is_empty() {
+                self.claim(&coloured);
+                for (leaf, _) in coloured.iter() {
+                    self.paths.mark(*leaf, 1);
+                }
+            }
+        }
+    }
+
+    /// Counts the triplets claimed by a node with the leaves below its heavy child marked and the leaves below its light children coloured
+    fn claim(&mut self, coloured: &[(usize, usize)]) {
+        let num_marked = self.paths.num_marked;
+        // a marked pair with a coloured leaf outside their cluster
+        let mut shared: i128 = coloured
+            .iter()
+            .map(|(leaf, _)| self.paths.pairs_off_path(*leaf) as i128)
+            .sum();
+        let mut unresolved = 0_i128;
+
+        let restricted = RestrictedTree::new(&self.lca, coloured.to_vec());
+        let mut colour_sizes: HashMap<usize, i64> = HashMap::default();
+        for (_, colour) in coloured.iter() {
+            *colour_sizes.entry(*colour).or_default() += 1;
+        }
+        let num_coloured = coloured.len() as i64;
+        let mut below: Vec<HashMap<usize, i64>> = vec![HashMap::default(); restricted.len()];
+        for x in (0..restricted.len()).rev() {
+            if let Some(colour) = restricted.colour[x] {
+                below[x].insert(colour, 1);
+                continue;
+            }
+            let v = restricted.nodes[x];
+            let children = &restricted.children[x];
+            let sizes = children
+                .iter()
+                .map(|y| below[*y].values().sum::<i64>())
+                .collect_vec();
+            let marked = children
+                .iter()
+                .map(|y| self.paths.count(self.paths.child_towards(v, restricted.nodes[*y])))
+                .collect_vec();
+            let largest = (0..children.len()).max_by_key(|i| below[children[*i]].len()).unwrap();
+            let mut counts = std::mem::take(&mut below[children[largest]]);
+
+            // pairs and triples of leaves of the same colour below distinct children, by colour
+            let mut same: HashMap<usize, (i64, i64)> = HashMap::default();
+            let mut same_triples = 0;
+            let mut largest_overlap = 0;
+            for (i, y) in children.iter().enumerate() {
+                if i == largest {
+                    continue;
+                }
+                for (colour, c) in below[*y].iter() {
+                    let prev = counts.get(colour).copied().unwrap_or(0);
+                    let (first, same_pairs) = same.entry(*colour).or_insert((prev, 0));
+                    same_triples += c * *same_pairs;
+                    *same_pairs += c * prev;
+                    largest_overlap += c * *first;
+                    counts.insert(*colour, prev + c);
+                }
+            }
+            // pairs of leaves of the same colour with a leaf of another colour below another child, for each child
+            let overlaps = children
+                .iter()
+                .enumerate()
+                .map(|(i, y)| match i == largest {
+                    true => largest_overlap,
+                    false => below[*y].iter().map(|(colour, c)| c * (counts[colour] - c)).sum(),
+                })
+                .collect_vec();
+
+            let size: i64 = sizes.iter().sum();
+            let cluster_marked = self.paths.count(v);
+            let mut size_pairs = 0;
+            let mut size_triples = 0;
+            let mut seen = 0;
+            for s in sizes.iter() {
+                size_triples += s * size_pairs;
+                size_pairs += s * seen;
+                seen += s;
+            }
+            let same_pairs: i64 = same.values().map(|(_, p)| p).sum();
+
+            // a coloured pair with a marked leaf or a leaf of another colour outside their cluster
+            shared += (same_pairs * (num_marked - cluster_marked)) as i128;
+            shared += same
+                .iter()
+                .map(|(colour, (_, p))| (p * ((num_coloured - size) - (colour_sizes[colour] - counts[colour]))) as i128)
+                .sum::<i128>();
+            // a pair of distinct colours with a marked leaf below a third child
+            unresolved += (cluster_marked * (size_pairs - same_pairs)) as i128;
+            unresolved -= (0..children.len())
+                .map(|i| (marked[i] * (sizes[i] * (size - sizes[i]) - overlaps[i])) as i128)
+                .sum::<i128>();
+            // three distinct colours below three distinct children
+            let mixed_triples = size * same_pairs
+                - (0..children.len()).map(|i| sizes[i] * overlaps[i]).sum::<i64>();
+            unresolved += (size_triples - mixed_triples + 2 * same_triples) as i128;
+
+            for y in children.iter() {
+                below[*y].clear();
+            }
+            below[x] = counts;
+        }
+        self.shared += shared;
+        self.unresolved += unresolved;
+    }
+}
+
+/// Returns the nontrivial splits of a tree encoded as BitVecs over a taxa index.
+///
+/// Splits are normalized so that the taxa with index 0 is never set. In rooted mode an additional bit represents the root, so that each nontrivial cluster is encoded as a distinct split.
+pub(crate) fn tree_splits<T>(
+    tree: &T,
+    taxa_map: &HashMap<&TreeNodeMeta<T>, usize>,
+    mode: SplitMode,
+) -> HashSet<BitVec>
+where
+    T: RootedMetaTree + Clusters,
+    <T as RootedTree>::Node: RootedMetaNode,
+{
+    let num_bits = match mode {
+        SplitMode::Rooted => taxa_map.len() + 1,
+        SplitMode::Unrooted => taxa_map.len(),
+    };
+    let mut splits: HashSet<BitVec> = vec![].into_iter().collect();
+    for (n_id, mut bp) in node_clusters(tree, taxa_map, num_bits) {
+        let size = bp.count_ones() as usize;
+        if n_id == tree.get_root_id() || size < 2 || num_bits - size < 2 {
+            continue;
+        }
+        if bp.is_bit_set(0).unwrap() {
+            let mut bp_rev = BitVec::from_ones(num_bits);
+            let _ = bp_rev.apply_mask_xor(&bp);
+            bp = bp_rev;
+        }
+        splits.insert(bp);
+    }
+    splits
+}
+
+/// Returns the cluster of every node of a tree in post-order, encoded as BitVecs of length num_bits over a taxa index.
+pub(crate) fn node_clusters<T>(
+    tree: &T,
+    taxa_map: &HashMap<&TreeNodeMeta<T>, usize>,
+    num_bits: usize,
+) -> Vec<(TreeNodeID<T>, BitVec)>
+where
+    T: RootedMetaTree + Clusters,
+    <T as RootedTree>::Node: RootedMetaNode,
+{
+    let mut clusters: HashMap<TreeNodeID<T>, BitVec> = vec![].into_iter().collect();
+    let mut postord_clusters = vec![];
+    for n_id in tree.postord_ids(tree.get_root_id()) {
+        let mut bp = BitVec::from_zeros(num_bits);
+        match tree.is_leaf(n_id) {
+            true => {
+                if let Some(idx) = tree.get_node_taxa(n_id).and_then(|t| taxa_map.get(t)) {
+                    bp.flip_bit(*idx);
+                }
+            }
+            false => {
+                tree.get_node_children_ids(n_id)
+                    .map(|x| clusters.get(&x).unwrap())
+                    .for_each(|x| {let _ = bp.apply_mask_or(x);});
+            }
+        };
+        clusters.insert(n_id, bp.clone());
+        postord_clusters.push((n_id, bp));
+    }
+    postord_clusters
+}
+
+/// Returns the splits of a tree encoded as BitVecs over a taxa index, along with the total weight of the edges inducing each split.
+///
+/// Splits are normalized so that the taxa with index 0 is never set, hence a cluster and its complement are encoded identically and the two edges incident on a bifurcating root are merged into a single split. Trivial splits of leaves are included, and missing edge weights are counted as zero.
+pub(crate) fn weighted_splits<T>(
+    tree: &T,
+    taxa_map: &HashMap<&TreeNodeMeta<T>, usize>,
+) -> HashMap<BitVec, TreeNodeWeight<T>>
+where
+    T: RootedWeightedTree + RootedMetaTree + Clusters,
+    <T as RootedTree>::Node: RootedWeightedNode + RootedMetaNode,
+{
+    let num_taxa = taxa_map.len();
+    let mut clusters: HashMap<TreeNodeID<T>, BitVec> = vec![].into_iter().collect();
+    let mut splits: HashMap<BitVec, TreeNodeWeight<T>> = vec![].into_iter().collect();
+    for n_id in tree.postord_ids(tree.get_root_id()) {
+        let mut bp = BitVec::from_zeros(num_taxa);
+        match tree.is_leaf(n_id) {
+            true => {
+                if let Some(idx) = tree.get_node_taxa(n_id).and_then(|t| taxa_map.get(t)) {
+                    bp.flip_bit(*idx);
+                }
+            }
+            false => {
+                tree.get_node_children_ids(n_id)
+                    .map(|x| clusters.get(&x).unwrap())
+                    .for_each(|x| {let _ = bp.apply_mask_or(x);});
+            }
+        };
+        clusters.insert(n_id, bp.clone());
+        let Some(parent_id) = tree.get_node_parent_id(n_id) else {
+            continue;
+        };
+        if num_taxa > 0 && bp.is_bit_set(0).unwrap() {
+            let mut bp_rev = BitVec::from_ones(num_taxa);
+            let _ = bp_rev.apply_mask_xor(&bp);
+            bp = bp_rev;
+        }
+        if bp.count_ones() == 0 {
+            continue;
+        }
+        let weight = tree
+            .get_edge_weight(parent_id, n_id)
+            .unwrap_or(TreeNodeWeight::<T>::zero());
+        let split_weight = splits.entry(bp).or_insert(TreeNodeWeight::<T>::zero());
+        *split_weight = *split_weight + weight;
+    }
+    splits
+}
+
+/// Selects how path lengths between leaves are measured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PathLength {
+    /// Number of edges in the path
+    #[default]
+    Topological,
+    /// Sum of edge weights in the path, where missing edge weights are counted as one
+    Weighted,
+}
+
+/// A trait describing naive computation of path-difference distance
+pub trait PathDifference: DistanceMatrix + RootedMetaTree + EulerWalk + Ancestors
+where
+    <Self as RootedTree>::Node: RootedWeightedNode + RootedMetaNode,
+{
+    /// Returns length of the path between two nodes
+    fn path_length(
+        &self,
+        node_id_1: TreeNodeID<Self>,
+        node_id_2: TreeNodeID<Self>,
+        path_length: PathLength,
+    ) -> TreeNodeWeight<Self> {
+        match path_length {
+            PathLength::Topological => {
+                let lca = self.get_lca_id(&[node_id_1, node_id_2]);
+                <TreeNodeWeight<Self> as NumCast>::from(
+                    self.depth(node_id_1) + self.depth(node_id_2) - 2 * self.depth(lca),
+                )
+                .unwrap()
+            }
+            PathLength::Weighted => self.pairwise_distance(node_id_1, node_id_2),
+        }
+    }
+
+    /// Returns the path-difference distance between two trees over their shared taxa using the naive algorithm (\Theta(n^2) paths)
+    fn path_diff(
+        &self,
+        tree: &Self,
+        norm: u32,
+        path_length: PathLength,
+    ) -> TreeNodeWeight<Self> {
+        let binding1 = self
+            .get_taxa_space()
+            .collect::<HashSet<&TreeNodeMeta<Self>>>();
+        let binding2 = tree
+            .get_taxa_space()
+            .collect::<HashSet<&TreeNodeMeta<Self>>>();
+        let taxa_set = binding1.intersection(&binding2).cloned();
+
+        self.path_diff_by_taxa(tree, norm, path_length, taxa_set)
+    }
+
+    /// Returns the path-difference distance between two trees restricted to a taxa set using the naive algorithm (\Theta(n^2) paths)
+    fn path_diff_by_taxa<'a>(
+        &'a self,
+        tree: &'a Self,
+        norm: u32,
+        path_length: PathLength,
+        taxa_set: impl Iterator<Item = &'a TreeNodeMeta<Self>>,
+    ) -> TreeNodeWeight<Self> {
+        let diff_vec = taxa_set.combinations(2).map(|x| {
+            let self_length = self.path_length(
+                self.get_taxa_node_id(x[0]).unwrap(),
+                self.get_taxa_node_id(x[1]).unwrap(),
+                path_length,
+            );
+            let tree_length = tree.path_length(
+                tree.get_taxa_node_id(x[0]).unwrap(),
+                tree.get_taxa_node_id(x[1]).unwrap(),
+                path_length,
+            );
+            (self_length - tree_length).abs()
+        });
+
+        vector_norm(diff_vec, norm)
+    }
+
+    #[cfg(feature = "parallel")]
+    /// Returns the path-difference distance between two trees over their shared taxa, computing paths in parallel
+    fn path_diff_par(
+        &self,
+        tree: &Self,
+        norm: u32,
+        path_length: PathLength,
+    ) -> TreeNodeWeight<Self> {
+        let binding1 = self
+            .get_taxa_space()
+            .collect::<HashSet<&TreeNodeMeta<Self>>>();
+        let binding2 = tree
+            .get_taxa_space()
+            .collect::<HashSet<&TreeNodeMeta<Self>>>();
+        let taxa_set = binding1.intersection(&binding2).cloned();
+
+        self.path_diff_by_taxa_par(tree, norm, path_length, taxa_set)
+    }
+
+    #[cfg(feature = "parallel")]
+    /// Returns the path-difference distance between two trees restricted to a taxa set, computing paths in parallel
+    fn path_diff_by_taxa_par<'a>(
+        &'a self,
+        tree: &'a Self,
+        norm: u32,
+        path_length: PathLength,
+        taxa_set: impl Iterator<Item = &'a TreeNodeMeta<Self>>,
+    ) -> TreeNodeWeight<Self> {
+        let diff_vec = taxa_set
+            .combinations(2)
+            .collect_vec()
+            .into_par_iter()
+            .map(|x| {
+                let self_length = self.path_length(
+                    self.get_taxa_node_id(x[0]).unwrap(),
+                    self.get_taxa_node_id(x[1]).unwrap(),
+                    path_length,
+                );
+                let tree_length = tree.path_length(
+                    tree.get_taxa_node_id(x[0]).unwrap(),
+                    tree.get_taxa_node_id(x[1]).unwrap(),
+                    path_length,
+                );
+                (self_length - tree_length).abs()
+            })
+            .collect::<Vec<_>>();
+
+        vector_norm(diff_vec.into_iter(), norm)
+    }
+}
+
+/// Returns the nth norm of an iterator of floating point values, where the 0th norm is the maximum.
+pub(crate) fn vector_norm<W: Float + std::iter::Sum>(vector: impl Iterator<Item = W>, norm: u32) -> W {
+    match norm {
+        0 => vector.fold(W::zero(), |acc, x| acc.max(x)),
+        1 => vector.sum(),
+        _ => vector
+            .map(|x| x.powi(norm as i32))
+            .sum::<W>()
+            .powf(W::one() / <W as NumCast>::from(norm).unwrap()),
+    }
 }
 
 /// A trait describing naive computation of cophenetic distance
@@ -428,3 +2067,269 @@ where
         Self::compute_norm(cophen_vec, norm)
     }
 }
+
+/// A trait describing computation of TBR distances of unrooted binary trees via maximum agreement forests.
+///
+/// The TBR distance is the number of components of a maximum agreement forest less one. The forest is found by branching on cherries of the first tree that are not cherries of the forest: either leaf of the cherry is cut off, or the leaves are kept together by cutting all but one of the p >= 2 subtrees pendant to the path between them. Every branch cuts at least one edge and the p branches keeping a pendant subtree cut p - 1 edges each, so the search tree has O(4^k) nodes for distance k, each reduced in O(n^2) time.
+pub trait TbrDistance: UnrootedMetaTree
+where
+    <Self as UnrootedTree>::Node: UnrootedMetaNode,
+{
+    /// Returns the taxa of the components of a maximum agreement forest of self and tree
+    fn tbr_forest(&self, tree: &Self) -> Result<Vec<Vec<UTreeNodeMeta<Self>>>, DistanceError> {
+        let taxa = self.get_taxa_space().cloned().collect_vec();
+        let taxa_map: HashMap<&UTreeNodeMeta<Self>, usize> =
+            taxa.iter().enumerate().map(|(idx, taxa)| (taxa, idx)).collect();
+        if tree.num_taxa() != taxa.len() || tree.get_taxa_space().any(|t| !taxa_map.contains_key(t)) {
+            return Err(DistanceError::TaxaMismatch);
+        }
+        let state = TbrState {
+            t1: UnrootedForest::new(self, &taxa_map)?,
+            forest: UnrootedForest::new(tree, &taxa_map)?,
+            members: (0..taxa.len()).map(|x| vec![x]).collect_vec(),
+        };
+        let forest = (0..=taxa.len())
+            .find_map(|max_cuts| state.clone().search(max_cuts))
+            .expect("cutting every leaf edge gives an agreement forest");
+        Ok(forest
+            .components()
+            .into_iter()
+            .map(|component| component.into_iter().map(|x| taxa[x].clone()).collect_vec())
+            .collect_vec())
+    }
+
+    /// Returns the TBR distance between self and tree
+    fn tbr_distance(&self, tree: &Self) -> Result<usize, DistanceError> {
+        Ok(self.tbr_forest(tree)?.len().saturating_sub(1))
+    }
+}
+
+/// An unrooted forest of binary trees with leaves labelled by merged taxa sets
+#[derive(Debug, Clone)]
+struct UnrootedForest {
+    neighbours: Vec<Vec<usize>>,
+    /// Label of each leaf
+    label: Vec<Option<usize>>,
+    /// Node of each label
+    leaf_of: Vec<usize>,
+}
+
+impl UnrootedForest {
+    fn new<T>(tree: &T, taxa_map: &HashMap<&UTreeNodeMeta<T>, usize>) -> Result<Self, DistanceError>
+    where
+        T: UnrootedMetaTree,
+        <T as UnrootedTree>::Node: UnrootedMetaNode,
+    {
+        let node_ids = tree.get_node_ids().collect_vec();
+        let index: HashMap<UTreeNodeID<T>, usize> =
+            node_ids.iter().enumerate().map(|(idx, id)| (*id, idx)).collect();
+        let mut forest = UnrootedForest {
+            neighbours: node_ids
+                .iter()
+                .map(|id| tree.get_node_neighbour_ids(*id).map(|n| index[&n]).collect_vec())
+                .collect_vec(),
+            label: node_ids
+                .iter()
+                .map(|id| tree.get_node_taxa(*id).map(|taxa| taxa_map[taxa]))
+                .collect_vec(),
+            leaf_of: vec![0; taxa_map.len()],
+        };
+        for node in 0..node_ids.len() {
+            forest.suppress(node);
+            match (forest.label[node], forest.neighbours[node].len()) {
+                (Some(label), 0 | 1) => forest.leaf_of[label] = node,
+                (None, 0 | 3) => {}
+                _ => return Err(DistanceError::NotBinary),
+            }
+        }
+        Ok(forest)
+    }
+
+    fn remove_edge(&mut self, x: usize, y: usize) {
+        self.neighbours[x].retain(|n| *n != y);
+        self.neighbours[y].retain(|n| *n != x);
+    }
+
+    /// Suppresses an unlabelled node of degree two
+    fn suppress(&mut self, node: usize) {
+        if let (None, [x, y]) = (self.label[node], self.neighbours[node].as_slice()) {
+            let (x, y) = (*x, *y);
+            self.remove_edge(node, x);
+            self.remove_edge(node, y);
+            self.neighbours[x].push(y);
+            self.neighbours[y].push(x);
+        }
+    }
+
+    /// Removes an edge, suppressing both of its endpoints
+    fn cut(&mut self, (x, y): (usize, usize)) {
+        self.remove_edge(x, y);
+        self.suppress(x);
+        self.suppress(y);
+    }
+
+    fn is_leaf(&self, node: usize) -> bool {
+        self.label[node].is_some() && self.neighbours[node].len() == 1
+    }
+
+    fn num_attached_leaves(&self) -> usize {
+        (0..self.label.len()).filter(|node| self.is_leaf(*node)).count()
+    }
+
+    /// Returns a pair of leaves adjacent to a common node
+    fn cherry(&self) -> Option<(usize, usize)> {
+        (0..self.label.len()).find_map(|node| {
+            let mut leaves = self.neighbours[node].iter().filter(|n| self.is_leaf(**n));
+            Some((*leaves.next()?, *leaves.next()?))
+        })
+    }
+
+    fn are_siblings(&self, x: usize, y: usize) -> bool {
+        match (self.neighbours[x].as_slice(), self.neighbours[y].as_slice()) {
+            ([n_x], [n_y]) => n_x == n_y || *n_x == y,
+            _ => false,
+        }
+    }
+
+    /// Replaces a pair of sibling leaves by a single leaf with the label of the first leaf
+    fn contract(&mut self, x: usize, y: usize) {
+        let label = self.label[x].unwrap();
+        self.label[y] = None;
+        if self.neighbours[x] == [y] {
+            self.remove_edge(x, y);
+            return;
+        }
+        let parent = self.neighbours[x][0];
+        self.remove_edge(x, parent);
+        self.remove_edge(y, parent);
+        self.label[x] = None;
+        self.label[parent] = Some(label);
+        self.leaf_of[label] = parent;
+    }
+
+    /// Returns the path between two nodes, if they are in the same component
+    fn path(&self, x: usize, y: usize) -> Option<Vec<usize>> {
+        let mut previous = vec![None; self.label.len()];
+        let mut stack = vec![x];
+        previous[x] = Some(x);
+        while let Some(node) = stack.pop() {
+            for n in self.neighbours[node].iter() {
+                if previous[*n].is_none() {
+                    previous[*n] = Some(node);
+                    stack.push(*n);
+                }
+            }
+        }
+        previous[y]?;
+        let mut path = vec![y];
+        while *path.last().unwrap() != x {
+            path.push(previous[*path.last().unwrap()].unwrap());
+        }
+        Some(path)
+    }
+}
+
+/// State of the search for a maximum agreement forest of unrooted trees, where the first tree is reduced as cherries agreeing with the forest are contracted
+#[derive(Debug, Clone)]
+struct TbrState {
+    t1: UnrootedForest,
+    forest: UnrootedForest,
+    /// Taxa of each label
+    members: Vec<Vec<usize>>,
+}
+
+impl TbrState {
+    /// Contracts cherries and removes leaves isolated in the forest until a cut is required. Returns sets of edges of the forest, one of which must be cut.
+    fn reduce(&mut self) -> Option<Vec<Vec<(usize, usize)>>> {
+        // any forest agrees with a tree on at most three leaves
+        while self.t1.num_attached_leaves() > 3 {
+            let (a, c) = self.t1.cherry().unwrap();
+            let (label_a, label_c) = (self.t1.label[a].unwrap(), self.t1.label[c].unwrap());
+            let (f_a, f_c) = (self.forest.leaf_of[label_a], self.forest.leaf_of[label_c]);
+            if self.forest.are_siblings(f_a, f_c) {
+                self.t1.contract(a, c);
+                self.forest.contract(f_a, f_c);
+                let merged = std::mem::take(&mut self.members[label_c]);
+                self.members[label_a].extend(merged);
+                continue;
+            }
+            if let Some(leaf) = [a, c].into_iter().find(|x| {
+                let label = self.t1.label[*x].unwrap();
+                self.forest.neighbours[self.forest.leaf_of[label]].is_empty()
+            }) {
+                self.t1.cut((leaf, self.t1.neighbours[leaf][0]));
+                continue;
+            }
+            let leaf_edge = |x: usize| (x, self.forest.neighbours[x][0]);
+            let mut branches = vec![vec![leaf_edge(f_a)], vec![leaf_edge(f_c)]];
+            if let Some(path) = self.forest.path(f_a, f_c) {
+                // a and c remain together only if all but one subtree pendant to the path between them are cut, and there are at least two such subtrees since a and c are not siblings
+                let pendants = path
+                    .iter()
+                    .tuple_windows()
+                    .map(|(previous, node, next)| {
+                        let pendant = self.forest.neighbours[*node]
+                            .iter()
+                            .find(|n| *n != previous && *n != next)
+                            .unwrap();
+                        (*node, *pendant)
+                    })
+                    .collect_vec();
+                branches.extend((0..pendants.len()).map(|kept| {
+                    pendants
+                        .iter()
+                        .enumerate()
+                        .filter(|(idx, _)| *idx != kept)
+                        .map(|(_, edge)| *edge)
+                        .collect_vec()
+                }));
+            }
+            return Some(branches);
+        }
+        None
+    }
+
+    /// Returns a maximum agreement forest with at most max_cuts cut edges, if one exists
+    fn search(mut self, max_cuts: usize) -> Option<Self> {
+        let Some(branches) = self.reduce() else {
+            return Some(self);
+        };
+        branches
+            .into_iter()
+            .filter(|cut| cut.len() <= max_cuts)
+            .find_map(|cut| {
+                let mut state = self.clone();
+                for edge in cut.iter() {
+                    state.forest.cut(*edge);
+                }
+                state.search(max_cuts - cut.len())
+            })
+    }
+
+    /// Returns the taxa of each component of the forest
+    fn components(&self) -> Vec<Vec<usize>> {
+        let mut visited = vec![false; self.forest.label.len()];
+        let mut components = vec![];
+        for root in 0..self.forest.label.len() {
+            if visited[root] || (self.forest.label[root].is_none() && self.forest.neighbours[root].is_empty()) {
+                continue;
+            }
+            let mut component = vec![];
+            let mut stack = vec![root];
+            visited[root] = true;
+            while let Some(node) = stack.pop() {
+                if let Some(label) = self.forest.label[node] {
+                    component.extend(self.members[label].iter().copied());
+                }
+                for n in self.forest.neighbours[node].iter() {
+                    if !visited[*n] {
+                        visited[*n] = true;
+                        stack.push(*n);
+                    }
+                }
+            }
+            components.push(component);
+        }
+        components
+    }
+}
@@ -4,7 +4,7 @@ use std::{
     hash::Hash,
 };
 
-use crate::prelude::{Clusters, EulerWalk, RootedTree, DFS};
+use crate::prelude::{Clusters, EditError, EulerWalk, RootedTree, RootingError, DFS};
 use crate::{
     iter::node_iter::Ancestors, node::simple_rnode::RootedTreeNode, tree::simple_rtree::TreeNodeID,
 };
@@ -16,10 +16,10 @@ use std::collections::{HashMap, HashSet};
 
 /// A trait describing subtree-prune-regraft operations
 pub trait SPR: RootedTree + DFS + Sized {
-    /// Attaches input tree to self by spliting an edge
+    /// Attaches input tree to self by splitting an edge with a new node. Nodes of the input tree keep their NodeIDs unless these are already in use in self, in which case they are given new NodeIDs. Returns Err if edge is not an edge of self.
     fn graft(&mut self, tree: Self, edge: (TreeNodeID<Self>, TreeNodeID<Self>)) -> Result<(), ()>;
 
-    /// Returns subtree starting at given node, while corresponding nodes from self.
+    /// Returns subtree starting at given node, while removing corresponding nodes and their taxa from self. The parent of the node is left in place. Returns Err if the node is not a non-root node of self.
     fn prune(&mut self, node_id: TreeNodeID<Self>) -> Result<Self, ()>;
 
     /// SPR function
@@ -33,13 +33,147 @@ pub trait SPR: RootedTree + DFS + Sized {
     }
 }
 
+/// A trait describing tree-bisection-reconnection operations
+pub trait TBR: SPR + for<'a> Reroot<'a> {
+    /// Bisects the tree by removing the edge above `node_id`, reroots the detached subtree at `reroot_edge` (keeping its root if None) and reconnects it by splitting `regraft_edge`. The former parent of `node_id` is suppressed, so `regraft_edge` must be an edge of the remaining tree that is not incident on it.
+    fn tbr(
+        &mut self,
+        node_id: TreeNodeID<Self>,
+        reroot_edge: Option<(TreeNodeID<Self>, TreeNodeID<Self>)>,
+        regraft_edge: (TreeNodeID<Self>, TreeNodeID<Self>),
+    ) -> Result<(), EditError> {
+        let missing_parent = || EditError::MissingParent {
+            node: node_id.to_string(),
+        };
+        let invalid_edge = |(p_id, c_id): (TreeNodeID<Self>, TreeNodeID<Self>)| EditError::InvalidEdge {
+            parent: p_id.to_string(),
+            child: c_id.to_string(),
+        };
+        let parent_id = self.get_node_parent_id(node_id).ok_or_else(missing_parent)?;
+        let not_binary = || EditError::NotBinary {
+            node: parent_id.to_string(),
+        };
+        let subtree_ids: HashSet<TreeNodeID<Self>> =
+            self.dfs(node_id).map(|node| node.get_id()).collect();
+        let is_edge = |tree: &Self, (p_id, c_id): (TreeNodeID<Self>, TreeNodeID<Self>)| {
+            tree.get_node_ids().contains(&c_id) && tree.get_node_parent_id(c_id) == Some(p_id)
+        };
+        if !is_edge(self, regraft_edge)
+            || subtree_ids.contains(&regraft_edge.1)
+            || regraft_edge.0 == parent_id
+            || regraft_edge.1 == parent_id
+        {
+            return Err(invalid_edge(regraft_edge));
+        }
+        if let Some(edge) = reroot_edge.filter(|edge| !is_edge(self, *edge) || !subtree_ids.contains(&edge.1)) {
+            return Err(invalid_edge(edge));
+        }
+        if self.get_node_children_ids(parent_id).count() != 2 {
+            return Err(not_binary());
+        }
+        let mut subtree = self.prune(node_id).map_err(|_| missing_parent())?;
+        // the remaining child of the former parent takes its place
+        let sibling_id = self.get_node_children_ids(parent_id).next().ok_or_else(not_binary)?;
+        match self.get_node_parent_id(parent_id) {
+            Some(_) => {
+                self.supress_node(parent_id).ok_or_else(not_binary)?;
+            }
+            None => {
+                self.remove_node(parent_id);
+                self.get_node_mut(sibling_id).unwrap().set_parent(None);
+                self.set_root(sibling_id);
+            }
+        }
+        if let Some(edge) = reroot_edge {
+            subtree.reroot_at_edge(edge).map_err(|_| invalid_edge(edge))?;
+        }
+        self.graft(subtree, regraft_edge).map_err(|_| invalid_edge(regraft_edge))
+    }
+}
+
+/// A struct describing a nearest neighbour interchange around an internal edge.
+///
+/// Applying the move exchanges `child`, a child of `edge.1`, with `sibling`, a sibling of `edge.1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NNIMove<N> {
+    /// Internal edge (parent, child) around which the subtrees are interchanged
+    pub edge: (N, N),
+    /// Sibling of `edge.1` that is moved below `edge.1`
+    pub sibling: N,
+    /// Child of `edge.1` that is moved below `edge.0`
+    pub child: N,
+}
+
+impl<N: Copy> NNIMove<N> {
+    /// Returns the move that undoes self
+    pub fn reverse(&self) -> Self {
+        NNIMove {
+            edge: self.edge,
+            sibling: self.child,
+            child: self.sibling,
+        }
+    }
+}
+
 /// A trait describing Nearest Neighbour interchange operations
 pub trait NNI<'a>
 where
     Self: RootedTree + Sized,
 {
-    /// Performs an NNI operation
-    fn nni(&mut self, parent_id: TreeNodeID<Self>) -> Result<(), ()>;
+    /// Applies an NNI move to the tree. Returns Err if the move does not describe an interchange around an edge of the tree.
+    fn apply_nni(&mut self, nni_move: NNIMove<TreeNodeID<Self>>) -> Result<(), EditError>;
+
+    /// Performs an NNI operation around the edge ending in `parent_id`, interchanging its first child with its first sibling
+    fn nni(&mut self, parent_id: TreeNodeID<Self>) -> Result<(), EditError> {
+        let nni_move = self
+            .nni_moves_at(parent_id)
+            .next()
+            .ok_or_else(|| EditError::NoNNIMove {
+                node: parent_id.to_string(),
+            })?;
+        self.apply_nni(nni_move)
+    }
+
+    /// Returns all NNI moves around the edge ending in a node. There are none if the node is the root or a leaf.
+    fn nni_moves_at(
+        &self,
+        node_id: TreeNodeID<Self>,
+    ) -> impl Iterator<Item = NNIMove<TreeNodeID<Self>>> {
+        let moves = match self.get_node_parent_id(node_id) {
+            Some(parent_id) if !self.is_leaf(node_id) => self
+                .get_sibling_ids(node_id)
+                .cartesian_product(self.get_node_children_ids(node_id).collect_vec())
+                .map(|(sibling, child)| NNIMove {
+                    edge: (parent_id, node_id),
+                    sibling,
+                    child,
+                })
+                .collect_vec(),
+            _ => vec![],
+        };
+        moves.into_iter()
+    }
+
+    /// Returns all NNI moves of a tree, i.e. every interchange around every internal edge
+    fn nni_moves(&self) -> impl Iterator<Item = NNIMove<TreeNodeID<Self>>> {
+        self.get_node_ids()
+            .flat_map(|node_id| self.nni_moves_at(node_id))
+            .collect_vec()
+            .into_iter()
+    }
+
+    /// Returns all trees that are a single NNI move away from self
+    fn nni_neighbours(&self) -> impl Iterator<Item = Self> {
+        self.nni_moves()
+            .map(|nni_move| {
+                let mut tree = self.clone();
+                tree.apply_nni(nni_move)
+                    .expect("moves are enumerated from the tree");
+                tree
+            })
+            .collect_vec()
+            .into_iter()
+    }
 }
 
 /// A trait describing rerooting a tree
@@ -47,10 +181,10 @@ pub trait Reroot<'a>
 where
     Self: RootedTree + Sized,
 {
-    /// Reroots tree at node. **Note: this changes the degree of a node**
-    fn reroot_at_node(&mut self, node_id: TreeNodeID<Self>) -> Result<(), ()>;
-    /// Reroots tree at a split node.
-    fn reroot_at_edge(&mut self, edge: (TreeNodeID<Self>, TreeNodeID<Self>)) -> Result<(), ()>;
+    /// Reroots tree at an internal node. **Note: this changes the degree of a node**. Returns Err if the node is not an internal node of the tree; to root next to a leaf use `reroot_at_edge` on its pendant edge.
+    fn reroot_at_node(&mut self, node_id: TreeNodeID<Self>) -> Result<(), RootingError>;
+    /// Reroots tree at a new node splitting an edge. Returns Err if the edge is not an edge of the tree.
+    fn reroot_at_edge(&mut self, edge: (TreeNodeID<Self>, TreeNodeID<Self>)) -> Result<(), RootingError>;
 }
 
 /// A trait describing balancing a binary tree
@@ -0,0 +1,271 @@
+use itertools::Itertools;
+use num::{Float, One, Zero};
+
+#[cfg(feature = "non_crypto_hash")]
+use fxhash::{FxHashMap as HashMap, FxHashSet as HashSet};
+#[cfg(not(feature = "non_crypto_hash"))]
+use std::collections::{HashMap, HashSet};
+
+use crate::prelude::*;
+
+/// A trait describing rooting of arbitrarily rooted trees by midpoint, outgroup and minimal ancestor deviation.
+pub trait Rooting:
+    for<'a> Reroot<'a> + DistanceMatrix + RootedMetaTree + EulerWalk + Ancestors + Clusters
+where
+    <Self as RootedTree>::Node: RootedWeightedNode + RootedMetaNode,
+{
+    /// Roots tree at the midpoint of the longest leaf to leaf path. Missing edge weights are counted as one.
+    fn midpoint_root(&mut self) -> Result<(), RootingError> {
+        let leaf_ids = self.get_leaf_ids().collect_vec();
+        if leaf_ids.len() < 2 {
+            return Err(RootingError::TooFewLeaves { min: 2 });
+        }
+        self.precompute_constant_time_lca();
+        let (leaf_1, leaf_2, max_dist) = leaf_ids
+            .iter()
+            .tuple_combinations()
+            .map(|(x, y)| (*x, *y, self.pairwise_distance(*x, *y)))
+            .max_by(|x, y| x.2.partial_cmp(&y.2).unwrap())
+            .unwrap();
+        let half_dist = max_dist / (TreeNodeWeight::<Self>::one() + TreeNodeWeight::<Self>::one());
+        let lca = self.get_lca_id(&[leaf_1, leaf_2]);
+
+        // the midpoint lies on the path from the leaf further away from the lca
+        let start_leaf = match self.pairwise_distance(leaf_1, lca) >= half_dist {
+            true => leaf_1,
+            false => leaf_2,
+        };
+        let mut dist = TreeNodeWeight::<Self>::zero();
+        let path = self.node_to_root_ids(start_leaf).collect_vec();
+        for (child_id, parent_id) in path.into_iter().tuple_windows() {
+            let edge_weight = edge_length(self, parent_id, child_id);
+            if dist + edge_weight >= half_dist || parent_id == lca {
+                return root_on_edge(self, (parent_id, child_id), half_dist - dist);
+            }
+            dist = dist + edge_weight;
+        }
+        Ok(())
+    }
+
+    /// Roots tree on the edge separating an outgroup from the remaining taxa. Returns Err if the outgroup is not monophyletic.
+    fn outgroup_root(&mut self, outgroup: &[TreeNodeMeta<Self>]) -> Result<(), RootingError> {
+        let outgroup_ids = outgroup
+            .iter()
+            .map(|taxa| {
+                self.get_taxa_node_id(taxa)
+                    .ok_or_else(|| RootingError::MissingTaxa {
+                        taxa: taxa.to_string(),
+                    })
+            })
+            .collect::<Result<HashSet<_>, _>>()?;
+        let ingroup_ids = self
+            .get_leaf_ids()
+            .filter(|id| !outgroup_ids.contains(id))
+            .collect_vec();
+        if outgroup_ids.is_empty() || ingroup_ids.is_empty() {
+            return Err(RootingError::NonMonophyletic);
+        }
+        self.precompute_constant_time_lca();
+        let root_id = self.get_root_id();
+
+        // either the outgroup or its complement is a cluster in the current rooting
+        for group in [outgroup_ids.iter().copied().collect_vec(), ingroup_ids] {
+            let lca = self.get_lca_id(group.as_slice());
+            if lca != root_id && self.get_cluster_size(lca) == group.len() {
+                let parent_id = self.get_node_parent_id(lca).unwrap();
+                if parent_id == root_id && self.get_root().num_children() == 2 {
+                    // already rooted on this edge
+                    return Ok(());
+                }
+                return self.reroot_at_edge((parent_id, lca));
+            }
+        }
+        Err(RootingError::NonMonophyletic)
+    }
+
+    /// Roots tree by the minimal ancestor deviation (MAD) criterion using the naive algorithm (\Theta(n^3)). Returns the ancestor deviation of the chosen root. Missing edge weights are counted as one.
+    fn mad_root(&mut self) -> Result<TreeNodeWeight<Self>, RootingError> {
+        let leaf_ids = self.get_leaf_ids().collect_vec();
+        if leaf_ids.len() < 3 {
+            return Err(RootingError::TooFewLeaves { min: 3 });
+        }
+        let leaf_dists = leaf_distances(self, &leaf_ids);
+        let root_id = self.get_root_id();
+        let root_children = self.get_node_children_ids(root_id).collect_vec();
+        let two = TreeNodeWeight::<Self>::one() + TreeNodeWeight::<Self>::one();
+
+        // candidate edges as (child side endpoint, parent side endpoint, length).
+        // the two edges of a bifurcating root form a single candidate edge.
+        let mut candidates = vec![];
+        for node_id in self.get_node_ids() {
+            let Some(parent_id) = self.get_node_parent_id(node_id) else {
+                continue;
+            };
+            let weight = edge_length(self, parent_id, node_id);
+            if parent_id == root_id && root_children.len() == 2 {
+                if node_id == root_children[0] {
+                    let sibling_weight = edge_length(self, root_id, root_children[1]);
+                    candidates.push((node_id, root_children[1], weight + sibling_weight));
+                }
+                continue;
+            }
+            candidates.push((node_id, parent_id, weight));
+        }
+
+        let mut best: Option<(TreeNodeWeight<Self>, usize, TreeNodeWeight<Self>)> = None;
+        for (idx, (node_id, other_id, weight)) in candidates.iter().enumerate() {
+            let side = self
+                .get_cluster_ids(*node_id)
+                .filter(|id| leaf_dists.contains_key(id))
+                .collect::<HashSet<_>>();
+            let other_side = leaf_ids.iter().filter(|id| !side.contains(id)).collect_vec();
+            let node_dists = &leaf_dists[node_id];
+            let other_dists = &leaf_dists[other_id];
+            let leaf_idx: HashMap<_, _> = leaf_ids.iter().enumerate().map(|(i, id)| (id, i)).collect();
+
+            let mut num_pairs = TreeNodeWeight::<Self>::zero();
+            let mut sq_dev = TreeNodeWeight::<Self>::zero();
+            // pairs on the same side of the edge have the same ancestor wherever the root is placed on the edge
+            for (dists, group) in [
+                (node_dists, side.iter().collect_vec()),
+                (other_dists, other_side.clone()),
+            ] {
+                for (x, y) in group.into_iter().tuple_combinations() {
+                    let pair_dist = leaf_dists[x][leaf_idx[y]];
+                    if pair_dist > TreeNodeWeight::<Self>::zero() {
+                        let dev = (dists[leaf_idx[x]] - dists[leaf_idx[y]]) / pair_dist;
+                        sq_dev = sq_dev + dev * dev;
+                        num_pairs = num_pairs + TreeNodeWeight::<Self>::one();
+                    }
+                }
+            }
+            // pairs separated by the edge determine the optimal root position on the edge
+            let spanning_pairs = side
+                .iter()
+                .cartesian_product(other_side.iter())
+                .filter_map(|(x, y)| {
+                    let pair_dist = leaf_dists[x][leaf_idx[*y]];
+                    (pair_dist > TreeNodeWeight::<Self>::zero())
+                        .then_some((node_dists[leaf_idx[x]], pair_dist))
+                })
+                .collect_vec();
+            let offset = match *weight > TreeNodeWeight::<Self>::zero() && !spanning_pairs.is_empty() {
+                true => {
+                    let numerator: TreeNodeWeight<Self> = spanning_pairs
+                        .iter()
+                        .map(|(d_x, d_xy)| (*d_xy - two * *d_x) / (*d_xy * *d_xy))
+                        .sum();
+                    let denominator: TreeNodeWeight<Self> = spanning_pairs
+                        .iter()
+                        .map(|(_, d_xy)| two / (*d_xy * *d_xy))
+                        .sum();
+                    (numerator / denominator).max(TreeNodeWeight::<Self>::zero()).min(*weight)
+                }
+                false => *weight / two,
+            };
+            for (d_x, d_xy) in spanning_pairs {
+                let dev = two * (d_x + offset) / d_xy - TreeNodeWeight::<Self>::one();
+                sq_dev = sq_dev + dev * dev;
+                num_pairs = num_pairs + TreeNodeWeight::<Self>::one();
+            }
+            let deviation = match num_pairs > TreeNodeWeight::<Self>::zero() {
+                true => (sq_dev / num_pairs).sqrt(),
+                false => TreeNodeWeight::<Self>::zero(),
+            };
+            if best.is_none_or(|(min_dev, _, _)| deviation < min_dev) {
+                best = Some((deviation, idx, offset));
+            }
+        }
+
+        let (deviation, idx, offset) = best.unwrap();
+        let (node_id, other_id, _) = candidates[idx];
+        match self.get_node_parent_id(node_id) == Some(other_id) {
+            true => root_on_edge(self, (other_id, node_id), offset)?,
+            false => {
+                // candidate edge spans both edges of the root
+                let weight = edge_length(self, root_id, node_id);
+                match offset <= weight {
+                    true => root_on_edge(self, (root_id, node_id), offset)?,
+                    false => {
+                        let total = weight + edge_length(self, root_id, other_id);
+                        root_on_edge(self, (root_id, other_id), total - offset)?
+                    }
+                }
+            }
+        };
+        Ok(deviation)
+    }
+}
+
+/// Returns the length of an edge, counting missing edge weights as one
+fn edge_length<T>(tree: &T, parent_id: TreeNodeID<T>, child_id: TreeNodeID<T>) -> TreeNodeWeight<T>
+where
+    T: RootedWeightedTree,
+    <T as RootedTree>::Node: RootedWeightedNode,
+{
+    tree.get_edge_weight(parent_id, child_id)
+        .unwrap_or(TreeNodeWeight::<T>::one())
+}
+
+/// Returns the distance from every node in a tree to each of the provided leaves
+fn leaf_distances<T>(
+    tree: &T,
+    leaf_ids: &[TreeNodeID<T>],
+) -> HashMap<TreeNodeID<T>, Vec<TreeNodeWeight<T>>>
+where
+    T: RootedWeightedTree,
+    <T as RootedTree>::Node: RootedWeightedNode,
+{
+    let mut dists: HashMap<TreeNodeID<T>, Vec<TreeNodeWeight<T>>> = tree
+        .get_node_ids()
+        .map(|id| (id, vec![TreeNodeWeight::<T>::zero(); leaf_ids.len()]))
+        .collect();
+    for (idx, leaf_id) in leaf_ids.iter().enumerate() {
+        let mut stack = vec![(*leaf_id, None)];
+        while let Some((node_id, prev_id)) = stack.pop() {
+            let node = tree.get_node(node_id).unwrap();
+            for neighbour_id in node.neighbours() {
+                if Some(neighbour_id) == prev_id {
+                    continue;
+                }
+                let edge_weight = match node.get_parent() == Some(neighbour_id) {
+                    true => edge_length(tree, neighbour_id, node_id),
+                    false => edge_length(tree, node_id, neighbour_id),
+                };
+                let dist = dists[&node_id][idx] + edge_weight;
+                dists.get_mut(&neighbour_id).unwrap()[idx] = dist;
+                stack.push((neighbour_id, Some(node_id)));
+            }
+        }
+    }
+    dists
+}
+
+/// Roots a tree on an edge so that the root is at a distance of `offset` from the child of the edge
+fn root_on_edge<T>(
+    tree: &mut T,
+    edge: (TreeNodeID<T>, TreeNodeID<T>),
+    offset: TreeNodeWeight<T>,
+) -> Result<(), RootingError>
+where
+    T: for<'a> Reroot<'a> + RootedWeightedTree,
+    <T as RootedTree>::Node: RootedWeightedNode,
+{
+    let (parent_id, child_id) = edge;
+    let weight = edge_length(tree, parent_id, child_id);
+    tree.set_edge_weight(edge, Some(weight));
+    tree.reroot_at_edge(edge)?;
+    // rerooting places the root halfway along the edge, with any suppressed edge merged into the other side
+    let root_id = tree.get_root_id();
+    let half_weight = tree.get_edge_weight(root_id, child_id).unwrap();
+    let other_id = tree
+        .get_node_children_ids(root_id)
+        .find(|id| *id != child_id)
+        .unwrap();
+    let other_weight = tree
+        .get_edge_weight(root_id, other_id)
+        .unwrap_or(TreeNodeWeight::<T>::zero());
+    tree.set_edge_weight((root_id, child_id), Some(offset));
+    tree.set_edge_weight((root_id, other_id), Some(other_weight + half_weight - offset));
+    Ok(())
+}
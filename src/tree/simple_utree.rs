@@ -0,0 +1,229 @@
+use crate::node::simple_unode::*;
+use itertools::Itertools;
+use std::fmt::Debug;
+
+/// A type alias for Unrooted Tree Node ID
+pub type UTreeNodeID<T> = <<T as UnrootedTree>::Node as UnrootedTreeNode>::NodeID;
+/// A type alias for Unrooted Tree Node meta annotation
+pub type UTreeNodeMeta<T> = <<T as UnrootedTree>::Node as UnrootedMetaNode>::Meta;
+/// A type alias for Unrooted Tree edge weight
+pub type UTreeNodeWeight<T> = <<T as UnrootedTree>::Node as UnrootedWeightedNode>::Weight;
+
+/// A trait describing the behaviour of an unrooted tree
+#[allow(clippy::needless_lifetimes)]
+pub trait UnrootedTree: Clone + Sync
+where
+    Self::Node: UnrootedTreeNode + Debug,
+{
+    /// An associated node type for an unrooted tree
+    type Node;
+
+    /// Creates new empty tree
+    fn new() -> Self;
+
+    /// Creates empty tree with specified capacity
+    fn with_capacity(cap: usize) -> Self;
+
+    /// Creates tree from a vec of nodes indexed by NodeID
+    fn from_nodes(nodes: Vec<Option<Self::Node>>) -> Self;
+
+    /// Returns reference to node by ID
+    fn get_node<'a>(&'a self, node_id: UTreeNodeID<Self>) -> Option<&'a Self::Node>;
+
+    /// Returns a mutable reference to a node
+    fn get_node_mut<'a>(&'a mut self, node_id: UTreeNodeID<Self>) -> Option<&'a mut Self::Node>;
+
+    /// Reurns an iterator over all NodeID's
+    fn get_node_ids(&self) -> impl Iterator<Item = UTreeNodeID<Self>>;
+
+    /// Returns an iterator with immutable references to nodes
+    fn get_nodes<'a>(&'a self) -> impl ExactSizeIterator<Item = &'a Self::Node> {
+        self.get_node_ids()
+            .map(|id| self.get_node(id).unwrap())
+            .collect_vec()
+            .into_iter()
+    }
+
+    /// Inserts a floating node into tree.
+    fn set_node(&mut self, node: Self::Node);
+
+    /// Removes node from tree while deleting any edges if they exist
+    fn remove_node(&mut self, node_id: UTreeNodeID<Self>) -> Option<Self::Node>;
+
+    /// Returns true if node with node_id exists in tree
+    fn contains_node(&self, node_id: UTreeNodeID<Self>) -> bool {
+        self.get_node(node_id).is_some()
+    }
+
+    /// Creates an edge between two nodes. Both nodes must already exist in tree.
+    fn set_edge(&mut self, node_1: UTreeNodeID<Self>, node_2: UTreeNodeID<Self>) {
+        self.get_node_mut(node_1).unwrap().add_neighbour(node_2);
+        self.get_node_mut(node_2).unwrap().add_neighbour(node_1);
+    }
+
+    /// Removes edge between two nodes without deleting either node.
+    fn remove_edge(&mut self, node_1: UTreeNodeID<Self>, node_2: UTreeNodeID<Self>) {
+        self.get_node_mut(node_1)
+            .unwrap()
+            .remove_neighbour(&node_2);
+        self.get_node_mut(node_2)
+            .unwrap()
+            .remove_neighbour(&node_1);
+    }
+
+    /// Adds node as a neighbour of an existing node in tree.
+    fn add_neighbour(&mut self, node_id: UTreeNodeID<Self>, neighbour: Self::Node) {
+        let neighbour_id = neighbour.get_id();
+        self.set_node(neighbour);
+        self.set_edge(node_id, neighbour_id);
+    }
+
+    /// Returns an iterator of neighbour ids of a node
+    fn get_node_neighbour_ids(
+        &self,
+        node_id: UTreeNodeID<Self>,
+    ) -> impl ExactSizeIterator<Item = UTreeNodeID<Self>> {
+        self.get_node(node_id).unwrap().get_neighbours()
+    }
+
+    /// Returns an iterator over all edges in the tree. Each edge is reported once, with the smaller NodeID first.
+    fn get_edges(&self) -> impl Iterator<Item = (UTreeNodeID<Self>, UTreeNodeID<Self>)> {
+        self.get_node_ids()
+            .flat_map(|id| {
+                self.get_node_neighbour_ids(id)
+                    .filter(move |n_id| id < *n_id)
+                    .map(move |n_id| (id, n_id))
+            })
+            .collect_vec()
+            .into_iter()
+    }
+
+    /// Returns number of edges in tree
+    fn num_edges(&self) -> usize {
+        self.get_edges().count()
+    }
+
+    /// Returns degree of a node
+    fn node_degree(&self, node_id: UTreeNodeID<Self>) -> usize {
+        self.get_node(node_id).unwrap().degree()
+    }
+
+    /// Returns true if node with node_id is a leaf node
+    fn is_leaf(&self, node_id: UTreeNodeID<Self>) -> bool {
+        self.get_node(node_id).unwrap().is_leaf()
+    }
+
+    /// Returns an iterator of leaf NodeID's
+    fn get_leaf_ids(&self) -> impl ExactSizeIterator<Item = UTreeNodeID<Self>> {
+        self.get_node_ids()
+            .filter(|x| self.is_leaf(*x))
+            .collect_vec()
+            .into_iter()
+    }
+
+    /// Returns total number of nodes in tree
+    fn num_nodes(&self) -> usize {
+        self.get_node_ids().count()
+    }
+
+    /// Returns true if every internal node has degree 3
+    fn is_binary(&self) -> bool {
+        self.get_node_ids()
+            .all(|id| self.is_leaf(id) || self.node_degree(id) == 3)
+    }
+
+    /// Splits an edge in the tree with provided node.
+    fn split_edge(&mut self, edge: (UTreeNodeID<Self>, UTreeNodeID<Self>), node: Self::Node) {
+        let n_id = node.get_id();
+        self.set_node(node);
+        self.remove_edge(edge.0, edge.1);
+        self.set_edge(edge.0, n_id);
+        self.set_edge(n_id, edge.1);
+    }
+
+    /// Removes a node of degree 2, connecting its two neighbours. Returns None if node does not have degree 2.
+    fn supress_node(&mut self, node_id: UTreeNodeID<Self>) -> Option<()> {
+        let neighbour_ids = self.get_node_neighbour_ids(node_id).collect_vec();
+        if neighbour_ids.len() != 2 {
+            return None;
+        }
+        self.remove_node(node_id);
+        self.set_edge(neighbour_ids[0], neighbour_ids[1]);
+        Some(())
+    }
+}
+
+/// A trait describing the behaviour of an unrooted tree where some of the nodes have a meta annotation. The terms meta and taxa are used interchangably here.
+#[allow(clippy::needless_lifetimes)]
+pub trait UnrootedMetaTree: UnrootedTree
+where
+    Self::Node: UnrootedMetaNode,
+{
+    ///  Returns an immutable reference to a node with a give meta annotation
+    fn get_taxa_node<'a>(&'a self, taxa: &UTreeNodeMeta<Self>) -> Option<&'a Self::Node>;
+
+    /// Returns the node id of a node with a meta annotation
+    fn get_taxa_node_id(&self, taxa: &UTreeNodeMeta<Self>) -> Option<UTreeNodeID<Self>> {
+        Some(self.get_taxa_node(taxa)?.get_id())
+    }
+
+    /// Returns total number of nodes with a meta annotation
+    fn num_taxa(&self) -> usize;
+
+    /// Sets the meta annotation of a node
+    fn set_node_taxa(&mut self, node_id: UTreeNodeID<Self>, taxa: Option<UTreeNodeMeta<Self>>);
+
+    /// Returns an immutable reference to the meta annotation of a node, and None is there is no meta annotation
+    fn get_node_taxa<'a>(&'a self, node_id: UTreeNodeID<Self>) -> Option<&'a UTreeNodeMeta<Self>> {
+        self.get_node(node_id).unwrap().get_taxa()
+    }
+
+    /// Returns an iterator with immutable references to all meta annotations in a tree.
+    fn get_taxa_space<'a>(&'a self) -> impl ExactSizeIterator<Item = &'a UTreeNodeMeta<Self>>;
+}
+
+/// A trait describing the behaviour of an unrooted tree where some of the edges are weighted
+#[allow(clippy::needless_lifetimes)]
+pub trait UnrootedWeightedTree: UnrootedTree
+where
+    Self::Node: UnrootedWeightedNode,
+{
+    /// Returns weight of edge
+    fn get_edge_weight(
+        &self,
+        node_1: UTreeNodeID<Self>,
+        node_2: UTreeNodeID<Self>,
+    ) -> Option<UTreeNodeWeight<Self>> {
+        self.get_node(node_1)
+            .unwrap()
+            .get_neighbour_weight(&node_2)
+    }
+
+    /// Sets weight of edge
+    fn set_edge_weight(
+        &mut self,
+        edge: (UTreeNodeID<Self>, UTreeNodeID<Self>),
+        edge_weight: Option<UTreeNodeWeight<Self>>,
+    ) {
+        self.get_node_mut(edge.0)
+            .unwrap()
+            .set_neighbour_weight(&edge.1, edge_weight);
+        self.get_node_mut(edge.1)
+            .unwrap()
+            .set_neighbour_weight(&edge.0, edge_weight);
+    }
+
+    /// Sets all edge weights to None
+    fn unweight(&mut self) {
+        let ids = self.get_node_ids().collect_vec();
+        for id in ids {
+            self.get_node_mut(id).unwrap().unweight();
+        }
+    }
+
+    /// Returns true if all edges are weighted
+    fn is_weighted(&self) -> bool {
+        self.get_edges()
+            .all(|(x, y)| self.get_edge_weight(x, y).is_some())
+    }
+}
@@ -1,7 +1,13 @@
-use crate::{
-    node::simple_rnode::RootedMetaNode,
-    prelude::{RootedMetaTree, RootedTree},
-};
+use num::NumCast;
+use rand::{distributions::Distribution, Rng};
+use std::str::FromStr;
+
+#[cfg(feature = "non_crypto_hash")]
+use fxhash::FxHashMap as HashMap;
+#[cfg(not(feature = "non_crypto_hash"))]
+use std::collections::HashMap;
+
+use crate::prelude::*;
 
 /// A trait describing generation of a random binary tree under the Yule model.
 pub trait Yule: RootedMetaTree
@@ -9,7 +15,12 @@ where
     <Self as RootedTree>::Node: RootedMetaNode,
 {
     /// Generate a random binary tree under the Yule model with num_taxa
-    fn yule(num_taxa: usize) -> Self;
+    fn yule(num_taxa: usize) -> Self {
+        Self::yule_with_rng(num_taxa, &mut rand::thread_rng())
+    }
+
+    /// Generate a random binary tree under the Yule model with num_taxa using a given random number generator
+    fn yule_with_rng<R: Rng + ?Sized>(num_taxa: usize, rng: &mut R) -> Self;
 }
 
 /// A trait describing generation of a random binary tree under the Uniform model.
@@ -18,5 +29,796 @@ where
     <Self as RootedTree>::Node: RootedMetaNode,
 {
     /// Generate a random binary tree under the Uniform model with num_taxa
-    fn unif(num_taxa: usize) -> Self;
+    fn unif(num_taxa: usize) -> Self {
+        Self::unif_with_rng(num_taxa, &mut rand::thread_rng())
+    }
+
+    /// Generate a random binary tree under the Uniform model with num_taxa using a given random number generator
+    fn unif_with_rng<R: Rng + ?Sized>(num_taxa: usize, rng: &mut R) -> Self;
+}
+
+/// A trait describing relabelling of simulated trees, whose leaves are labelled by the integers 0 to n-1.
+pub trait SimulatedTaxa: RootedMetaTree
+where
+    <Self as RootedTree>::Node: RootedMetaNode,
+{
+    /// Replaces the taxa of each leaf labelled by an integer i with the taxa at position i. Returns Err if a leaf is not labelled by an integer less than the number of taxa given.
+    fn set_simulated_taxa(&mut self, taxa: &[TreeNodeMeta<Self>]) -> Result<(), SimulationError> {
+        let leaf_taxa = self
+            .get_leaf_ids()
+            .map(|leaf_id| {
+                self.get_node_taxa(leaf_id)
+                    .and_then(|label| label.to_string().parse::<usize>().ok())
+                    .and_then(|idx| taxa.get(idx))
+                    .map(|new_taxa| (leaf_id, new_taxa.clone()))
+                    .ok_or(SimulationError::InvalidParameter { name: "taxa".to_string() })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        // old labels are cleared first since they may coincide with new labels of other leaves
+        for (leaf_id, _) in leaf_taxa.iter() {
+            self.set_node_taxa(*leaf_id, None);
+        }
+        for (leaf_id, new_taxa) in leaf_taxa {
+            self.set_node_taxa(leaf_id, Some(new_taxa));
+        }
+        Ok(())
+    }
+}
+
+/// A trait describing assignment of random edge weights to trees.
+pub trait RandomEdgeWeights: RootedWeightedTree
+where
+    <Self as RootedTree>::Node: RootedWeightedNode,
+{
+    /// Sets the weight of every edge to an independent draw from a distribution, using a given random number generator
+    fn set_random_edge_weights<D, R>(&mut self, distribution: &D, rng: &mut R)
+    where
+        D: Distribution<f64>,
+        R: Rng + ?Sized,
+    {
+        let node_ids = self.get_node_ids().collect::<Vec<_>>();
+        for node_id in node_ids {
+            if let Some(parent_id) = self.get_node_parent_id(node_id) {
+                let weight = <TreeNodeWeight<Self> as NumCast>::from(distribution.sample(rng));
+                self.set_edge_weight((parent_id, node_id), weight);
+            }
+        }
+    }
+
+    /// Sets the weight of every edge to an independent exponentially distributed draw with a given rate, using a given random number generator
+    fn set_exponential_edge_weights<R: Rng + ?Sized>(&mut self, rate: f64, rng: &mut R) -> Result<(), SimulationError> {
+        if !(rate > 0.0 && rate.is_finite()) {
+            return Err(SimulationError::InvalidParameter { name: "rate".to_string() });
+        }
+        self.set_random_edge_weights(&Exponential(rate), rng);
+        Ok(())
+    }
+}
+
+/// Exponential distribution with a given rate
+struct Exponential(f64);
+
+impl Distribution<f64> for Exponential {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> f64 {
+        exponential(self.0, rng)
+    }
+}
+
+/// Returns an exponentially distributed waiting time, which is infinite if rate is zero
+pub(crate) fn exponential<R: Rng + ?Sized>(rate: f64, rng: &mut R) -> f64 {
+    match rate > 0.0 {
+        true => -(1.0 - rng.gen::<f64>()).ln() / rate,
+        false => f64::INFINITY,
+    }
+}
+
+/// A rooted tree with the age of each node before the present, built up by simulations
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TimedTree {
+    pub(crate) ages: Vec<f64>,
+    pub(crate) parents: Vec<Option<usize>>,
+    pub(crate) children: Vec<Vec<usize>>,
+    pub(crate) labels: Vec<Option<String>>,
+}
+
+impl TimedTree {
+    pub(crate) fn add_node(&mut self, age: f64) -> usize {
+        self.ages.push(age);
+        self.parents.push(None);
+        self.children.push(vec![]);
+        self.labels.push(None);
+        self.ages.len() - 1
+    }
+
+    pub(crate) fn add_child(&mut self, parent: usize, child: usize) {
+        self.parents[child] = Some(parent);
+        self.children[parent].push(child);
+    }
+
+    /// Inserts a new node of a given age on the edge above child, returning the new node
+    pub(crate) fn split_edge(&mut self, child: usize, age: f64) -> usize {
+        let parent = self.parents[child].unwrap();
+        let node = self.add_node(age);
+        self.children[parent].iter_mut().filter(|c| **c == child).for_each(|c| *c = node);
+        self.parents[node] = Some(parent);
+        self.add_child(node, child);
+        node
+    }
+
+    /// Returns the leaves of the subtree below node
+    pub(crate) fn leaves(&self, node: usize) -> Vec<usize> {
+        let mut leaves = vec![];
+        let mut stack = vec![node];
+        while let Some(u) = stack.pop() {
+            match self.children[u].is_empty() {
+                true => leaves.push(u),
+                false => stack.extend(self.children[u].iter()),
+            }
+        }
+        leaves
+    }
+
+    /// Builds the tree below root restricted to the leaves that are kept, suppressing nodes left with a single child. Node ages are set as zeta values, edge weights are set to differences of ages and leaves are labelled by parsing their labels.
+    pub(crate) fn build_tree<T>(&self, root: usize, keep: impl Fn(usize) -> bool) -> T
+    where
+        T: RootedMetaTree + RootedWeightedTree + PathFunction,
+        <T as RootedTree>::Node: RootedMetaNode + RootedWeightedNode + RootedZetaNode,
+        TreeNodeID<T>: From<usize>,
+    {
+        let mut kept = vec![false; self.ages.len()];
+        let mut order = vec![root];
+        let mut idx = 0;
+        while idx < order.len() {
+            order.extend(self.children[order[idx]].iter());
+            idx += 1;
+        }
+        for u in order.into_iter().rev() {
+            kept[u] = match self.children[u].is_empty() {
+                true => keep(u),
+                false => self.children[u].iter().any(|c| kept[*c]),
+            };
+        }
+        let kept_children = |u: usize| self.children[u].iter().copied().filter(|c| kept[*c]).collect::<Vec<_>>();
+        let mut root = root;
+        while let [child] = kept_children(root).as_slice() {
+            root = *child;
+        }
+
+        let mut tree = <T as RootedTree>::new();
+        let root_id = tree.get_root_id();
+        tree.set_node_zeta(root_id, <TreeNodeZeta<T> as NumCast>::from(self.ages[root]));
+        if let Some(label) = self.labels[root].as_ref() {
+            tree.set_node_taxa(root_id, TreeNodeMeta::<T>::from_str(label).ok());
+        }
+        let mut new_ids = (0..).map(TreeNodeID::<T>::from).filter(|id| *id != root_id);
+        let mut stack = kept_children(root)
+            .into_iter()
+            .map(|c| (root_id, self.ages[root], c))
+            .collect::<Vec<_>>();
+        while let Some((parent_id, parent_age, u)) = stack.pop() {
+            if let [child] = kept_children(u).as_slice() {
+                stack.push((parent_id, parent_age, *child));
+                continue;
+            }
+            let mut node = <T as RootedTree>::Node::new(new_ids.next().unwrap());
+            node.set_weight(<TreeNodeWeight<T> as NumCast>::from(parent_age - self.ages[u]));
+            node.set_zeta(<TreeNodeZeta<T> as NumCast>::from(self.ages[u]));
+            if let Some(label) = self.labels[u].as_ref() {
+                node.set_taxa(TreeNodeMeta::<T>::from_str(label).ok());
+            }
+            let node_id = node.get_id();
+            tree.add_child(parent_id, node);
+            stack.extend(kept_children(u).into_iter().map(|c| (node_id, self.ages[u], c)));
+        }
+        tree
+    }
+}
+
+/// Rates of a constant-rate birth-death process, with extant lineages sampled independently at the present
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BirthDeathRates {
+    /// Rate of speciation of each lineage
+    pub birth_rate: f64,
+    /// Rate of extinction of each lineage
+    pub death_rate: f64,
+    /// Probability of sampling each extant lineage
+    pub sampling_fraction: f64,
+}
+
+impl BirthDeathRates {
+    /// Creates birth-death rates with every extant lineage sampled
+    pub fn new(birth_rate: f64, death_rate: f64) -> Self {
+        BirthDeathRates {
+            birth_rate,
+            death_rate,
+            sampling_fraction: 1.0,
+        }
+    }
+
+    /// Sets the probability of sampling each extant lineage
+    pub fn with_sampling_fraction(mut self, sampling_fraction: f64) -> Self {
+        self.sampling_fraction = sampling_fraction;
+        self
+    }
+
+    fn validate(&self) -> Result<(), SimulationError> {
+        if !(self.birth_rate >= 0.0 && self.birth_rate.is_finite()) {
+            return Err(SimulationError::InvalidParameter { name: "birth rate".to_string() });
+        }
+        if !(self.death_rate >= 0.0 && self.death_rate.is_finite()) {
+            return Err(SimulationError::InvalidParameter { name: "death rate".to_string() });
+        }
+        if !(self.sampling_fraction > 0.0 && self.sampling_fraction <= 1.0) {
+            return Err(SimulationError::InvalidParameter { name: "sampling fraction".to_string() });
+        }
+        Ok(())
+    }
+
+    /// Returns the probability that a lineage of a given age leaves no sampled descendants at the present
+    fn extinction_probability(&self, age: f64) -> f64 {
+        let (lambda, rho) = (self.birth_rate, self.sampling_fraction);
+        let r = lambda - self.death_rate;
+        match r.abs() < 1e-12 {
+            true => 1.0 - rho / (1.0 + rho * lambda * age),
+            false => 1.0 - rho * r / (rho * lambda + (lambda * (1.0 - rho) - self.death_rate) * (-r * age).exp()),
+        }
+    }
+
+    /// Returns the age of a node of the reconstructed tree with a given quantile, where ages are independent and identically distributed given the number of sampled taxa under a uniform prior on the time of origin
+    fn node_age(&self, quantile: f64) -> f64 {
+        let a = self.birth_rate * self.sampling_fraction;
+        let c = self.birth_rate * (1.0 - self.sampling_fraction) - self.death_rate;
+        let r = self.birth_rate - self.death_rate;
+        match r.abs() < 1e-12 {
+            true => quantile / (a * (1.0 - quantile)),
+            false => {
+                // ages are only bounded when lineages are expected to die out, so quantiles are scaled to the total mass of ages
+                let total = match r > 0.0 {
+                    true => 1.0,
+                    false => -a / c,
+                };
+                let q = quantile * total;
+                ((a + q * c) / (a * (1.0 - q))).ln() / r
+            }
+        }
+    }
+
+    /// Adds a subtree of lineages that leave no sampled descendants, descending from a lineage starting at node, and returns the leaves that are extinct.
+    fn add_unsampled_subtree<R: Rng + ?Sized>(&self, tree: &mut TimedTree, node: usize, rng: &mut R) -> Vec<usize> {
+        loop {
+            let mut subtree = TimedTree::default();
+            let mut extinct = vec![];
+            let mut stack = vec![(None, tree.ages[node])];
+            let mut sampled = false;
+            // lineages are followed depth first, so that subtrees with sampled descendants are rejected early
+            while let Some((parent, start)) = stack.pop() {
+                let end = (start - exponential(self.death_rate, rng)).max(0.0);
+                let mut prev = parent;
+                let mut age = start - exponential(self.birth_rate, rng);
+                while age > end {
+                    let split = subtree.add_node(age);
+                    if let Some(p) = prev {
+                        subtree.add_child(p, split);
+                    }
+                    stack.push((Some(split), age));
+                    prev = Some(split);
+                    age -= exponential(self.birth_rate, rng);
+                }
+                let leaf = subtree.add_node(end);
+                if let Some(p) = prev {
+                    subtree.add_child(p, leaf);
+                }
+                match end > 0.0 {
+                    true => extinct.push(leaf),
+                    false if rng.gen::<f64>() < self.sampling_fraction => {
+                        sampled = true;
+                        break;
+                    }
+                    false => {}
+                }
+            }
+            if sampled {
+                continue;
+            }
+            // the first lineage of the subtree descends from node
+            let offset = tree.ages.len();
+            for u in 0..subtree.ages.len() {
+                let v = tree.add_node(subtree.ages[u]);
+                match subtree.parents[u] {
+                    Some(p) => tree.add_child(p + offset, v),
+                    None => tree.add_child(node, v),
+                }
+            }
+            return extinct.into_iter().map(|u| u + offset).collect();
+        }
+    }
+}
+
+/// Selects what a birth-death tree is conditioned on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BirthDeathCondition {
+    /// Number of sampled extant taxa, under a uniform prior on the time of origin
+    NumTaxa(usize),
+    /// Age of the root, with both lineages descending from the root leaving sampled extant taxa
+    CrownAge(f64),
+}
+
+/// A trait describing generation of random trees under a constant-rate birth-death process with incomplete sampling.
+///
+/// Nodes are annotated with their ages before the present as zeta values, and edge weights are set to differences of ages. Sampled extant taxa are labelled 0 to n-1 and extinct taxa with the integers that follow. Extant lineages that are not sampled are removed.
+pub trait BirthDeath: RootedMetaTree + RootedWeightedTree + PathFunction
+where
+    <Self as RootedTree>::Node: RootedMetaNode + RootedWeightedNode + RootedZetaNode,
+    TreeNodeID<Self>: From<usize>,
+{
+    /// Generates a random birth-death tree. If prune_extinct is true, extinct lineages are removed, resulting in an ultrametric tree.
+    fn birth_death(
+        rates: BirthDeathRates,
+        condition: BirthDeathCondition,
+        prune_extinct: bool,
+    ) -> Result<Self, SimulationError> {
+        Self::birth_death_with_rng(rates, condition, prune_extinct, &mut rand::thread_rng())
+    }
+
+    /// Generates a random birth-death tree using a given random number generator. If prune_extinct is true, extinct lineages are removed, resulting in an ultrametric tree.
+    ///
+    /// Trees conditioned on the number of taxa are sampled without rejection, as a coalescent point process to which extinct subtrees are grafted. Trees conditioned on crown age are simulated forwards in time, rejecting trees where either lineage of the root leaves no sampled taxa.
+    fn birth_death_with_rng<R: Rng + ?Sized>(
+        rates: BirthDeathRates,
+        condition: BirthDeathCondition,
+        prune_extinct: bool,
+        rng: &mut R,
+    ) -> Result<Self, SimulationError> {
+        rates.validate()?;
+        let mut tree = TimedTree::default();
+        let (root, sampled, extinct) = match condition {
+            BirthDeathCondition::NumTaxa(num_taxa) => {
+                if num_taxa < 2 {
+                    return Err(SimulationError::InvalidParameter { name: "number of taxa".to_string() });
+                }
+                if rates.birth_rate <= 0.0 {
+                    return Err(SimulationError::InvalidParameter { name: "birth rate".to_string() });
+                }
+                let sampled = (0..num_taxa).map(|_| tree.add_node(0.0)).collect::<Vec<_>>();
+                // nodes waiting for their right subtree, in decreasing order of age
+                let mut stack: Vec<usize> = vec![];
+                let mut last = sampled[0];
+                for leaf in sampled.iter().skip(1) {
+                    let node = tree.add_node(rates.node_age(rng.gen::<f64>()));
+                    while let Some(top) = stack.last().copied() {
+                        if tree.ages[top] >= tree.ages[node] {
+                            break;
+                        }
+                        stack.pop();
+                        tree.add_child(top, last);
+                        last = top;
+                    }
+                    tree.add_child(node, last);
+                    stack.push(node);
+                    last = *leaf;
+                }
+                while let Some(top) = stack.pop() {
+                    tree.add_child(top, last);
+                    last = top;
+                }
+                let mut extinct = vec![];
+                if !prune_extinct {
+                    // lineages branch off into subtrees without sampled descendants at a rate proportional to the probability of leaving no sampled descendants
+                    for node in (0..tree.ages.len()).filter(|u| *u != last) {
+                        let mut age = tree.ages[tree.parents[node].unwrap()] - exponential(2.0 * rates.birth_rate, rng);
+                        while age > tree.ages[node] {
+                            if rng.gen::<f64>() < rates.extinction_probability(age) {
+                                let split = tree.split_edge(node, age);
+                                extinct.extend(rates.add_unsampled_subtree(&mut tree, split, rng));
+                            }
+                            age -= exponential(2.0 * rates.birth_rate, rng);
+                        }
+                    }
+                }
+                (last, sampled, extinct)
+            }
+            BirthDeathCondition::CrownAge(crown_age) => {
+                if !(crown_age > 0.0 && crown_age.is_finite()) {
+                    return Err(SimulationError::InvalidParameter { name: "crown age".to_string() });
+                }
+                let total_rate = rates.birth_rate + rates.death_rate;
+                loop {
+                    tree = TimedTree::default();
+                    let root = tree.add_node(crown_age);
+                    // nodes from which living lineages descend
+                    let mut alive = vec![root, root];
+                    let mut extinct = vec![];
+                    let mut time = 0.0;
+                    while !alive.is_empty() {
+                        time += exponential(alive.len() as f64 * total_rate, rng);
+                        if time >= crown_age {
+                            break;
+                        }
+                        let parent = alive.swap_remove(rng.gen_range(0..alive.len()));
+                        let node = tree.add_node(crown_age - time);
+                        tree.add_child(parent, node);
+                        match rng.gen::<f64>() * total_rate < rates.birth_rate {
+                            true => alive.extend([node, node]),
+                            false => extinct.push(node),
+                        }
+                    }
+                    let mut sampled = vec![];
+                    for parent in alive {
+                        let node = tree.add_node(0.0);
+                        tree.add_child(parent, node);
+                        if rng.gen::<f64>() < rates.sampling_fraction {
+                            sampled.push(node);
+                        }
+                    }
+                    let mut is_sampled = vec![false; tree.ages.len()];
+                    sampled.iter().for_each(|u| is_sampled[*u] = true);
+                    if tree.children[root]
+                        .iter()
+                        .all(|c| tree.leaves(*c).into_iter().any(|u| is_sampled[u]))
+                    {
+                        break (root, sampled, extinct);
+                    }
+                }
+            }
+        };
+        let mut keep = vec![false; tree.ages.len()];
+        for (idx, leaf) in sampled.iter().enumerate() {
+            tree.labels[*leaf] = Some(idx.to_string());
+            keep[*leaf] = true;
+        }
+        if !prune_extinct {
+            for (idx, leaf) in extinct.iter().enumerate() {
+                tree.labels[*leaf] = Some((sampled.len() + idx).to_string());
+                keep[*leaf] = true;
+            }
+        }
+        Ok(tree.build_tree(root, |u| keep[u]))
+    }
+}
+
+/// Size of a single population backwards in time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PopulationSize {
+    /// Constant effective population size
+    Constant(f64),
+    /// Effective population size growing exponentially towards the present, with size N(t) = size * exp(-growth_rate * t) at time t before the present
+    Exponential {
+        /// Effective population size at the present
+        size: f64,
+        /// Rate of growth of the population forwards in time
+        growth_rate: f64,
+    },
+}
+
+impl PopulationSize {
+    fn validate(&self) -> Result<(), SimulationError> {
+        let (size, growth_rate) = match self {
+            PopulationSize::Constant(size) => (*size, 0.0),
+            PopulationSize::Exponential { size, growth_rate } => (*size, *growth_rate),
+        };
+        if !(size > 0.0 && size.is_finite()) {
+            return Err(SimulationError::InvalidParameter { name: "population size".to_string() });
+        }
+        if !growth_rate.is_finite() {
+            return Err(SimulationError::InvalidParameter { name: "growth rate".to_string() });
+        }
+        Ok(())
+    }
+
+    /// Returns the waiting time from a given time until the next coalescence of num_lineages lineages, which is infinite if the lineages never coalesce
+    fn waiting_time<R: Rng + ?Sized>(&self, time: f64, num_lineages: usize, rng: &mut R) -> f64 {
+        let pairs = (num_lineages * (num_lineages - 1)) as f64 / 2.0;
+        let hazard = exponential(1.0, rng);
+        match *self {
+            PopulationSize::Constant(size) | PopulationSize::Exponential { size, growth_rate: 0.0 } => {
+                hazard * size / pairs
+            }
+            PopulationSize::Exponential { size, growth_rate } => {
+                // the integrated rate of coalescence reaches the hazard when exp(growth_rate * t) reaches this value
+                let scaled = (growth_rate * time).exp() + hazard * size * growth_rate / pairs;
+                match scaled > 0.0 {
+                    true => scaled.ln() / growth_rate - time,
+                    false => f64::INFINITY,
+                }
+            }
+        }
+    }
+}
+
+/// A population divided into demes with constant effective population sizes, between which lineages migrate
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructuredPopulation {
+    /// Effective population size of each deme
+    pub sizes: Vec<f64>,
+    /// Rate at which each lineage in a deme (row) migrates to another deme (column) backwards in time. Diagonal entries are ignored.
+    pub migration_rates: Vec<Vec<f64>>,
+}
+
+impl StructuredPopulation {
+    /// Creates a structured population from the sizes of demes and backwards migration rates between them
+    pub fn new(sizes: Vec<f64>, migration_rates: Vec<Vec<f64>>) -> Self {
+        StructuredPopulation {
+            sizes,
+            migration_rates,
+        }
+    }
+
+    /// Returns the number of demes
+    pub fn num_demes(&self) -> usize {
+        self.sizes.len()
+    }
+
+    fn validate(&self) -> Result<(), SimulationError> {
+        if self.sizes.is_empty() || self.sizes.iter().any(|size| !(*size > 0.0 && size.is_finite())) {
+            return Err(SimulationError::InvalidParameter { name: "population size".to_string() });
+        }
+        if self.migration_rates.len() != self.num_demes()
+            || self.migration_rates.iter().any(|row| {
+                row.len() != self.num_demes() || row.iter().any(|rate| !(*rate >= 0.0 && rate.is_finite()))
+            })
+        {
+            return Err(SimulationError::InvalidParameter { name: "migration rates".to_string() });
+        }
+        Ok(())
+    }
+}
+
+/// Returns a tree with one leaf per sampling time, labelled by position, along with the indices of samples from the most recent
+fn sampled_leaves(sampling_times: &[f64]) -> Result<(TimedTree, Vec<usize>), SimulationError> {
+    if sampling_times.len() < 2 {
+        return Err(SimulationError::InvalidParameter { name: "number of samples".to_string() });
+    }
+    if sampling_times.iter().any(|time| !(*time >= 0.0 && time.is_finite())) {
+        return Err(SimulationError::InvalidParameter { name: "sampling time".to_string() });
+    }
+    let mut tree = TimedTree::default();
+    for (idx, time) in sampling_times.iter().enumerate() {
+        let leaf = tree.add_node(*time);
+        tree.labels[leaf] = Some(idx.to_string());
+    }
+    let mut order = (0..sampling_times.len()).collect::<Vec<_>>();
+    order.sort_by(|x, y| sampling_times[*x].total_cmp(&sampling_times[*y]));
+    Ok((tree, order))
+}
+
+/// Returns an index picked at random with probability proportional to its weight, where at least one weight is positive
+fn weighted_index<R: Rng + ?Sized>(weights: impl Iterator<Item = f64> + Clone, rng: &mut R) -> usize {
+    let mut target = rng.gen::<f64>() * weights.clone().sum::<f64>();
+    let mut last = 0;
+    for (idx, weight) in weights.enumerate().filter(|(_, weight)| *weight > 0.0) {
+        target -= weight;
+        last = idx;
+        if target < 0.0 {
+            break;
+        }
+    }
+    last
+}
+
+/// Joins two lineages picked uniformly at random at a given time, replacing them by their common ancestor
+fn coalesce<R: Rng + ?Sized>(tree: &mut TimedTree, lineages: &mut Vec<usize>, time: f64, rng: &mut R) {
+    let first = lineages.swap_remove(rng.gen_range(0..lineages.len()));
+    let second = lineages.swap_remove(rng.gen_range(0..lineages.len()));
+    let node = tree.add_node(time);
+    tree.add_child(node, first);
+    tree.add_child(node, second);
+    lineages.push(node);
+}
+
+/// A trait describing generation of random genealogies under the coalescent.
+///
+/// Samples may be taken at different times before the present. Sampled taxa are labelled 0 to n-1 in the order their sampling times are given. Nodes are annotated with their heights before the present as zeta values, and edge weights are set to differences of heights. Time is measured in the same units as effective population sizes, such that each pair of lineages coalesces at rate 1/N(t).
+pub trait Coalescent: RootedMetaTree + RootedWeightedTree + PathFunction
+where
+    <Self as RootedTree>::Node: RootedMetaNode + RootedWeightedNode + RootedZetaNode,
+    TreeNodeID<Self>: From<usize>,
+{
+    /// Generates a random genealogy under the Kingman coalescent of samples taken at given times before the present.
+    fn coalescent(sampling_times: &[f64], population: PopulationSize) -> Result<Self, SimulationError> {
+        Self::coalescent_with_rng(sampling_times, population, &mut rand::thread_rng())
+    }
+
+    /// Generates a random genealogy under the Kingman coalescent of samples taken at given times before the present, using a given random number generator.
+    fn coalescent_with_rng<R: Rng + ?Sized>(
+        sampling_times: &[f64],
+        population: PopulationSize,
+        rng: &mut R,
+    ) -> Result<Self, SimulationError> {
+        population.validate()?;
+        let (mut tree, order) = sampled_leaves(sampling_times)?;
+        let mut pending = order.into_iter().peekable();
+        let mut lineages = vec![];
+        let mut time = 0.0;
+        loop {
+            let next_sample = pending.peek().map_or(f64::INFINITY, |leaf| tree.ages[*leaf]);
+            let waiting_time = match lineages.len() < 2 {
+                true => f64::INFINITY,
+                false => population.waiting_time(time, lineages.len(), rng),
+            };
+            if time + waiting_time < next_sample {
+                time += waiting_time;
+                coalesce(&mut tree, &mut lineages, time, rng);
+                if lineages.len() == 1 && pending.peek().is_none() {
+                    break;
+                }
+            } else if next_sample.is_finite() {
+                time = next_sample;
+                lineages.push(pending.next().unwrap());
+            } else {
+                // the population shrinks backwards in time too fast for lineages to coalesce
+                return Err(SimulationError::InvalidParameter { name: "growth rate".to_string() });
+            }
+        }
+        Ok(tree.build_tree(lineages[0], |_| true))
+    }
+
+    /// Generates a random genealogy under the structured coalescent of samples taken at given times before the present from given demes.
+    fn structured_coalescent(
+        samples: &[(f64, usize)],
+        population: &StructuredPopulation,
+    ) -> Result<Self, SimulationError> {
+        Self::structured_coalescent_with_rng(samples, population, &mut rand::thread_rng())
+    }
+
+    /// Generates a random genealogy under the structured coalescent of samples taken at given times before the present from given demes, using a given random number generator.
+    ///
+    /// Lineages only coalesce with lineages in the same deme, and move between demes at the backwards migration rates of the population.
+    fn structured_coalescent_with_rng<R: Rng + ?Sized>(
+        samples: &[(f64, usize)],
+        population: &StructuredPopulation,
+        rng: &mut R,
+    ) -> Result<Self, SimulationError> {
+        population.validate()?;
+        if samples.iter().any(|(_, deme)| *deme >= population.num_demes()) {
+            return Err(SimulationError::InvalidParameter { name: "deme".to_string() });
+        }
+        let sampling_times = samples.iter().map(|(time, _)| *time).collect::<Vec<_>>();
+        let (mut tree, order) = sampled_leaves(&sampling_times)?;
+        let migration_totals = population
+            .migration_rates
+            .iter()
+            .enumerate()
+            .map(|(deme, row)| row.iter().enumerate().filter(|(other, _)| *other != deme).map(|(_, rate)| rate).sum::<f64>())
+            .collect::<Vec<_>>();
+        let mut pending = order.into_iter().peekable();
+        let mut lineages = vec![vec![]; population.num_demes()];
+        let mut num_lineages = 0;
+        let mut time = 0.0;
+        loop {
+            let next_sample = pending.peek().map_or(f64::INFINITY, |leaf| tree.ages[*leaf]);
+            let coalescence_rates = lineages
+                .iter()
+                .zip(population.sizes.iter())
+                .map(|(deme, size)| (deme.len() * deme.len().saturating_sub(1)) as f64 / (2.0 * size))
+                .collect::<Vec<_>>();
+            let migration_rates = lineages
+                .iter()
+                .zip(migration_totals.iter())
+                .map(|(deme, total)| deme.len() as f64 * total)
+                .collect::<Vec<_>>();
+            let total_rate = coalescence_rates.iter().chain(migration_rates.iter()).sum::<f64>();
+            let waiting_time = exponential(total_rate, rng);
+            if time + waiting_time < next_sample {
+                time += waiting_time;
+                let rates = coalescence_rates.iter().chain(migration_rates.iter()).copied();
+                match weighted_index(rates, rng) {
+                    deme if deme < population.num_demes() => {
+                        coalesce(&mut tree, &mut lineages[deme], time, rng);
+                        num_lineages -= 1;
+                        if num_lineages == 1 && pending.peek().is_none() {
+                            break;
+                        }
+                    }
+                    event => {
+                        let source = event - population.num_demes();
+                        let rates = population.migration_rates[source]
+                            .iter()
+                            .enumerate()
+                            .map(|(deme, rate)| if deme == source { 0.0 } else { *rate });
+                        let target = weighted_index(rates, rng);
+                        let idx = rng.gen_range(0..lineages[source].len());
+                        let lineage = lineages[source].swap_remove(idx);
+                        lineages[target].push(lineage);
+                    }
+                }
+            } else if next_sample.is_finite() {
+                time = next_sample;
+                let leaf = pending.next().unwrap();
+                lineages[samples[leaf].1].push(leaf);
+                num_lineages += 1;
+            } else {
+                // lineages are isolated in demes without migration between them
+                return Err(SimulationError::InvalidParameter { name: "migration rates".to_string() });
+            }
+        }
+        let root = lineages.into_iter().flatten().next().unwrap();
+        Ok(tree.build_tree(root, |_| true))
+    }
+}
+
+/// A trait describing generation of random gene trees within a species tree under the multispecies coalescent.
+pub trait MultispeciesCoalescent: Coalescent + DFS
+where
+    <Self as RootedTree>::Node: RootedMetaNode + RootedWeightedNode + RootedZetaNode,
+    TreeNodeID<Self>: From<usize>,
+{
+    /// Generates a random gene tree within self as the species tree, given pairs of sampled taxa and the species they are sampled from. Edge weights of the species tree are in coalescent units, in which each pair of lineages in a species coalesces at rate 1.
+    fn multispecies_coalescent(
+        &self,
+        samples: &[(TreeNodeMeta<Self>, TreeNodeMeta<Self>)],
+    ) -> Result<Self, SimulationError> {
+        self.multispecies_coalescent_with_rng(samples, &mut rand::thread_rng())
+    }
+
+    /// Generates a random gene tree within self as the species tree using a given random number generator, given pairs of sampled taxa and the species they are sampled from.
+    ///
+    /// Lineages coalesce independently along each edge of the species tree, so that lineages from different species that fail to coalesce in the edge above their common ancestor sort incompletely. Edge weights of the gene tree are in coalescent units, and nodes are annotated with their heights above the most recent leaf of the species tree as zeta values.
+    fn multispecies_coalescent_with_rng<R: Rng + ?Sized>(
+        &self,
+        samples: &[(TreeNodeMeta<Self>, TreeNodeMeta<Self>)],
+        rng: &mut R,
+    ) -> Result<Self, SimulationError> {
+        if samples.len() < 2 {
+            return Err(SimulationError::InvalidParameter { name: "number of samples".to_string() });
+        }
+        let root_id = self.get_root_id();
+        let node_ids = self.postord_ids(root_id).collect::<Vec<_>>();
+        let mut depths: HashMap<TreeNodeID<Self>, f64> = HashMap::default();
+        for node_id in node_ids.iter().rev().copied() {
+            let depth = match self.get_node_parent_id(node_id) {
+                None => 0.0,
+                Some(parent_id) => {
+                    let weight = self
+                        .get_node(node_id)
+                        .unwrap()
+                        .get_weight()
+                        .and_then(<f64 as NumCast>::from)
+                        .ok_or_else(|| SimulationError::MissingEdgeWeight {
+                            parent: parent_id.to_string(),
+                            child: node_id.to_string(),
+                        })?;
+                    depths[&parent_id] + weight
+                }
+            };
+            depths.insert(node_id, depth);
+        }
+        let max_depth = depths.values().copied().fold(0.0, f64::max);
+
+        let mut tree = TimedTree::default();
+        // lineages leaving each species, starting with those sampled from leaves
+        let mut lineages: HashMap<TreeNodeID<Self>, Vec<usize>> = HashMap::default();
+        for (sample, species) in samples {
+            let species_id = self
+                .get_taxa_node_id(species)
+                .filter(|id| self.is_leaf(*id))
+                .ok_or_else(|| SimulationError::MissingTaxa {
+                    taxa: species.to_string(),
+                })?;
+            let leaf = tree.add_node(max_depth - depths[&species_id]);
+            tree.labels[leaf] = Some(sample.to_string());
+            lineages.entry(species_id).or_default().push(leaf);
+        }
+        for node_id in node_ids {
+            let mut species_lineages = lineages.remove(&node_id).unwrap_or_default();
+            species_lineages.extend(
+                self.get_node_children_ids(node_id)
+                    .flat_map(|child_id| lineages.remove(&child_id).unwrap_or_default()),
+            );
+            let mut age = max_depth - depths[&node_id];
+            let end_age = match self.get_node_parent_id(node_id) {
+                Some(parent_id) => max_depth - depths[&parent_id],
+                None => f64::INFINITY,
+            };
+            while species_lineages.len() > 1 {
+                let num_lineages = species_lineages.len() as f64;
+                age += exponential(num_lineages * (num_lineages - 1.0) / 2.0, rng);
+                if age >= end_age {
+                    break;
+                }
+                coalesce(&mut tree, &mut species_lineages, age, rng);
+            }
+            lineages.insert(node_id, species_lineages);
+        }
+        Ok(tree.build_tree(lineages[&root_id][0], |_| true))
+    }
 }
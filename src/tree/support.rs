@@ -0,0 +1,189 @@
+use itertools::Itertools;
+use num::NumCast;
+use std::str::FromStr;
+use vers_vecs::BitVec;
+
+#[cfg(feature = "non_crypto_hash")]
+use fxhash::FxHashMap as HashMap;
+#[cfg(not(feature = "non_crypto_hash"))]
+use std::collections::HashMap;
+
+use crate::prelude::*;
+use crate::tree::distances::{leaf_taxa_index, node_clusters, tree_splits, CompactTree};
+
+/// Selects where branch supports are written when annotating a tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SupportLabel {
+    /// Supports are written into the zeta value of each node
+    #[default]
+    Zeta,
+    /// Supports are written as meta annotations of internal nodes with two decimal places, which are encoded as internal labels in Newick. These annotations are not registered as taxa of the tree.
+    Taxa,
+}
+
+/// A trait describing bootstrap supports of the branches of a tree as the frequencies of their splits among replicate trees.
+///
+/// Each branch is identified by the NodeID of its lower endpoint, and only internal branches are considered. Splits of replicate trees are hashed as BitVecs over the leaf taxa of self, ignoring the root.
+pub trait SplitSupport: RootedMetaTree + Clusters + PathFunction
+where
+    <Self as RootedTree>::Node: RootedMetaNode + RootedZetaNode,
+{
+    /// Returns the fraction of replicate trees containing the split of each branch of self. Supports are 0 if there are no replicate trees.
+    fn split_support<'a>(&self, trees: impl IntoIterator<Item = &'a Self>) -> Vec<(TreeNodeID<Self>, f64)>
+    where
+        Self: 'a,
+    {
+        let taxa_map = leaf_taxa_index(&[self]);
+        let num_taxa = taxa_map.len();
+        let mut split_counts: HashMap<BitVec, usize> = tree_splits(self, &taxa_map, SplitMode::Unrooted)
+            .into_iter()
+            .map(|split| (split, 0))
+            .collect();
+        let mut num_trees = 0;
+        for tree in trees {
+            for split in tree_splits(tree, &taxa_map, SplitMode::Unrooted) {
+                if let Some(count) = split_counts.get_mut(&split) {
+                    *count += 1;
+                }
+            }
+            num_trees += 1;
+        }
+        node_clusters(self, &taxa_map, num_taxa)
+            .into_iter()
+            .filter(|(n_id, _)| *n_id != self.get_root_id() && !self.is_leaf(*n_id))
+            .map(|(n_id, mut bp)| {
+                if num_taxa > 0 && bp.is_bit_set(0).unwrap() {
+                    let mut bp_rev = BitVec::from_ones(num_taxa);
+                    let _ = bp_rev.apply_mask_xor(&bp);
+                    bp = bp_rev;
+                }
+                let count = split_counts.get(&bp).copied().unwrap_or(0);
+                match num_trees {
+                    0 => (n_id, 0.0),
+                    _ => (n_id, count as f64 / num_trees as f64),
+                }
+            })
+            .collect_vec()
+    }
+
+    /// Annotates nodes of self with supports of the branches above them, such as those returned by split_support or transfer_support. Returns Err if a node does not exist or a support cannot be written as a zeta value or meta annotation.
+    fn annotate_support(&mut self, supports: &[(TreeNodeID<Self>, f64)], label: SupportLabel) -> Result<(), SupportError> {
+        for (n_id, support) in supports {
+            if !self.get_node_ids().contains(n_id) {
+                return Err(SupportError::MissingNode {
+                    node: n_id.to_string(),
+                });
+            }
+            let invalid_support = || SupportError::InvalidSupport {
+                node: n_id.to_string(),
+                support: *support,
+            };
+            match label {
+                SupportLabel::Zeta => {
+                    let zeta = <TreeNodeZeta<Self> as NumCast>::from(*support).ok_or_else(invalid_support)?;
+                    self.set_node_zeta(*n_id, Some(zeta)).ok_or_else(invalid_support)?;
+                }
+                SupportLabel::Taxa => {
+                    let taxa = TreeNodeMeta::<Self>::from_str(&format!("{:.2}", support)).map_err(|_| invalid_support())?;
+                    // supports of different nodes may coincide, so they are kept out of the taxa of the tree
+                    self.set_node_taxa(*n_id, None);
+                    self.get_node_mut(*n_id).unwrap().set_taxa(Some(taxa));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A trait describing transfer bootstrap expectation (TBE) supports of the branches of a tree.
+///
+/// The transfer distance of a branch to another tree is the least number of taxa that must be moved to turn its split into a split of the other tree. Each branch is identified by the NodeID of its lower endpoint, and only branches whose smaller side has at least two taxa are considered. Trees are assumed to be on the same set of taxa. Transfer distances to a tree are computed in \Theta(n^2) time.
+pub trait TransferSupport: RootedMetaTree + Clusters
+where
+    <Self as RootedTree>::Node: RootedMetaNode,
+{
+    /// Returns the minimal transfer distance from each branch of self to any branch of tree
+    fn transfer_distances(&self, tree: &Self) -> Vec<(TreeNodeID<Self>, usize)> {
+        let branches = TransferBranches::new(self);
+        branches.transfer_distances(tree).collect_vec()
+    }
+
+    /// Returns the transfer bootstrap expectation of each branch of self over a collection of replicate trees. Supports are 0 if there are no replicate trees.
+    fn transfer_support<'a>(&self, trees: impl IntoIterator<Item = &'a Self>) -> Vec<(TreeNodeID<Self>, f64)>
+    where
+        Self: 'a,
+    {
+        let branches = TransferBranches::new(self);
+        let mut total_distances = vec![0; branches.clusters.len()];
+        let mut num_trees = 0;
+        for tree in trees {
+            for (total, (_, distance)) in total_distances.iter_mut().zip(branches.transfer_distances(tree)) {
+                *total += distance;
+            }
+            num_trees += 1;
+        }
+        branches
+            .clusters
+            .iter()
+            .zip(total_distances)
+            .map(|((n_id, _, depth), total)| match num_trees {
+                0 => (*n_id, 0.0),
+                _ => (*n_id, 1.0 - total as f64 / (num_trees as f64 * (depth - 1) as f64)),
+            })
+            .collect_vec()
+    }
+}
+
+/// Clusters of the branches of a reference tree, along with the depth (size of the smaller side) of each branch
+struct TransferBranches<'a, T: RootedMetaTree>
+where
+    <T as RootedTree>::Node: RootedMetaNode,
+{
+    taxa_map: HashMap<&'a TreeNodeMeta<T>, usize>,
+    clusters: Vec<(TreeNodeID<T>, BitVec, usize)>,
+}
+
+impl<'a, T> TransferBranches<'a, T>
+where
+    T: RootedMetaTree + Clusters,
+    <T as RootedTree>::Node: RootedMetaNode,
+{
+    fn new(tree: &'a T) -> Self {
+        let taxa_map = leaf_taxa_index(&[tree]);
+        let num_taxa = taxa_map.len();
+        let clusters = node_clusters(tree, &taxa_map, num_taxa)
+            .into_iter()
+            .filter(|(n_id, _)| *n_id != tree.get_root_id())
+            .filter_map(|(n_id, bp)| {
+                let size = bp.count_ones() as usize;
+                let depth = size.min(num_taxa - size);
+                (depth > 1).then_some((n_id, bp, depth))
+            })
+            .collect_vec();
+        TransferBranches { taxa_map, clusters }
+    }
+
+    /// Returns the transfer distance of each branch to a tree
+    fn transfer_distances<'b>(&'b self, tree: &T) -> impl Iterator<Item = (TreeNodeID<T>, usize)> + 'b {
+        let num_taxa = self.taxa_map.len();
+        let compact_tree = CompactTree::new(tree, &self.taxa_map);
+        self.clusters.iter().map(move |(n_id, bp, depth)| {
+            let in_cluster = (0..num_taxa).map(|idx| bp.is_bit_set(idx).unwrap()).collect_vec();
+            let size = bp.count_ones() as i64;
+            // the symmetric difference of the cluster with the cluster below each edge of tree
+            let distance = compact_tree
+                .cluster_overlaps(&in_cluster)
+                .into_iter()
+                .zip(compact_tree.cluster_size.iter())
+                .zip(compact_tree.parent.iter())
+                .filter(|(_, parent)| parent.is_some())
+                .map(|((overlap, cluster_size), _)| {
+                    let hamming = (size + cluster_size - 2 * overlap) as usize;
+                    hamming.min(num_taxa - hamming)
+                })
+                .min()
+                .unwrap_or(*depth);
+            (*n_id, distance)
+        })
+    }
+}
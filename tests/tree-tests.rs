@@ -212,6 +212,214 @@ fn tree_spr() {
     tree.spr((1, 2), (5, 4)).unwrap();
     dbg!(format!("{}", &tree.to_newick()));
 }
+#[test]
+fn spr_prune_graft() {
+    let mut tree = PhyloTree::from_newick("((A,B),C);".as_bytes()).unwrap();
+    let root_id = tree.get_root_id();
+    let a = tree.get_taxa_node_id(&"A".to_string()).unwrap();
+    let c = tree.get_taxa_node_id(&"C".to_string()).unwrap();
+    let ab = tree.get_node_parent_id(a).unwrap();
+
+    // pruning removes the subtree from self and leaves its former parent in place
+    let pruned = tree.prune(ab).unwrap();
+    assert_eq!(pruned.get_root_id(), ab);
+    assert_eq!(pruned.get_node_parent_id(ab), None);
+    assert_eq!(pruned.get_taxa_space().cloned().sorted().collect_vec(), ["A", "B"]);
+    assert!(!tree.get_node_ids().contains(&ab));
+    assert!(!tree.get_node_ids().contains(&a));
+    assert_eq!(tree.get_taxa_node_id(&"A".to_string()), None);
+    assert_eq!(tree.get_node_children_ids(root_id).collect_vec(), [c]);
+    assert!(tree.prune(root_id).is_err());
+    assert!(tree.prune(a).is_err());
+
+    // grafted nodes keep their NodeIDs when these are free in self
+    tree.graft(pruned, (root_id, c)).unwrap();
+    let new_parent = tree.get_node_parent_id(c).unwrap();
+    assert_ne!(new_parent, root_id);
+    assert_eq!(tree.get_node_parent_id(ab), Some(new_parent));
+    assert_eq!(tree.get_taxa_node_id(&"A".to_string()), Some(a));
+    assert_eq!(tree.get_taxa_space().cloned().sorted().collect_vec(), ["A", "B", "C"]);
+
+    // and are given new NodeIDs when these are already in use
+    let old_ids = tree.get_node_ids().collect_vec();
+    let other = PhyloTree::from_newick("(D,E);".as_bytes()).unwrap();
+    tree.graft(other, (root_id, new_parent)).unwrap();
+    let d = tree.get_taxa_node_id(&"D".to_string()).unwrap();
+    let de = tree.get_node_parent_id(d).unwrap();
+    assert!(!old_ids.contains(&d) && !old_ids.contains(&de));
+    assert_eq!(tree.get_node_parent_id(tree.get_node_parent_id(new_parent).unwrap()), Some(root_id));
+    assert_eq!(tree.num_taxa(), 5);
+    assert!(tree.graft(PhyloTree::new(100), (a, c)).is_err());
+
+    // spr moves a subtree onto another edge
+    let mut tree = PhyloTree::from_newick("((A,B),(C,D));".as_bytes()).unwrap();
+    let a = tree.get_taxa_node_id(&"A".to_string()).unwrap();
+    let c = tree.get_taxa_node_id(&"C".to_string()).unwrap();
+    let c_parent = tree.get_node_parent_id(c).unwrap();
+    tree.spr((tree.get_node_parent_id(a).unwrap(), a), (c_parent, c)).unwrap();
+    let a_parent = tree.get_node_parent_id(a).unwrap();
+    assert_eq!(tree.get_node_parent_id(c), Some(a_parent));
+    assert_eq!(tree.get_node_parent_id(a_parent), Some(c_parent));
+    assert_eq!(tree.num_taxa(), 4);
+}
+
+#[test]
+fn tree_nni() {
+    let input_str = String::from("((A,B),(C,D));");
+    let mut tree = PhyloTree::from_newick(input_str.as_bytes()).unwrap();
+    let moves = tree.nni_moves().collect_vec();
+    assert_eq!(moves.len(), 4);
+    assert_eq!(tree.nni_moves_at(tree.get_root_id()).count(), 0);
+    assert_eq!(tree.nni_moves_at(tree.get_taxa_node_id(&"A".to_string()).unwrap()).count(), 0);
+    for neighbour in tree.nni_neighbours() {
+        assert_eq!(neighbour.num_nodes(), tree.num_nodes());
+        assert_ne!(neighbour.to_newick().to_string(), tree.to_newick().to_string());
+    }
+
+    let original = tree.to_newick().to_string();
+    tree.precompute_constant_time_lca();
+    tree.apply_nni(moves[0]).unwrap();
+    let a = tree.get_taxa_node_id(&"A".to_string()).unwrap();
+    let b = tree.get_taxa_node_id(&"B".to_string()).unwrap();
+    assert_eq!(tree.get_lca_id(&[a, b]), tree.get_root_id());
+    tree.apply_nni(moves[0].reverse()).unwrap();
+    assert_eq!(tree.get_lca_id(&[a, b]), tree.get_node_parent_id(a).unwrap());
+    assert!(matches!(tree.apply_nni(moves[0].reverse()), Err(EditError::InvalidNNIMove)));
+    assert!(matches!(tree.nni(a), Err(EditError::NoNNIMove { .. })));
+
+    tree.nni(1).unwrap();
+    assert_ne!(tree.to_newick().to_string(), original);
+}
+
+#[test]
+fn reroot() {
+    let input_str = String::from("((A:1,B:2):3,(C:4,D:5):6);");
+    let mut tree = PhyloTree::from_newick(input_str.as_bytes()).unwrap();
+    let taxa = ["A", "B", "C", "D"].map(|x| x.to_string());
+    let leaf_dists = |tree: &PhyloTree| {
+        taxa.iter()
+            .tuple_combinations()
+            .map(|(x, y)| {
+                tree.pairwise_distance(
+                    tree.get_taxa_node_id(x).unwrap(),
+                    tree.get_taxa_node_id(y).unwrap(),
+                )
+            })
+            .collect_vec()
+    };
+    let dists = leaf_dists(&tree);
+
+    let a = tree.get_taxa_node_id(&"A".to_string()).unwrap();
+    let a_parent = tree.get_node_parent_id(a).unwrap();
+    tree.precompute_constant_time_lca();
+    tree.reroot_at_edge((a_parent, a)).unwrap();
+    assert_eq!(tree.get_node_parent_id(a), Some(tree.get_root_id()));
+    assert_eq!(tree.get_root().num_children(), 2);
+    assert_eq!(tree.get_node(a).unwrap().get_weight(), Some(0.5));
+    assert_eq!(leaf_dists(&tree), dists);
+    assert_eq!(tree.num_taxa(), 4);
+    assert_eq!(tree.get_leaf_ids().len(), 4);
+    let c = tree.get_taxa_node_id(&"C".to_string()).unwrap();
+    let d = tree.get_taxa_node_id(&"D".to_string()).unwrap();
+    assert_eq!(tree.get_lca_id(&[c, d]), tree.get_node_parent_id(c).unwrap());
+
+    let c_parent = tree.get_node_parent_id(c).unwrap();
+    tree.reroot_at_node(c_parent).unwrap();
+    assert_eq!(tree.get_root_id(), c_parent);
+    assert_eq!(tree.get_root().num_children(), 3);
+    assert_eq!(leaf_dists(&tree), dists);
+    assert!(matches!(tree.reroot_at_node(a), Err(RootingError::InvalidNode { .. })));
+    assert!(matches!(tree.reroot_at_edge((a, c)), Err(RootingError::InvalidEdge { .. })));
+}
+
+#[test]
+fn rooting() {
+    let input_str = String::from("((A:1,B:1):1,(C:1,D:7):1);");
+    let mut tree = PhyloTree::from_newick(input_str.as_bytes()).unwrap();
+    let a = tree.get_taxa_node_id(&"A".to_string()).unwrap();
+    let d = tree.get_taxa_node_id(&"D".to_string()).unwrap();
+    tree.midpoint_root().unwrap();
+    assert_eq!(tree.get_node_parent_id(d), Some(tree.get_root_id()));
+    assert_eq!(tree.get_node(d).unwrap().get_weight(), Some(5.0));
+    assert_eq!(tree.pairwise_distance(tree.get_root_id(), a), 5.0);
+
+    let input_str = String::from("(((A:1,B:1):1,C:2):1,D:3);");
+    let mut tree = PhyloTree::from_newick(input_str.as_bytes()).unwrap();
+    let a = tree.get_taxa_node_id(&"A".to_string()).unwrap();
+    let ab = tree.get_node_parent_id(a).unwrap();
+    tree.outgroup_root(&["A".to_string(), "B".to_string()]).unwrap();
+    assert_eq!(tree.get_node_parent_id(ab), Some(tree.get_root_id()));
+    assert_eq!(tree.get_root().num_children(), 2);
+    let root_id = tree.get_root_id();
+    tree.outgroup_root(&["D".to_string(), "C".to_string()]).unwrap();
+    assert_eq!(tree.get_root_id(), root_id);
+    assert!(matches!(
+        tree.outgroup_root(&["A".to_string(), "C".to_string()]),
+        Err(RootingError::NonMonophyletic)
+    ));
+    assert!(matches!(
+        tree.outgroup_root(&["E".to_string()]),
+        Err(RootingError::MissingTaxa { .. })
+    ));
+
+    let input_str = String::from("((A:1,B:1):1,(C:1,D:1):1);");
+    let mut tree = PhyloTree::from_newick(input_str.as_bytes()).unwrap();
+    let a = tree.get_taxa_node_id(&"A".to_string()).unwrap();
+    let c = tree.get_taxa_node_id(&"C".to_string()).unwrap();
+    tree.reroot_at_edge((tree.get_node_parent_id(a).unwrap(), a)).unwrap();
+    let deviation = tree.mad_root().unwrap();
+    assert!(deviation.abs() < 1e-6);
+    let root_id = tree.get_root_id();
+    assert_eq!(tree.pairwise_distance(root_id, a), 2.0);
+    assert_eq!(tree.pairwise_distance(root_id, c), 2.0);
+    assert_eq!(tree.pairwise_distance(a, c), 4.0);
+}
+
+#[test]
+fn unrooted_tree() {
+    let input_str = String::from("((A:1,B:2):3,(C:4,D:5):6);");
+    let tree = PhyloTree::from_newick(input_str.as_bytes()).unwrap();
+    let unrooted_tree = tree.unroot();
+    assert_eq!(unrooted_tree.num_nodes(), tree.num_nodes() - 1);
+    assert_eq!(unrooted_tree.num_edges(), unrooted_tree.num_nodes() - 1);
+    assert_eq!(unrooted_tree.num_taxa(), 4);
+    assert_eq!(unrooted_tree.get_leaf_ids().len(), 4);
+    assert!(unrooted_tree.is_binary());
+    let a = unrooted_tree.get_taxa_node_id(&"A".to_string()).unwrap();
+    let c = unrooted_tree.get_taxa_node_id(&"C".to_string()).unwrap();
+    let ab = unrooted_tree.get_node_neighbour_ids(a).next().unwrap();
+    let cd = unrooted_tree.get_node_neighbour_ids(c).next().unwrap();
+    assert_eq!(unrooted_tree.get_edge_weight(ab, cd), Some(9.0));
+    assert_eq!(unrooted_tree.get_edge_weight(cd, ab), Some(9.0));
+
+    let rooted_tree: PhyloTree = unrooted_tree.root_at_edge((ab, cd)).unwrap();
+    assert_eq!(rooted_tree.num_nodes(), tree.num_nodes());
+    assert_eq!(rooted_tree.get_edge_weight(rooted_tree.get_root_id(), ab), Some(4.5));
+    assert_eq!(rooted_tree.pairwise_distance(a, c), tree.pairwise_distance(a, c));
+
+    let rooted_tree: PhyloTree = unrooted_tree.root_at_node(cd).unwrap();
+    assert_eq!(rooted_tree.get_root_id(), cd);
+    assert_eq!(rooted_tree.get_root().num_children(), 3);
+    assert_eq!(rooted_tree.get_node_parent_id(ab), Some(cd));
+    assert_eq!(rooted_tree.get_edge_weight(cd, ab), Some(9.0));
+    assert_eq!(rooted_tree.num_taxa(), 4);
+    assert!(matches!(unrooted_tree.root_at_node::<f32>(a), Err(RootingError::InvalidNode { .. })));
+    assert!(matches!(unrooted_tree.root_at_edge::<f32>((a, c)), Err(RootingError::InvalidEdge { .. })));
+
+    let tree_length = |t: &PhyloUnrootedTree| {
+        t.get_edges()
+            .map(|(x, y)| t.get_edge_weight(x, y).unwrap())
+            .sum::<f32>()
+    };
+    let mut split_tree = unrooted_tree.clone();
+    let split_id = split_tree.next_id();
+    split_tree.split_edge((ab, cd), split_tree.next_node());
+    assert_eq!(split_tree.num_edges(), unrooted_tree.num_edges() + 1);
+    assert_eq!(split_tree.get_edge_weight(ab, split_id), Some(4.5));
+    assert_eq!(split_tree.get_edge_weight(split_id, cd), Some(4.5));
+    assert_eq!(tree_length(&split_tree), tree_length(&unrooted_tree));
+}
+
 #[test]
 fn tree_cluster() {
     let input_str: String = String::from("((A,B),C);");
@@ -346,6 +554,945 @@ fn robinson_foulds() {
     assert_eq!(t1.rfs(&t2), 1);
 }
 
+#[test]
+fn generalized_robinson_foulds() {
+    let t1 = PhyloTree::from_newick("((A,B),(C,D));".as_bytes()).unwrap();
+    let t2 = PhyloTree::from_newick("(A,(B,(C,D)));".as_bytes()).unwrap();
+    assert_eq!(t1.rf(&t2, SplitMode::Unrooted), 0);
+    assert_eq!(t1.rf(&t2, SplitMode::Rooted), 2);
+    assert_eq!(t1.nrf(&t2, SplitMode::Rooted), 0.5);
+
+    let t1 = PhyloTree::from_newick("((A,B),C,(D,E));".as_bytes()).unwrap();
+    let t2 = PhyloTree::from_newick("((A,C),B,(D,E));".as_bytes()).unwrap();
+    assert_eq!(t1.rf(&t2, SplitMode::Unrooted), 2);
+    assert_eq!(t1.nrf(&t2, SplitMode::Unrooted), 0.5);
+    assert_eq!(t1.nrf_splits(&t2, SplitMode::Unrooted), 0.5);
+
+    // unresolved trees are normalized by the taxon count rather than their splits
+    let t3 = PhyloTree::from_newick("((A,B),C,D,E);".as_bytes()).unwrap();
+    let t4 = PhyloTree::from_newick("(A,B,C,(D,E));".as_bytes()).unwrap();
+    assert_eq!(t3.rf(&t4, SplitMode::Unrooted), 2);
+    assert_eq!(t3.nrf(&t4, SplitMode::Unrooted), 0.5);
+    assert_eq!(t3.nrf_splits(&t4, SplitMode::Unrooted), 1.0);
+    assert_eq!(t1.matching_split_distance(&t1, SplitMode::Unrooted), 0);
+    assert_eq!(t1.matching_split_distance(&t2, SplitMode::Unrooted), 2);
+    assert!(t1.clustering_info_distance(&t1, SplitMode::Unrooted).abs() < 1e-9);
+    assert!(t1.clustering_info_distance(&t2, SplitMode::Unrooted) > 0.0);
+    assert!((t1.shared_phylo_info(&t2, SplitMode::Unrooted) - 5_f64.log2()).abs() < 1e-9);
+    assert!((t1.phylo_info_distance(&t2, SplitMode::Unrooted) - 2.0 * 5_f64.log2()).abs() < 1e-9);
+    assert!(t1.phylo_info_distance(&t1, SplitMode::Unrooted).abs() < 1e-9);
+}
+
+#[test]
+fn quartet_distance() {
+    // resolution of a quartet as the index of the pairing with the shortest total path length
+    fn quartet_topology(tree: &PhyloTree, q: &[&String]) -> Option<usize> {
+        let ids = q.iter().map(|x| tree.get_taxa_node_id(x).unwrap()).collect_vec();
+        let d = |x: usize, y: usize| tree.pairwise_distance(ids[x], ids[y]);
+        let sums = [d(0, 1) + d(2, 3), d(0, 2) + d(1, 3), d(0, 3) + d(1, 2)];
+        let min = sums.iter().cloned().fold(f32::INFINITY, f32::min);
+        let min_idx = sums.iter().positions(|x| *x == min).collect_vec();
+        (min_idx.len() == 1).then_some(min_idx[0])
+    }
+    let newicks = [
+        ("((A,B),C,(D,E));", "((A,C),B,(D,E));"),
+        ("(((A,B),C),(D,(E,F)),(G,H));", "((A,(B,C,D)),(E,F,G),H);"),
+        ("(A,B,C,D,E,F,G);", "(((A,B),(C,D)),((E,F),G));"),
+        ("((A,B,C),(D,E,F),(G,H));", "((A,D,G),(B,E,H),(C,F));"),
+    ];
+    let mut trees = newicks
+        .iter()
+        .map(|(x, y)| {
+            (
+                PhyloTree::from_newick(x.as_bytes()).unwrap(),
+                PhyloTree::from_newick(y.as_bytes()).unwrap(),
+            )
+        })
+        .collect_vec();
+    trees.extend((0..5).map(|_| (PhyloTree::yule(10), PhyloTree::yule(10))));
+    // random multifurcating trees, including high degree nodes on either side
+    use rand::{rngs::StdRng, SeedableRng};
+    let mut rng = StdRng::seed_from_u64(3);
+    for (n, max_degree_1, max_degree_2) in [(4, 2, 3), (6, 3, 3), (9, 2, 2), (9, 5, 2), (12, 3, 4), (14, 2, 6), (16, 4, 2)] {
+        let taxa = (0..n).map(|i| format!("T{i}")).collect_vec();
+        for _ in 0..4 {
+            let t1 = format!("{};", random_newick(&taxa, max_degree_1, &mut rng));
+            let t2 = format!("{};", random_newick(&taxa, max_degree_2, &mut rng));
+            trees.push((
+                PhyloTree::from_newick(t1.as_bytes()).unwrap(),
+                PhyloTree::from_newick(t2.as_bytes()).unwrap(),
+            ));
+        }
+    }
+    for (mut t1, mut t2) in trees {
+        t1.precompute_constant_time_lca();
+        t2.precompute_constant_time_lca();
+        let report = t1.quartet_report(&t2).unwrap();
+        let taxa = t1.get_taxa_space().filter(|x| t1.is_leaf(t1.get_taxa_node_id(x).unwrap())).cloned().collect_vec();
+        let mut expected = QuartetReport::default();
+        for q in taxa.iter().combinations(4) {
+            expected.total += 1;
+            match (quartet_topology(&t1, &q), quartet_topology(&t2, &q)) {
+                (Some(x), Some(y)) if x == y => expected.shared += 1,
+                (Some(_), Some(_)) => expected.different += 1,
+                (Some(_), None) => expected.resolved_first += 1,
+                (None, Some(_)) => expected.resolved_second += 1,
+                (None, None) => expected.unresolved += 1,
+            }
+        }
+        assert_eq!(report, expected);
+        assert_eq!(t1.quartet_distance(&t2).unwrap(), report.distance());
+        assert_eq!(t1.quartet_distance(&t1).unwrap(), 0);
+    }
+}
+
+/// Returns a random tree on taxa as a newick string, where each internal node has between 2 and max_degree children
+fn random_newick<R: rand::Rng>(taxa: &[String], max_degree: usize, rng: &mut R) -> String {
+    use rand::seq::SliceRandom;
+    if taxa.len() == 1 {
+        return taxa[0].clone();
+    }
+    let mut taxa = taxa.to_vec();
+    taxa.shuffle(rng);
+    let num_children = rng.gen_range(2..=max_degree.min(taxa.len()));
+    let mut cuts = (1..taxa.len()).collect_vec();
+    cuts.shuffle(rng);
+    let cuts = [0].into_iter()
+        .chain(cuts.into_iter().take(num_children - 1).sorted())
+        .chain([taxa.len()])
+        .collect_vec();
+    let children = cuts
+        .windows(2)
+        .map(|w| random_newick(&taxa[w[0]..w[1]], max_degree, rng))
+        .join(",");
+    format!("({children})")
+}
+
+#[test]
+fn triplet_distance() {
+    let newicks = [
+        ("((A,B),C);", "((A,C),B);"),
+        ("(((A,B),C),(D,(E,F)),(G,H));", "((A,(B,C,D)),(E,F,G),H);"),
+        ("(A,B,C,D,E,F,G);", "(((A,B),(C,D)),((E,F),G));"),
+        ("((A,B,C),(D,E,F),(G,H));", "((A,D,G),(B,E,H),(C,F));"),
+    ];
+    let mut trees = newicks
+        .iter()
+        .map(|(x, y)| {
+            (
+                PhyloTree::from_newick(x.as_bytes()).unwrap(),
+                PhyloTree::from_newick(y.as_bytes()).unwrap(),
+            )
+        })
+        .collect_vec();
+    trees.extend((0..5).map(|_| (PhyloTree::yule(10), PhyloTree::yule(10))));
+    for (mut t1, mut t2) in trees {
+        t1.precompute_constant_time_lca();
+        t2.precompute_constant_time_lca();
+        let report = t1.triplet_report(&t2).unwrap();
+        assert_eq!(report, t1.triplet_report_naive(&t2).unwrap());
+        assert_eq!(t1.triplet_distance(&t2).unwrap(), report.distance());
+        assert_eq!(t1.triplet_distance(&t1).unwrap(), 0);
+    }
+    // compare with enumeration of all triplets on random trees of several sizes and degrees
+    use rand::{rngs::StdRng, SeedableRng};
+    let mut rng = StdRng::seed_from_u64(9);
+    for (n, max_degree) in [(4, 2), (9, 2), (9, 4), (25, 2), (25, 3), (40, 5)] {
+        let taxa = (0..n).map(|x| x.to_string()).collect_vec();
+        for _ in 0..4 {
+            let x = random_newick(&taxa, max_degree, &mut rng);
+            let y = random_newick(&taxa, max_degree, &mut rng);
+            let mut t1 = PhyloTree::from_newick(format!("{x};").as_bytes()).unwrap();
+            let mut t2 = PhyloTree::from_newick(format!("{y};").as_bytes()).unwrap();
+            t1.precompute_constant_time_lca();
+            t2.precompute_constant_time_lca();
+            assert_eq!(t1.triplet_report(&t2).unwrap(), t1.triplet_report_naive(&t2).unwrap());
+            assert_eq!(t2.triplet_report(&t1).unwrap(), t2.triplet_report_naive(&t1).unwrap());
+        }
+    }
+
+    let t1 = PhyloTree::from_newick("((A,B),C);".as_bytes()).unwrap();
+    let t2 = PhyloTree::from_newick("((A,B),D);".as_bytes()).unwrap();
+    assert!(matches!(t1.triplet_report(&t2), Err(DistanceError::TaxaMismatch)));
+}
+
+#[test]
+fn path_difference() {
+    let mut t1 = PhyloTree::from_newick("((A:1,B:2):3,(C:4,D:5):6);".as_bytes()).unwrap();
+    let mut t2 = PhyloTree::from_newick("((A:1,C:2):3,(B:4,D:5):6);".as_bytes()).unwrap();
+    t1.precompute_constant_time_lca();
+    t2.precompute_constant_time_lca();
+    assert_eq!(t1.path_diff(&t1, 1, PathLength::Topological), 0.0);
+    // AB and CD have length 2 in t1 and 4 in t2, AC and BD the reverse
+    assert_eq!(t1.path_diff(&t2, 1, PathLength::Topological), 8.0);
+    assert_eq!(t1.path_diff(&t2, 2, PathLength::Topological), 4.0);
+    assert_eq!(t1.path_diff(&t2, 0, PathLength::Topological), 2.0);
+    // AB: 3 vs 14, AC: 14 vs 3, AD: 15 vs 15, BC: 15 vs 15, BD: 16 vs 9, CD: 9 vs 16
+    assert_eq!(t1.path_diff(&t2, 1, PathLength::Weighted), 36.0);
+    #[cfg(feature = "parallel")]
+    {
+        assert_eq!(t1.path_diff_par(&t2, 1, PathLength::Topological), 8.0);
+        assert_eq!(t1.path_diff_par(&t2, 1, PathLength::Weighted), 36.0);
+    }
+}
+
+#[test]
+fn maximum_agreement_subtree() {
+    let t1 = PhyloTree::from_newick("(((A,B),C),D);".as_bytes()).unwrap();
+    let t2 = PhyloTree::from_newick("(((A,C),B),D);".as_bytes()).unwrap();
+    assert_eq!(t1.mast_size(&t2), 3);
+    let mast = t1.mast(&t2).unwrap();
+    assert_eq!(mast.get_leaf_ids().len(), 3);
+    assert_eq!(t1.mast_size(&t1), 4);
+
+    let t1 = PhyloTree::from_newick("(A,B,C,D);".as_bytes()).unwrap();
+    let t2 = PhyloTree::from_newick("((A,B),(C,D));".as_bytes()).unwrap();
+    assert_eq!(t1.mast_size(&t2), 2);
+
+    // compare with the largest agreeing subset of taxa
+    for _ in 0..3 {
+        let t1 = PhyloTree::yule(7);
+        let t2 = PhyloTree::yule(7);
+        let taxa = t1.get_taxa_space().cloned().collect_vec();
+        let max_size = taxa
+            .iter()
+            .powerset()
+            .filter(|subset| subset.len() > 1)
+            .filter(|subset| {
+                let c1 = t1
+                    .contract_tree(&subset.iter().map(|x| t1.get_taxa_node_id(x).unwrap()).collect_vec())
+                    .unwrap();
+                let c2 = t2
+                    .contract_tree(&subset.iter().map(|x| t2.get_taxa_node_id(x).unwrap()).collect_vec())
+                    .unwrap();
+                c1.rf(&c2, SplitMode::Rooted) == 0
+            })
+            .map(|subset| subset.len())
+            .max()
+            .unwrap();
+        assert_eq!(t1.mast_size(&t2), max_size);
+        let mast_taxa = t1.mast_taxa(&t2);
+        assert_eq!(mast_taxa.len(), max_size);
+        let c1 = t1.mast(&t2).unwrap();
+        let c2 = t2
+            .contract_tree(&mast_taxa.iter().map(|x| t2.get_taxa_node_id(x).unwrap()).collect_vec())
+            .unwrap();
+        assert_eq!(c1.get_leaf_ids().len(), max_size);
+        assert_eq!(c1.rf(&c2, SplitMode::Rooted), 0);
+    }
+
+    // binary trees take the heavy path algorithm, which must agree with the dynamic program
+    for n in [30, 80] {
+        let t1 = PhyloTree::yule(n);
+        let t2 = PhyloTree::unif(n);
+        // a taxa missing from t1 makes t2 multifurcating without changing the shared taxa
+        let mut t3 = t2.clone();
+        let id = t3.next_id();
+        t3.add_child(t3.get_root_id(), PhyloNode::new(id));
+        t3.set_node_taxa(id, Some("X".to_string()));
+        let size = t1.mast_size(&t2);
+        assert_eq!(size, t1.mast_size(&t3));
+        assert_eq!(size, t2.mast_size(&t1));
+        assert_eq!(t1.mast_size(&t1), n);
+        let mast_taxa = t1.mast_taxa(&t2);
+        assert_eq!(mast_taxa.iter().unique().count(), size);
+        let c1 = t1.mast(&t2).unwrap();
+        let c2 = t2
+            .contract_tree(&mast_taxa.iter().map(|x| t2.get_taxa_node_id(x).unwrap()).collect_vec())
+            .unwrap();
+        assert_eq!(c1.rf(&c2, SplitMode::Rooted), 0);
+    }
+
+    // random binary trees across sizes and seeds, against the dynamic program forced by an extra taxa
+    use rand::{rngs::StdRng, SeedableRng};
+    for seed in 0..12 {
+        let mut rng = StdRng::seed_from_u64(seed);
+        for n in [2, 3, 4, 5, 7, 10, 16, 25, 40, 64, 100] {
+            let taxa = (0..n).map(|i| format!("T{i}")).collect_vec();
+            let t1 = PhyloTree::from_newick(format!("{};", random_newick(&taxa, 2, &mut rng)).as_bytes()).unwrap();
+            let t2 = PhyloTree::from_newick(format!("{};", random_newick(&taxa, 2, &mut rng)).as_bytes()).unwrap();
+            let t3 = PhyloTree::from_newick(format!("({},X);", random_newick(&taxa, 2, &mut rng)).as_bytes()).unwrap();
+            let mut t4 = t2.clone();
+            let id = t4.next_id();
+            t4.add_child(t4.get_root_id(), PhyloNode::new(id));
+            t4.set_node_taxa(id, Some("X".to_string()));
+            assert!(t1.is_binary() && t2.is_binary() && !t4.is_binary());
+            let size = t1.mast_size(&t2);
+            assert_eq!(size, t1.mast_size(&t4), "seed {seed}, n {n}");
+            assert_eq!(size, t2.mast_size(&t1), "seed {seed}, n {n}");
+            let mast_taxa = t1.mast_taxa(&t2);
+            assert_eq!(mast_taxa.iter().unique().count(), size);
+            let c1 = t1.mast(&t2).unwrap();
+            let c2 = t2
+                .contract_tree(&mast_taxa.iter().map(|x| t2.get_taxa_node_id(x).unwrap()).collect_vec())
+                .unwrap();
+            assert_eq!(c1.rf(&c2, SplitMode::Rooted), 0);
+            // a binary tree with a taxa missing from the other tree
+            let mut t5 = t3.clone();
+            let id = t5.next_id();
+            t5.add_child(t5.get_root_id(), PhyloNode::new(id));
+            t5.set_node_taxa(id, Some("Y".to_string()));
+            assert_eq!(t1.mast_size(&t3), t1.mast_size(&t5), "seed {seed}, n {n}");
+        }
+    }
+
+    let t1 = PhyloTree::from_newick("((A,B),C);".as_bytes()).unwrap();
+    let t2 = PhyloTree::from_newick("((D,E),F);".as_bytes()).unwrap();
+    assert_eq!(t1.mast_size(&t2), 0);
+    assert!(matches!(t1.mast(&t2), Err(DistanceError::NoSharedTaxa)));
+}
+
+#[test]
+fn spr_distance() {
+    let t1 = PhyloTree::from_newick("(((A,B),C),D);".as_bytes()).unwrap();
+    let t2 = PhyloTree::from_newick("(((A,C),B),D);".as_bytes()).unwrap();
+    assert_eq!(t1.spr_distance(&t1).unwrap(), 0);
+    assert_eq!(t1.spr_distance(&t2).unwrap(), 1);
+    let forest = t1.spr_forest(&t2).unwrap();
+    assert_eq!(forest.components.len(), 1);
+    assert_eq!(forest.root_component.len() + forest.components[0].len(), 4);
+
+    let t2 = PhyloTree::from_newick("(((D,C),B),A);".as_bytes()).unwrap();
+    assert_eq!(t1.spr_distance(&t2).unwrap(), 2);
+    let t2 = PhyloTree::from_newick("((A,B,C),D);".as_bytes()).unwrap();
+    assert!(t1.spr_distance(&t2).is_err());
+
+    // components of the forest induce the same topology in both trees
+    for _ in 0..5 {
+        let t1 = PhyloTree::yule(12);
+        let t2 = PhyloTree::yule(12);
+        let forest = t1.spr_forest(&t2).unwrap();
+        let approx = t1.spr_forest_approx(&t2).unwrap();
+        assert!(forest.spr_distance() <= approx.spr_distance());
+        assert!(approx.spr_distance() <= 3 * forest.spr_distance());
+        for component in forest.components.iter().chain([&forest.root_component]) {
+            assert!(t1.get_taxa_space().count() > 0);
+            if component.len() < 3 {
+                continue;
+            }
+            let c1 = t1
+                .contract_tree(&component.iter().map(|x| t1.get_taxa_node_id(x).unwrap()).collect_vec())
+                .unwrap();
+            let c2 = t2
+                .contract_tree(&component.iter().map(|x| t2.get_taxa_node_id(x).unwrap()).collect_vec())
+                .unwrap();
+            assert_eq!(c1.rf(&c2, SplitMode::Rooted), 0);
+        }
+        assert_eq!(
+            forest.components.iter().map(|c| c.len()).sum::<usize>() + forest.root_component.len(),
+            12
+        );
+    }
+}
+
+#[test]
+fn tbr() {
+    let input_str = String::from("((((A,B),C),D),(E,F));");
+    let tree = PhyloTree::from_newick(input_str.as_bytes()).unwrap();
+    let id = |tree: &PhyloTree, taxa: &str| tree.get_taxa_node_id(&taxa.to_string()).unwrap();
+    let (a, e) = (id(&tree, "A"), id(&tree, "E"));
+    let ab = tree.get_node_parent_id(a).unwrap();
+    let abc = tree.get_node_parent_id(ab).unwrap();
+    let ef = tree.get_node_parent_id(e).unwrap();
+
+    let mut moved = tree.clone();
+    assert!(matches!(moved.tbr(abc, None, (abc, ab)), Err(EditError::InvalidEdge { .. })));
+    assert!(matches!(moved.tbr(abc, Some((ef, e)), (ef, e)), Err(EditError::InvalidEdge { .. })));
+    assert!(matches!(moved.tbr(moved.get_root_id(), None, (ef, e)), Err(EditError::MissingParent { .. })));
+    moved.tbr(abc, Some((ab, a)), (ef, e)).unwrap();
+    assert_eq!(moved.num_nodes(), tree.num_nodes());
+    assert_eq!(moved.num_taxa(), 6);
+    assert!(moved.is_binary());
+    let (a, e) = (id(&moved, "A"), id(&moved, "E"));
+    let b = id(&moved, "B");
+    let c = id(&moved, "C");
+    assert_eq!(moved.get_lca_id(&[b, c]), moved.get_node_parent_id(b).unwrap());
+    assert_eq!(moved.get_lca_id(&[a, c, e]), moved.get_node_parent_id(e).unwrap());
+
+    let t1 = tree.unroot();
+    let t2 = moved.unroot();
+    assert_eq!(t1.tbr_distance(&t1).unwrap(), 0);
+    assert_eq!(t1.tbr_distance(&t2).unwrap(), 1);
+    let forest = t1.tbr_forest(&t2).unwrap();
+    assert_eq!(forest.len(), 2);
+    assert_eq!(forest.iter().map(|c| c.len()).sum::<usize>(), 6);
+
+    // a single TBR move between random trees
+    for _ in 0..10 {
+        let tree = PhyloTree::yule(10);
+        let mut moved = tree.clone();
+        let node_id = moved
+            .get_node_ids()
+            .find(|id| {
+                moved.get_node_parent_id(*id).is_some()
+                    && !moved.is_leaf(*id)
+                    && moved.get_node_parent_id(*id) != Some(moved.get_root_id())
+            })
+            .unwrap();
+        let parent_id = moved.get_node_parent_id(node_id).unwrap();
+        let subtree = moved.dfs(node_id).map(|node| node.get_id()).collect_vec();
+        let regraft_edge = moved
+            .get_node_ids()
+            .filter(|id| !subtree.contains(id) && *id != parent_id)
+            .filter_map(|id| Some((moved.get_node_parent_id(id)?, id)))
+            .find(|(p_id, _)| *p_id != parent_id)
+            .unwrap();
+        let reroot_child = moved.get_node_children_ids(node_id).next().unwrap();
+        moved.tbr(node_id, Some((node_id, reroot_child)), regraft_edge).unwrap();
+        assert!(tree.unroot().tbr_distance(&moved.unroot()).unwrap() <= 1);
+    }
+}
+
+#[test]
+fn transfer_support() {
+    let t1 = PhyloTree::from_newick("(((A,B),C),(D,(E,F)));".as_bytes()).unwrap();
+    let t2 = PhyloTree::from_newick("(((A,C),B),(D,(E,F)));".as_bytes()).unwrap();
+    let a = t1.get_taxa_node_id(&"A".to_string()).unwrap();
+    let ab = t1.get_node_parent_id(a).unwrap();
+    let abc = t1.get_node_parent_id(ab).unwrap();
+    assert!(t1.transfer_distances(&t1).iter().all(|(_, d)| *d == 0));
+    let distances: HashMap<_, _> = t1.transfer_distances(&t2).into_iter().collect();
+    assert_eq!(distances[&ab], 1);
+    assert_eq!(distances[&abc], 0);
+    let supports: HashMap<_, _> = t1.transfer_support([&t1, &t2]).into_iter().collect();
+    assert_eq!(supports[&ab], 0.5);
+    assert_eq!(supports[&abc], 1.0);
+    assert!(t1.transfer_support([]).iter().all(|(_, s)| *s == 0.0));
+
+    // compare with the least hamming distance to any bipartition
+    for _ in 0..5 {
+        let t1 = PhyloTree::yule(15);
+        let t2 = PhyloTree::yule(15);
+        let taxa = t1.get_taxa_space().cloned().collect_vec();
+        let cluster = |tree: &PhyloTree, n_id| {
+            let leaves: HashSet<_> = tree.get_cluster(n_id).filter_map(|n| n.get_taxa().cloned()).collect();
+            taxa.iter().map(|t| leaves.contains(t)).collect_vec()
+        };
+        let t2_clusters = t2
+            .get_node_ids()
+            .filter(|id| *id != t2.get_root_id())
+            .map(|id| cluster(&t2, id))
+            .collect_vec();
+        for (n_id, distance) in t1.transfer_distances(&t2) {
+            let c1 = cluster(&t1, n_id);
+            let expected = t2_clusters
+                .iter()
+                .map(|c2| {
+                    let hamming = c1.iter().zip(c2).filter(|(x, y)| x != y).count();
+                    hamming.min(taxa.len() - hamming)
+                })
+                .min()
+                .unwrap();
+            assert_eq!(distance, expected);
+        }
+    }
+}
+
+#[test]
+fn split_support() {
+    let mut tree = PhyloTree::from_newick("(((A,B),C),(D,E));".as_bytes()).unwrap();
+    let replicates = ["(((A,B),C),(D,E));", "(((A,C),B),(D,E));", "((A,B),(C,(D,E)));"]
+        .into_iter()
+        .map(|newick| PhyloTree::from_newick(newick.as_bytes()).unwrap())
+        .collect_vec();
+    let a = tree.get_taxa_node_id(&"A".to_string()).unwrap();
+    let d = tree.get_taxa_node_id(&"D".to_string()).unwrap();
+    let ab = tree.get_node_parent_id(a).unwrap();
+    let abc = tree.get_node_parent_id(ab).unwrap();
+    let de = tree.get_node_parent_id(d).unwrap();
+    let supports = tree.split_support(replicates.iter());
+    assert_eq!(supports.len(), 3);
+    let support_map: HashMap<_, _> = supports.iter().copied().collect();
+    assert_eq!(support_map[&ab], 2.0 / 3.0);
+    assert_eq!(support_map[&abc], 1.0);
+    assert_eq!(support_map[&de], 1.0);
+
+    tree.annotate_support(&supports, SupportLabel::Zeta).unwrap();
+    assert_eq!(tree.get_zeta(ab), Some(2.0 / 3.0));
+    assert_eq!(tree.get_zeta(a), None);
+    tree.annotate_support(&supports, SupportLabel::Taxa).unwrap();
+    assert_eq!(tree.to_newick().to_string(), "(((A,B)0.67,C)1.00,(D,E)1.00);");
+    assert_eq!(tree.get_node_taxa(abc), Some(&"1.00".to_string()));
+    assert_eq!(tree.get_node_taxa(de), Some(&"1.00".to_string()));
+    assert_eq!(tree.get_taxa_node_id(&"1.00".to_string()), None);
+    assert_eq!(tree.get_taxa_space().cloned().sorted().collect_vec(), ["A", "B", "C", "D", "E"]);
+    assert_eq!(tree.rfs(&replicates[0]), 0);
+    assert!(matches!(
+        tree.annotate_support(&[(100, 1.0)], SupportLabel::Zeta),
+        Err(SupportError::MissingNode { .. })
+    ));
+}
+
+#[test]
+fn consensus() {
+    let trees = [
+        "(((A:1,B:1):1,C:1):1,(D:1,E:1):1);",
+        "(((A:1,B:1):3,D:1):1,(C:1,E:1):1);",
+        "(((A:1,C:1):1,B:1):1,(D:1,E:1):1);",
+    ]
+    .into_iter()
+    .map(|newick| PhyloTree::from_newick(newick.as_bytes()).unwrap())
+    .collect_vec();
+    let num_internal = |tree: &PhyloTree| tree.get_node_ids().filter(|id| !tree.is_leaf(*id)).count();
+
+    let strict = PhyloTree::consensus(&trees, ConsensusMethod::Strict, false).unwrap();
+    assert_eq!(strict.num_taxa(), 5);
+    assert_eq!(num_internal(&strict), 1);
+
+    let majority = PhyloTree::consensus(&trees, ConsensusMethod::Majority(0.5), true).unwrap();
+    assert_eq!(majority.rf(&trees[0], SplitMode::Rooted), 0);
+    let a = majority.get_taxa_node_id(&"A".to_string()).unwrap();
+    let ab = majority.get_node_parent_id(a).unwrap();
+    assert_eq!(majority.get_zeta(ab), Some(2.0 / 3.0));
+    assert_eq!(majority.get_node(ab).unwrap().get_weight(), Some(2.0));
+    assert_eq!(majority.get_node(a).unwrap().get_weight(), Some(1.0));
+    let majority = PhyloTree::consensus(&trees, ConsensusMethod::Majority(0.7), false).unwrap();
+    assert_eq!(num_internal(&majority), 1);
+    let majority = PhyloTree::consensus(&trees, ConsensusMethod::Majority(2.0 / 3.0), false).unwrap();
+    assert_eq!(majority.rf(&trees[0], SplitMode::Rooted), 0);
+    let strict = PhyloTree::consensus(&trees[..2], ConsensusMethod::Strict, false).unwrap();
+    let majority = PhyloTree::consensus(&trees[..2], ConsensusMethod::Majority(1.0), false).unwrap();
+    assert_eq!(num_internal(&strict), 2);
+    assert_eq!(majority.rf(&strict, SplitMode::Rooted), 0);
+    assert_eq!(num_internal(&majority), 2);
+
+    let majority = PhyloTree::consensus(&trees[..2], ConsensusMethod::Majority(0.5), false).unwrap();
+    assert_eq!(num_internal(&majority), 2);
+    let extended = PhyloTree::consensus(&trees[..2], ConsensusMethod::ExtendedMajority, false).unwrap();
+    assert_eq!(extended.rf(&trees[0], SplitMode::Rooted), 0);
+
+    assert!(PhyloTree::consensus(&[], ConsensusMethod::Strict, false).is_err());
+    assert!(PhyloTree::consensus(&trees, ConsensusMethod::Majority(0.2), false).is_err());
+    let other = PhyloTree::from_newick("((A,B),(C,F));".as_bytes()).unwrap();
+    assert!(PhyloTree::consensus(&[trees[0].clone(), other], ConsensusMethod::Strict, false).is_err());
+}
+
+#[test]
+fn maximum_clade_credibility() {
+    let trees = [
+        "(((A:1,B:1):1,C:2):1,(D:1,E:1):2);",
+        "(((A:2,B:2):1,C:3):1,(D:2,E:2):2);",
+        "(((A:1,C:1):1,B:2):1,(D:1,E:1):2);",
+    ]
+    .into_iter()
+    .map(|newick| PhyloTree::from_newick(newick.as_bytes()).unwrap())
+    .collect_vec();
+    assert_eq!(PhyloTree::mcc_index(&trees, CladeCredibility::Product).unwrap(), 0);
+    assert_eq!(PhyloTree::mcc_index(&trees, CladeCredibility::Sum).unwrap(), 0);
+    let reordered = [trees[2].clone(), trees[1].clone(), trees[0].clone()];
+    assert_eq!(PhyloTree::mcc_index(&reordered, CladeCredibility::Product).unwrap(), 1);
+
+    let (mcc_tree, summaries) = PhyloTree::mcc_tree(&trees, CladeCredibility::Product).unwrap();
+    assert_eq!(mcc_tree.rf(&trees[0], SplitMode::Rooted), 0);
+    assert_eq!(summaries.len(), mcc_tree.num_nodes());
+    let summaries: HashMap<_, _> = summaries.into_iter().collect();
+    let a = mcc_tree.get_taxa_node_id(&"A".to_string()).unwrap();
+    let ab = mcc_tree.get_node_parent_id(a).unwrap();
+    let abc = mcc_tree.get_node_parent_id(ab).unwrap();
+    let root = mcc_tree.get_root_id();
+    assert_eq!(summaries[&ab].mean, 1.5);
+    assert_eq!((summaries[&ab].hpd_lower, summaries[&ab].hpd_upper), (1.0, 2.0));
+    assert_eq!(summaries[&abc].median, 2.0);
+    assert_eq!(summaries[&root].mean, 10.0 / 3.0);
+    assert_eq!(summaries[&a].mean, 0.0);
+    assert_eq!(mcc_tree.get_zeta(ab), Some(1.5));
+    assert!((mcc_tree.get_node(ab).unwrap().get_weight().unwrap() - 5.0 / 6.0).abs() < 1e-6);
+}
+
+#[test]
+fn adams_consensus_and_median() {
+    let trees = [
+        "(((A,X),B),(C,D));",
+        "((A,B),(C,(D,X)));",
+    ]
+    .into_iter()
+    .map(|newick| PhyloTree::from_newick(newick.as_bytes()).unwrap())
+    .collect_vec();
+    let strict = PhyloTree::consensus(&trees, ConsensusMethod::Strict, false).unwrap();
+    assert_eq!(strict.get_node_ids().filter(|id| !strict.is_leaf(*id)).count(), 1);
+    let adams = PhyloTree::adams_consensus(&trees).unwrap();
+    let expected = PhyloTree::from_newick("((A,B),X,(C,D));".as_bytes()).unwrap();
+    assert_eq!(adams.rf(&expected, SplitMode::Rooted), 0);
+    assert_eq!(adams.num_taxa(), 5);
+    assert!(PhyloTree::adams_consensus(&[]).is_err());
+
+    fn depth(tree: &PhyloTree, node_id: usize) -> f32 {
+        tree.depth(node_id) as f32
+    }
+    let mut trees = [
+        "(((A,B),C),(D,E));",
+        "(((A,B),D),(C,E));",
+        "(((A,C),B),(D,E));",
+    ]
+    .into_iter()
+    .map(|newick| PhyloTree::from_newick(newick.as_bytes()).unwrap())
+    .collect_vec();
+    assert!(PhyloTree::median_tree(&trees, MedianMetric::Cophenetic(1)).is_err());
+    for tree in trees.iter_mut() {
+        tree.precompute_constant_time_lca();
+        tree.set_zeta(depth).unwrap();
+    }
+    for metric in [
+        MedianMetric::RobinsonFoulds,
+        MedianMetric::ClusterAffinity,
+        MedianMetric::Cophenetic(1),
+    ] {
+        let median = PhyloTree::median_tree(&trees, metric).unwrap();
+        assert_eq!(median.rf(&trees[0], SplitMode::Rooted), 0);
+    }
+
+    // both trees have the same total distance, so the first is returned
+    let tied = ["((A,B),(C,D));", "((A,C),(B,D));"]
+        .into_iter()
+        .map(|newick| PhyloTree::from_newick(newick.as_bytes()).unwrap())
+        .collect_vec();
+    let median = PhyloTree::median_tree(&tied, MedianMetric::RobinsonFoulds).unwrap();
+    assert!(std::ptr::eq(median, &tied[0]));
+}
+
+#[test]
+fn neighbor_joining() {
+    let tree = PhyloTree::from_newick("(((A:1,B:2):1,C:3):2,(D:1,E:4):1,F:2);".as_bytes()).unwrap();
+    let matrix = PairwiseDistances::from_tree(&tree);
+    assert_eq!(matrix.num_taxa(), 6);
+    let nj_tree = PhyloTree::neighbor_joining(&matrix, RootPlacement::Arbitrary).unwrap();
+    assert_eq!(nj_tree.num_taxa(), 6);
+    assert_eq!(nj_tree.rf(&tree, SplitMode::Unrooted), 0);
+    let nj_matrix = PairwiseDistances::from_tree(&nj_tree);
+    for (x, y) in (0..6).tuple_combinations() {
+        let taxa_x = &matrix.taxa()[x];
+        let taxa_y = &matrix.taxa()[y];
+        let nj_x = nj_matrix.taxa().iter().position(|t| t == taxa_x).unwrap();
+        let nj_y = nj_matrix.taxa().iter().position(|t| t == taxa_y).unwrap();
+        assert!((nj_matrix.get(nj_x, nj_y) - matrix.get(x, y)).abs() < 1e-5);
+    }
+
+    let rooted = PhyloTree::neighbor_joining(&matrix, RootPlacement::Midpoint).unwrap();
+    assert_eq!(rooted.rf(&tree, SplitMode::Unrooted), 0);
+    assert_eq!(rooted.get_node_children_ids(rooted.get_root_id()).count(), 2);
+
+    let taxa = vec!["A".to_string(), "B".to_string()];
+    let pair = PairwiseDistances::from_rows(taxa.clone(), &[vec![0.0, 2.0], vec![2.0, 0.0]]).unwrap();
+    let pair_tree = PhyloTree::neighbor_joining(&pair, RootPlacement::Arbitrary).unwrap();
+    assert_eq!(pair_tree.num_taxa(), 2);
+    assert!(PairwiseDistances::from_rows(taxa.clone(), &[vec![0.0, 2.0], vec![1.0, 0.0]]).is_err());
+    assert!(PairwiseDistances::<String, f32>::from_rows(taxa, &[vec![0.0, 2.0]]).is_err());
+    let single = PairwiseDistances::<String, f32>::new(vec!["A".to_string()]);
+    assert!(PhyloTree::neighbor_joining(&single, RootPlacement::Arbitrary).is_err());
+}
+
+#[test]
+fn average_linkage() {
+    let taxa = ["A", "B", "C", "D"].map(String::from).to_vec();
+    let rows = vec![
+        vec![0.0, 2.0, 6.0, 10.0],
+        vec![2.0, 0.0, 6.0, 10.0],
+        vec![6.0, 6.0, 0.0, 12.0],
+        vec![10.0, 10.0, 12.0, 0.0],
+    ];
+    let matrix = PairwiseDistances::from_rows(taxa, &rows).unwrap();
+    let expected = PhyloTree::from_newick("(((A,B),C),D);".as_bytes()).unwrap();
+
+    let upgma = PhyloTree::upgma(&matrix).unwrap();
+    assert_eq!(upgma.rf(&expected, SplitMode::Rooted), 0);
+    assert_eq!(upgma.get_zeta(upgma.get_root_id()), Some(32.0 / 6.0));
+    let a = upgma.get_taxa_node_id(&"A".to_string()).unwrap();
+    let ab = upgma.get_node_parent_id(a).unwrap();
+    assert_eq!(upgma.get_zeta(a), Some(0.0));
+    assert_eq!(upgma.get_zeta(ab), Some(1.0));
+    assert_eq!(upgma.get_node(a).unwrap().get_weight(), Some(1.0));
+    // every leaf is at the same distance from the root
+    for leaf_id in upgma.get_leaf_ids() {
+        let length: f32 = upgma
+            .node_to_root_ids(leaf_id)
+            .filter_map(|id| upgma.get_node(id).unwrap().get_weight())
+            .sum();
+        assert!((length - 32.0 / 6.0).abs() < 1e-5);
+    }
+
+    let wpgma = PhyloTree::wpgma(&matrix).unwrap();
+    assert_eq!(wpgma.rf(&expected, SplitMode::Rooted), 0);
+    assert_eq!(wpgma.get_zeta(wpgma.get_root_id()), Some(5.5));
+    let single = PairwiseDistances::<String, f32>::new(vec!["A".to_string()]);
+    assert!(PhyloTree::upgma(&single).is_err());
+}
+
+#[test]
+fn balanced_minimum_evolution() {
+    let tree = PhyloTree::from_newick(
+        "((((A:1,B:2):1,C:3):2,(D:1,E:4):1):1,((F:2,G:1):2,(H:3,I:1):1):1);".as_bytes(),
+    )
+    .unwrap();
+    let matrix = PairwiseDistances::from_tree(&tree);
+    let bionj_tree = PhyloTree::bionj(&matrix, RootPlacement::Arbitrary).unwrap();
+    assert_eq!(bionj_tree.rf(&tree, SplitMode::Unrooted), 0);
+
+    // a caterpillar on the taxa in reverse order is far from the tree
+    let start = PhyloTree::from_newick("(((((((I,H),G),F),E),D),C),B,A);".as_bytes()).unwrap();
+    assert!(start.rf(&tree, SplitMode::Unrooted) > 0);
+    let spr_tree = PhyloTree::bme_refine(&start, &matrix, TopologySearch::Spr, RootPlacement::Midpoint).unwrap();
+    assert_eq!(spr_tree.rf(&tree, SplitMode::Unrooted), 0);
+    assert_eq!(spr_tree.get_node_children_ids(spr_tree.get_root_id()).count(), 2);
+    let fitted = PairwiseDistances::from_tree(&spr_tree);
+    for (x, y) in (0..matrix.num_taxa()).tuple_combinations() {
+        let fitted_x = fitted.taxa().iter().position(|t| t == &matrix.taxa()[x]).unwrap();
+        let fitted_y = fitted.taxa().iter().position(|t| t == &matrix.taxa()[y]).unwrap();
+        assert!((fitted.get(fitted_x, fitted_y) - matrix.get(x, y)).abs() < 1e-4);
+    }
+
+    // a single interchange away from the tree
+    let start = PhyloTree::from_newick("((((A,C),B),(D,E)),((F,G),(H,I)));".as_bytes()).unwrap();
+    let nni_tree = PhyloTree::bme_refine(&start, &matrix, TopologySearch::Nni, RootPlacement::Arbitrary).unwrap();
+    assert_eq!(nni_tree.rf(&tree, SplitMode::Unrooted), 0);
+    let bme_tree = PhyloTree::bme(&matrix, TopologySearch::Nni, RootPlacement::Arbitrary).unwrap();
+    assert_eq!(bme_tree.rf(&tree, SplitMode::Unrooted), 0);
+
+    // many rounds of moves from random starting trees, over updated balanced averages
+    use rand::{rngs::StdRng, SeedableRng};
+    let mut rng = StdRng::seed_from_u64(5);
+    for n in [12, 40, 100] {
+        let tree = PhyloTree::yule_with_rng(n, &mut rng);
+        let start = PhyloTree::yule_with_rng(n, &mut rng);
+        let matrix = PairwiseDistances::from_tree(&tree);
+        let spr_tree = PhyloTree::bme_refine(&start, &matrix, TopologySearch::Spr, RootPlacement::Arbitrary).unwrap();
+        assert_eq!(spr_tree.rf(&tree, SplitMode::Unrooted), 0);
+    }
+
+    let other = PhyloTree::from_newick("((A,B),(C,X));".as_bytes()).unwrap();
+    assert!(PhyloTree::bme_refine(&other, &matrix, TopologySearch::Nni, RootPlacement::Arbitrary).is_err());
+}
+
+#[test]
+fn birth_death() {
+    use rand::{rngs::StdRng, SeedableRng};
+    let mut rng = StdRng::seed_from_u64(7);
+    let rates = BirthDeathRates::new(1.0, 0.5).with_sampling_fraction(0.5);
+
+    let tree = PhyloTree::birth_death_with_rng(rates, BirthDeathCondition::NumTaxa(20), true, &mut rng).unwrap();
+    assert_eq!(tree.num_taxa(), 20);
+    assert!(tree.is_binary());
+    for leaf in tree.get_leaves() {
+        assert_eq!(leaf.get_zeta(), Some(0.0));
+    }
+    for node in tree.get_nodes().filter(|n| n.get_id() != tree.get_root_id()) {
+        let parent = tree.get_node_parent(node.get_id()).unwrap();
+        let age = node.get_zeta().unwrap() + node.get_weight().unwrap();
+        assert!((parent.get_zeta().unwrap() - age).abs() < 1e-4);
+    }
+
+    let tree = PhyloTree::birth_death_with_rng(rates, BirthDeathCondition::NumTaxa(20), false, &mut rng).unwrap();
+    let extant = tree.get_leaves().filter(|n| n.get_zeta() == Some(0.0)).collect_vec();
+    assert_eq!(extant.len(), 20);
+    assert!(tree.num_taxa() >= 20);
+    for leaf in extant {
+        assert!(leaf.get_taxa().unwrap().parse::<usize>().unwrap() < 20);
+    }
+    for node in tree.get_nodes().filter(|n| n.get_id() != tree.get_root_id()) {
+        let parent = tree.get_node_parent(node.get_id()).unwrap();
+        assert!(parent.get_zeta().unwrap() > node.get_zeta().unwrap());
+    }
+
+    let tree = PhyloTree::birth_death_with_rng(rates, BirthDeathCondition::CrownAge(3.0), true, &mut rng).unwrap();
+    assert_eq!(tree.get_node(tree.get_root_id()).unwrap().get_zeta(), Some(3.0));
+    assert_eq!(tree.get_node_children_ids(tree.get_root_id()).count(), 2);
+    for leaf in tree.get_leaves() {
+        assert_eq!(leaf.get_zeta(), Some(0.0));
+    }
+    let tree = PhyloTree::birth_death_with_rng(rates, BirthDeathCondition::CrownAge(3.0), false, &mut rng).unwrap();
+    assert_eq!(tree.get_node(tree.get_root_id()).unwrap().get_zeta(), Some(3.0));
+
+    assert!(PhyloTree::birth_death(BirthDeathRates::new(-1.0, 0.0), BirthDeathCondition::NumTaxa(5), true).is_err());
+    assert!(PhyloTree::birth_death(rates.with_sampling_fraction(0.0), BirthDeathCondition::NumTaxa(5), true).is_err());
+}
+
+#[test]
+fn coalescent() {
+    use rand::{rngs::StdRng, SeedableRng};
+    let mut rng = StdRng::seed_from_u64(11);
+
+    // expected time to the most recent common ancestor of n samples is 2N(1-1/n)
+    let root_height = |tree: &PhyloTree| tree.get_node(tree.get_root_id()).unwrap().get_zeta().unwrap() as f64;
+    let mean_height = (0..2000)
+        .map(|_| root_height(&PhyloTree::coalescent_with_rng(&[0.0; 10], PopulationSize::Constant(2.0), &mut rng).unwrap()))
+        .sum::<f64>()
+        / 2000.0;
+    assert!((mean_height - 3.6).abs() < 0.2);
+    let growth = PopulationSize::Exponential { size: 2.0, growth_rate: 5.0 };
+    let mean_growth_height = (0..2000)
+        .map(|_| root_height(&PhyloTree::coalescent_with_rng(&[0.0; 10], growth, &mut rng).unwrap()))
+        .sum::<f64>()
+        / 2000.0;
+    assert!(mean_growth_height < mean_height / 2.0);
+
+    let sampling_times = [0.0, 5.0, 10.0, 0.0, 2.5];
+    let tree = PhyloTree::coalescent_with_rng(&sampling_times, PopulationSize::Constant(1.0), &mut rng).unwrap();
+    assert_eq!(tree.num_taxa(), 5);
+    assert!(tree.is_binary());
+    assert!(root_height(&tree) > 10.0);
+    for leaf in tree.get_leaves() {
+        let idx = leaf.get_taxa().unwrap().parse::<usize>().unwrap();
+        assert_eq!(leaf.get_zeta().unwrap() as f64, sampling_times[idx]);
+    }
+    for node in tree.get_nodes().filter(|n| n.get_id() != tree.get_root_id()) {
+        let parent = tree.get_node_parent(node.get_id()).unwrap();
+        let height = node.get_zeta().unwrap() + node.get_weight().unwrap();
+        assert!((parent.get_zeta().unwrap() - height).abs() < 1e-4);
+    }
+    assert!(PhyloTree::coalescent(&[0.0], PopulationSize::Constant(1.0)).is_err());
+    assert!(PhyloTree::coalescent(&[0.0, 1.0], PopulationSize::Constant(0.0)).is_err());
+
+    let samples = [(0.0, 0), (0.0, 0), (1.0, 1), (0.0, 1), (2.0, 2)];
+    let population = StructuredPopulation::new(
+        vec![1.0, 2.0, 0.5],
+        vec![vec![0.0, 0.5, 0.1], vec![0.2, 0.0, 0.0], vec![0.0, 1.0, 0.0]],
+    );
+    let tree = PhyloTree::structured_coalescent_with_rng(&samples, &population, &mut rng).unwrap();
+    assert_eq!(tree.num_taxa(), 5);
+    assert!(tree.is_binary());
+    for leaf in tree.get_leaves() {
+        let idx = leaf.get_taxa().unwrap().parse::<usize>().unwrap();
+        assert_eq!(leaf.get_zeta().unwrap() as f64, samples[idx].0);
+    }
+    let isolated = StructuredPopulation::new(vec![1.0, 1.0], vec![vec![0.0; 2]; 2]);
+    assert!(PhyloTree::structured_coalescent(&[(0.0, 0), (0.0, 1)], &isolated).is_err());
+    assert!(PhyloTree::structured_coalescent(&[(0.0, 0), (0.0, 3)], &population).is_err());
+}
+
+#[test]
+fn multispecies_coalescent() {
+    use rand::{rngs::StdRng, SeedableRng};
+    let mut rng = StdRng::seed_from_u64(3);
+    let species_tree = PhyloTree::from_newick("((A:1,B:1):0.5,C:1.5);".as_bytes()).unwrap();
+
+    // with one sample per species, the gene tree matches the species tree with probability 1 - 2/3 exp(-0.5)
+    let samples = ["A", "B", "C"].map(|x| (x.to_string(), x.to_string()));
+    let concordant = (0..4000)
+        .filter(|_| {
+            let gene_tree = species_tree.multispecies_coalescent_with_rng(&samples, &mut rng).unwrap();
+            gene_tree.rf(&species_tree, SplitMode::Rooted) == 0
+        })
+        .count();
+    assert!((concordant as f64 / 4000.0 - (1.0 - 2.0 / 3.0 * (-0.5_f64).exp())).abs() < 0.03);
+
+    let samples = [("a1", "A"), ("a2", "A"), ("b1", "B"), ("c1", "C"), ("c2", "C")]
+        .map(|(x, y)| (x.to_string(), y.to_string()));
+    let gene_tree = species_tree.multispecies_coalescent_with_rng(&samples, &mut rng).unwrap();
+    assert_eq!(gene_tree.num_taxa(), 5);
+    assert!(gene_tree.is_binary());
+    assert!(gene_tree.get_node(gene_tree.get_root_id()).unwrap().get_zeta().unwrap() > 1.5);
+    for leaf in gene_tree.get_leaves() {
+        assert!(samples.iter().any(|(x, _)| Some(x) == leaf.get_taxa()));
+        assert_eq!(leaf.get_zeta(), Some(0.0));
+    }
+
+    let missing = [("a1", "A"), ("d1", "D")].map(|(x, y)| (x.to_string(), y.to_string()));
+    assert!(species_tree.multispecies_coalescent(&missing).is_err());
+    let unweighted = PhyloTree::from_newick("((A,B),C);".as_bytes()).unwrap();
+    assert!(unweighted.multispecies_coalescent(&samples).is_err());
+}
+
+#[test]
+fn seeded_simulation() {
+    use rand::{distributions::Uniform as UniformDistribution, rngs::StdRng, SeedableRng};
+    let newick = |tree: &PhyloTree| tree.to_newick().to_string();
+
+    let yule_1 = PhyloTree::yule_with_rng(30, &mut StdRng::seed_from_u64(5));
+    let yule_2 = PhyloTree::yule_with_rng(30, &mut StdRng::seed_from_u64(5));
+    assert_eq!(newick(&yule_1), newick(&yule_2));
+    let unif_1 = PhyloTree::unif_with_rng(30, &mut StdRng::seed_from_u64(5));
+    let unif_2 = PhyloTree::unif_with_rng(30, &mut StdRng::seed_from_u64(5));
+    assert_eq!(newick(&unif_1), newick(&unif_2));
+    let rates = BirthDeathRates::new(1.0, 0.3);
+    let birth_death_1 =
+        PhyloTree::birth_death_with_rng(rates, BirthDeathCondition::NumTaxa(30), false, &mut StdRng::seed_from_u64(5)).unwrap();
+    let birth_death_2 =
+        PhyloTree::birth_death_with_rng(rates, BirthDeathCondition::NumTaxa(30), false, &mut StdRng::seed_from_u64(5)).unwrap();
+    assert_eq!(newick(&birth_death_1), newick(&birth_death_2));
+
+    let mut tree = PhyloTree::yule_with_rng(5, &mut StdRng::seed_from_u64(5));
+    let taxa = ["A", "B", "C", "D", "E"].map(|x| x.to_string());
+    tree.set_simulated_taxa(&taxa).unwrap();
+    assert_eq!(tree.get_taxa_space().cloned().sorted().collect_vec(), taxa.to_vec());
+    assert!(tree.set_simulated_taxa(&taxa).is_err());
+
+    let mut rng = StdRng::seed_from_u64(5);
+    tree.set_exponential_edge_weights(2.0, &mut rng).unwrap();
+    assert!(tree
+        .get_nodes()
+        .filter(|n| n.get_id() != tree.get_root_id())
+        .all(|n| n.get_weight().unwrap() > 0.0));
+    tree.set_random_edge_weights(&UniformDistribution::new(1.0, 2.0), &mut rng);
+    assert!(tree
+        .get_nodes()
+        .filter(|n| n.get_id() != tree.get_root_id())
+        .all(|n| (1.0..2.0).contains(&n.get_weight().unwrap())));
+    assert!(tree.set_exponential_edge_weights(0.0, &mut rng).is_err());
+}
+
+#[test]
+fn relabel_overlapping_taxa() {
+    use rand::{rngs::StdRng, SeedableRng};
+    for (num_taxa, taxa) in [(3, vec!["1", "0"]), (6, vec!["3", "4", "5", "0", "1", "2"])] {
+        let mut tree = PhyloTree::yule_with_rng(num_taxa, &mut StdRng::seed_from_u64(5));
+        let taxa = taxa.into_iter().map(|x| x.to_string()).collect_vec();
+        let leaf_ids = (0..taxa.len())
+            .map(|idx| tree.get_taxa_node_id(&idx.to_string()).unwrap())
+            .collect_vec();
+        tree.set_simulated_taxa(&taxa).unwrap();
+        assert_eq!(tree.get_taxa_space().cloned().sorted().collect_vec(), taxa.iter().cloned().sorted().collect_vec());
+        for (leaf_id, new_taxa) in leaf_ids.into_iter().zip(taxa.iter()) {
+            assert_eq!(tree.get_node_taxa(leaf_id), Some(new_taxa));
+            assert_eq!(tree.get_taxa_node_id(new_taxa), Some(leaf_id));
+        }
+    }
+
+    // moving a taxa onto another node keeps it mapped when its former node is relabelled
+    let mut tree = PhyloTree::from_newick("((A,B),C);".as_bytes()).unwrap();
+    let a = tree.get_taxa_node_id(&"A".to_string()).unwrap();
+    let b = tree.get_taxa_node_id(&"B".to_string()).unwrap();
+    tree.set_node_taxa(b, Some("A".to_string()));
+    tree.set_node_taxa(a, Some("B".to_string()));
+    assert_eq!(tree.get_taxa_node_id(&"A".to_string()), Some(b));
+    assert_eq!(tree.get_taxa_node_id(&"B".to_string()), Some(a));
+    assert_eq!(tree.num_taxa(), 3);
+}
+
+#[test]
+fn weighted_robinson_foulds() {
+    let input_str: String = String::from("((A:1,B:2):3,(C:4,D:5):6);");
+    let t1 = PhyloTree::from_newick(input_str.as_bytes()).unwrap();
+    let input_str: String = String::from("(A:1,(B:2,(C:4,D:5):9):0);");
+    let t2 = PhyloTree::from_newick(input_str.as_bytes()).unwrap();
+    assert_eq!(t1.wrfs(&t1), 0.0);
+    assert_eq!(t1.wrfs(&t2), 0.0);
+
+    let input_str: String = String::from("((A:1,C:2):3,(B:4,D:5):6);");
+    let t2 = PhyloTree::from_newick(input_str.as_bytes()).unwrap();
+    assert_eq!(t1.wrfs(&t2), 22.0);
+    assert_eq!(t2.wrfs(&t1), 22.0);
+}
+
+#[test]
+fn branch_score() {
+    let input_str: String = String::from("((A:1,B:2):3,(C:4,D:5):6);");
+    let t1 = PhyloTree::from_newick(input_str.as_bytes()).unwrap();
+    let input_str: String = String::from("(A:1,(B:2,(C:4,D:5):9):0);");
+    let t2 = PhyloTree::from_newick(input_str.as_bytes()).unwrap();
+    assert_eq!(t1.branch_score(&t2, MissingWeightPolicy::Zero).unwrap(), 0.0);
+
+    let input_str: String = String::from("((A:1,C:2):3,(B:4,D:5):6);");
+    let t2 = PhyloTree::from_newick(input_str.as_bytes()).unwrap();
+    assert_eq!(t1.branch_score(&t2, MissingWeightPolicy::Zero).unwrap(), 170_f32.sqrt());
+
+    let input_str: String = String::from("((A:1,B:2),(C:4,D:5):6);");
+    let t2 = PhyloTree::from_newick(input_str.as_bytes()).unwrap();
+    assert_eq!(t1.branch_score(&t2, MissingWeightPolicy::Zero).unwrap(), 3.0);
+    assert!(matches!(
+        t1.branch_score(&t2, MissingWeightPolicy::Error),
+        Err(DistanceError::MissingEdgeWeight { .. })
+    ));
+}
+
 #[test]
 fn cluster_affinity() {
     let input_str: String = String::from("(((A,B),C),D);");